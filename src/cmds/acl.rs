@@ -0,0 +1,135 @@
+use crate::s3::{AclGrantee, S3Client};
+use crate::utils::colors;
+use crate::utils::output::OutputFormat;
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+
+const GROUPS_URI: &str = "http://acs.amazonaws.com/groups/";
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CannedAcl {
+    Private,
+    PublicRead,
+    PublicReadWrite,
+    AuthenticatedRead,
+    AwsExecRead,
+    BucketOwnerRead,
+    BucketOwnerFullControl,
+}
+
+impl CannedAcl {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Private => "private",
+            Self::PublicRead => "public-read",
+            Self::PublicReadWrite => "public-read-write",
+            Self::AuthenticatedRead => "authenticated-read",
+            Self::AwsExecRead => "aws-exec-read",
+            Self::BucketOwnerRead => "bucket-owner-read",
+            Self::BucketOwnerFullControl => "bucket-owner-full-control",
+        }
+    }
+}
+
+/// Explicit grants for `acl set`, as grantee lists per permission
+pub struct Grants {
+    pub read: Vec<String>,
+    pub read_acp: Vec<String>,
+    pub write_acp: Vec<String>,
+    pub full_control: Vec<String>,
+}
+
+/// Parses a grantee like `id=<canonical id>`, `uri=<group uri>` or
+/// `email=<address>` into the x-amz-grant-* header syntax.
+pub fn parse_grantee(value: &str) -> Result<String> {
+    let Some((kind, grantee)) = value.split_once('=') else {
+        bail!("Grantee must look like id=..., uri=... or email=...");
+    };
+    let kind = match kind {
+        "id" => "id",
+        "uri" => "uri",
+        "email" | "emailAddress" => "emailAddress",
+        _ => bail!("Unknown grantee type `{}`, expected id, uri or email", kind),
+    };
+    if grantee.is_empty() || grantee.contains('"') {
+        bail!("Invalid grantee `{}`", grantee);
+    }
+    Ok(format!("{}=\"{}\"", kind, grantee))
+}
+
+fn describe_grantee(grantee: &AclGrantee) -> String {
+    if let Some(uri) = &grantee.uri {
+        return match uri.strip_prefix(GROUPS_URI) {
+            Some("global/AllUsers") => "Everyone".to_string(),
+            Some("global/AuthenticatedUsers") => "Authenticated users".to_string(),
+            Some("s3/LogDelivery") => "Log delivery".to_string(),
+            _ => uri.clone(),
+        };
+    }
+    match (&grantee.display_name, &grantee.id, &grantee.email_address) {
+        (Some(name), Some(id), _) => format!("{} ({})", name, id),
+        (None, Some(id), _) => id.clone(),
+        (_, _, Some(email)) => email.clone(),
+        _ => "<unknown>".to_string(),
+    }
+}
+
+pub async fn get(path: String, output: OutputFormat, s3_client: &S3Client) -> Result<()> {
+    let acl = s3_client.get_acl(&path).await?;
+
+    match output {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&acl)?),
+        OutputFormat::Text => {
+            if let Some(owner) = &acl.owner {
+                println!("{}: {}", colors::fmt_info("Owner"), describe_grantee(owner));
+            }
+            println!("{}:", colors::fmt_info("Grants"));
+            for grant in &acl.grants {
+                println!(
+                    "  {}  {}",
+                    colors::fmt_success(&format!("{:<12}", grant.permission)),
+                    describe_grantee(&grant.grantee)
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn set(
+    path: String,
+    canned_acl: Option<CannedAcl>,
+    grants: Grants,
+    s3_client: &S3Client,
+) -> Result<()> {
+    let grants: Vec<(&str, String)> = [
+        ("read", grants.read),
+        ("read-acp", grants.read_acp),
+        ("write-acp", grants.write_acp),
+        ("full-control", grants.full_control),
+    ]
+    .into_iter()
+    .filter(|(_, grantees)| !grantees.is_empty())
+    .map(|(permission, grantees)| (permission, grantees.join(", ")))
+    .collect();
+
+    let has_grants = !grants.is_empty();
+    if canned_acl.is_some() == has_grants {
+        println!(
+            "{}",
+            colors::fmt_warn("Give either a canned ACL or --grant-* options, not both")
+        );
+        return Ok(());
+    }
+
+    s3_client
+        .put_acl(&path, canned_acl.map(CannedAcl::as_str), &grants)
+        .await?;
+    println!(
+        "{} `{}`",
+        colors::fmt_success("ACL updated for"),
+        colors::fmt_path(&path)
+    );
+    Ok(())
+}
@@ -0,0 +1,944 @@
+use crate::cmds::dl::{self, decoding_writer, Decryption};
+use crate::cmds::up;
+use crate::compress::Compression;
+use crate::config::Config;
+use crate::cse::{CseSettings, Envelope};
+use crate::s3::{PutOptions, S3Client};
+use crate::sse::SseSettings;
+use crate::utils::{colors, output, size};
+use anyhow::Result;
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Rows taken by the pane titles, the status line and the key bar
+const CHROME_ROWS: u16 = 3;
+
+/// Bytes read from the start of a file for its preview
+const PREVIEW_BYTES: u64 = 64 * 1024;
+
+const HELP: &str =
+    "Tab pane  Enter open  F3/v view  F4/i info  F5/c copy  F6/r rename  F8/d delete  q quit";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Remote,
+    Local,
+}
+
+struct Entry {
+    /// Name relative to the pane's directory, without a trailing slash
+    name: String,
+    is_dir: bool,
+    size: Option<u64>,
+}
+
+impl Entry {
+    fn parent() -> Self {
+        Self {
+            name: "..".to_string(),
+            is_dir: true,
+            size: None,
+        }
+    }
+
+    fn is_parent(&self) -> bool {
+        self.name == ".."
+    }
+}
+
+#[derive(Default)]
+struct Pane {
+    entries: Vec<Entry>,
+    selected: usize,
+    /// First entry shown, once the list is longer than the pane
+    offset: usize,
+}
+
+impl Pane {
+    /// Replaces the entries, selecting the one named `select` if given
+    fn set_entries(&mut self, entries: Vec<Entry>, select: Option<&str>) {
+        self.selected = select
+            .and_then(|name| entries.iter().position(|entry| entry.name == name))
+            .unwrap_or(0)
+            .min(entries.len().saturating_sub(1));
+        self.entries = entries;
+    }
+
+    fn selected(&self) -> Option<&Entry> {
+        self.entries.get(self.selected)
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        let last = self.entries.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+
+    fn scroll_into_view(&mut self, height: usize) {
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if height > 0 && self.selected >= self.offset + height {
+            self.offset = self.selected + 1 - height;
+        }
+    }
+}
+
+enum Mode {
+    Normal,
+    /// Waiting for y or n before deleting the selected entry
+    ConfirmDelete,
+    /// Editing the new name of the selected entry
+    Rename(String),
+    /// Full-screen text, a preview or metadata
+    View {
+        title: String,
+        lines: Vec<String>,
+        offset: usize,
+    },
+}
+
+struct Browser<'a> {
+    s3_client: &'a S3Client,
+    /// Keys for previews of client-side encrypted objects, kept so the
+    /// passphrase is only asked for once
+    decryption: Decryption<'a>,
+    bucket: String,
+    prefix: String,
+    local_dir: PathBuf,
+    remote: Pane,
+    local: Pane,
+    side: Side,
+    mode: Mode,
+    status: String,
+}
+
+fn enter_screen() -> Result<()> {
+    terminal::enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen, Hide)?;
+    Ok(())
+}
+
+fn leave_screen() -> Result<()> {
+    execute!(io::stdout(), LeaveAlternateScreen, Show)?;
+    terminal::disable_raw_mode()?;
+    Ok(())
+}
+
+/// Restores the terminal however the browser exits, errors included
+struct ScreenGuard;
+
+impl Drop for ScreenGuard {
+    fn drop(&mut self) {
+        let _ = leave_screen();
+    }
+}
+
+/// Cuts or pads `text` to exactly `width` characters
+fn fit(text: &str, width: usize) -> String {
+    let mut fitted: String = text.chars().take(width).collect();
+    let len = fitted.chars().count();
+    fitted.extend(std::iter::repeat_n(' ', width - len));
+    fitted
+}
+
+/// Parent of an S3 prefix, e.g. `a/` for `a/b/`
+fn parent_prefix(prefix: &str) -> String {
+    let trimmed = prefix.trim_end_matches('/');
+    match trimmed.rfind('/') {
+        Some(i) => trimmed[..=i].to_string(),
+        None => String::new(),
+    }
+}
+
+/// Collects one byte more than a preview shows, so a longer file can be
+/// told apart, then fails with `BrokenPipe` to stop the download
+#[derive(Default)]
+struct PreviewWriter {
+    data: Vec<u8>,
+}
+
+impl AsyncWrite for PreviewWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let room = (PREVIEW_BYTES as usize + 1).saturating_sub(this.data.len());
+        if room == 0 {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+        let n = buf.len().min(room);
+        this.data.extend_from_slice(&buf[..n]);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Splits a preview into lines, or `None` if it does not look like text
+fn text_lines(data: &[u8], truncated: bool) -> Option<Vec<String>> {
+    if data.contains(&0) {
+        return None;
+    }
+    let mut lines: Vec<String> = String::from_utf8_lossy(data)
+        .lines()
+        .map(|line| line.replace('\t', "    "))
+        .collect();
+    if truncated {
+        lines.push(format!(
+            "... only the first {} are shown",
+            size::format_size(PREVIEW_BYTES)
+        ));
+    }
+    Some(lines)
+}
+
+impl<'a> Browser<'a> {
+    fn new(
+        s3_client: &'a S3Client,
+        config: &'a Config,
+        prefix: String,
+        local_dir: PathBuf,
+    ) -> Self {
+        Self {
+            s3_client,
+            decryption: Decryption::new(CseSettings::default(), config),
+            bucket: s3_client.bucket_name(),
+            prefix,
+            local_dir,
+            remote: Pane::default(),
+            local: Pane::default(),
+            side: Side::Remote,
+            mode: Mode::Normal,
+            status: String::new(),
+        }
+    }
+
+    fn pane(&mut self) -> &mut Pane {
+        match self.side {
+            Side::Remote => &mut self.remote,
+            Side::Local => &mut self.local,
+        }
+    }
+
+    fn selected(&self) -> Option<&Entry> {
+        match self.side {
+            Side::Remote => self.remote.selected(),
+            Side::Local => self.local.selected(),
+        }
+        .filter(|entry| !entry.is_parent())
+    }
+
+    fn remote_key(&self, entry: &Entry) -> String {
+        if entry.is_dir {
+            format!("{}{}/", self.prefix, entry.name)
+        } else {
+            format!("{}{}", self.prefix, entry.name)
+        }
+    }
+
+    async fn load_remote(&mut self, select: Option<&str>) -> Result<()> {
+        let (prefixes, objects) = self.s3_client.list_dir(&self.prefix).await?;
+        let mut entries = Vec::new();
+        if !self.prefix.is_empty() {
+            entries.push(Entry::parent());
+        }
+        entries.extend(prefixes.iter().map(|prefix| {
+            Entry {
+                name: prefix[self.prefix.len()..]
+                    .trim_end_matches('/')
+                    .to_string(),
+                is_dir: true,
+                size: None,
+            }
+        }));
+        entries.extend(objects.into_iter().map(|object| Entry {
+            name: object.key[self.prefix.len()..].to_string(),
+            is_dir: false,
+            size: Some(object.size),
+        }));
+        self.remote.set_entries(entries, select);
+        Ok(())
+    }
+
+    async fn load_local(&mut self, select: Option<&str>) -> Result<()> {
+        let mut dirs = Vec::new();
+        let mut files = Vec::new();
+        let mut dir = fs::read_dir(&self.local_dir).await?;
+        while let Some(entry) = dir.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            let entry = Entry {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                is_dir: metadata.is_dir(),
+                size: (!metadata.is_dir()).then_some(metadata.len()),
+            };
+            if entry.is_dir {
+                dirs.push(entry);
+            } else {
+                files.push(entry);
+            }
+        }
+        dirs.sort_by(|a, b| a.name.cmp(&b.name));
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut entries = Vec::new();
+        if self.local_dir.parent().is_some() {
+            entries.push(Entry::parent());
+        }
+        entries.extend(dirs);
+        entries.extend(files);
+        self.local.set_entries(entries, select);
+        Ok(())
+    }
+
+    /// Opens the selected directory, or the parent for `..`
+    async fn open(&mut self) -> Result<()> {
+        let pane = match self.side {
+            Side::Remote => &self.remote,
+            Side::Local => &self.local,
+        };
+        let Some(entry) = pane.selected() else {
+            return Ok(());
+        };
+        if entry.is_parent() {
+            return self.go_up().await;
+        }
+        if !entry.is_dir {
+            return self.preview().await;
+        }
+
+        match self.side {
+            Side::Remote => {
+                self.prefix = self.remote_key(entry);
+                self.remote.offset = 0;
+                self.load_remote(None).await
+            }
+            Side::Local => {
+                self.local_dir = self.local_dir.join(&entry.name);
+                self.local.offset = 0;
+                self.load_local(None).await
+            }
+        }
+    }
+
+    async fn go_up(&mut self) -> Result<()> {
+        match self.side {
+            Side::Remote => {
+                if self.prefix.is_empty() {
+                    return Ok(());
+                }
+                let child = self.prefix.trim_end_matches('/');
+                let child = child.rsplit('/').next().unwrap_or(child).to_string();
+                self.prefix = parent_prefix(&self.prefix);
+                self.load_remote(Some(&child)).await
+            }
+            Side::Local => {
+                let Some(parent) = self.local_dir.parent().map(Path::to_path_buf) else {
+                    return Ok(());
+                };
+                let child = self
+                    .local_dir
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned());
+                self.local_dir = parent;
+                self.load_local(child.as_deref()).await
+            }
+        }
+    }
+
+    async fn refresh(&mut self) -> Result<()> {
+        let remote = self.remote.selected().map(|entry| entry.name.clone());
+        let local = self.local.selected().map(|entry| entry.name.clone());
+        self.load_remote(remote.as_deref()).await?;
+        self.load_local(local.as_deref()).await
+    }
+
+    async fn preview(&mut self) -> Result<()> {
+        let Some(entry) = self.selected() else {
+            return Ok(());
+        };
+        if entry.is_dir {
+            self.status = "Only files can be previewed".to_string();
+            return Ok(());
+        }
+        let name = entry.name.clone();
+
+        let (title, data, truncated) = match self.side {
+            Side::Remote => {
+                let key = self.remote_key(entry);
+                let (data, truncated) = self.remote_preview(&key).await?;
+                (format!("s3://{}/{}", self.bucket, key), data, truncated)
+            }
+            Side::Local => {
+                let path = self.local_dir.join(&entry.name);
+                let size = entry.size.unwrap_or(0);
+                let length = size.min(PREVIEW_BYTES);
+                let mut data = Vec::new();
+                fs::File::open(&path)
+                    .await?
+                    .take(length)
+                    .read_to_end(&mut data)
+                    .await?;
+                (path.display().to_string(), data, size > length)
+            }
+        };
+
+        match text_lines(&data, truncated) {
+            Some(lines) => {
+                self.mode = Mode::View {
+                    title,
+                    lines,
+                    offset: 0,
+                }
+            }
+            None => self.status = format!("{} is binary, no preview", name),
+        }
+        Ok(())
+    }
+
+    /// Reads the start of an object, decrypted and decompressed like `cat`
+    /// does, and whether there is more of it than the preview shows.
+    async fn remote_preview(&mut self, key: &str) -> Result<(Vec<u8>, bool)> {
+        let metadata = self.s3_client.head_object(key).await?;
+        let compression = Compression::of_object(&metadata, false)?;
+        let envelope = Envelope::from_metadata(&metadata.metadata)?;
+        if compression.is_none() && envelope.is_none() {
+            let length = metadata.size.min(PREVIEW_BYTES);
+            let data = if length == 0 {
+                Vec::new()
+            } else {
+                self.s3_client.get_range(key, 0, length - 1).await?
+            };
+            return Ok((data, metadata.size > length));
+        }
+
+        let cipher = match &envelope {
+            // The passphrase is asked for outside the browser, like copies do
+            Some(envelope) => {
+                leave_screen()?;
+                let cipher = self.decryption.cipher(envelope).await;
+                enter_screen()?;
+                Some(cipher?)
+            }
+            None => None,
+        };
+
+        // Decoding data from its start, stopping once the preview is full
+        let mut preview = PreviewWriter::default();
+        let result = async {
+            let mut writer =
+                decoding_writer(&mut preview, compression, envelope.as_ref().zip(cipher));
+            self.s3_client
+                .download_to_writer(key, &mut writer, PREVIEW_BYTES as usize, |_| {})
+                .await?;
+            writer.shutdown().await?;
+            anyhow::Ok(())
+        }
+        .await;
+        match result {
+            Err(e) if !output::is_broken_pipe(&e) => return Err(e),
+            _ => {}
+        }
+
+        let mut data = preview.data;
+        let truncated = data.len() as u64 > PREVIEW_BYTES;
+        data.truncate(PREVIEW_BYTES as usize);
+        Ok((data, truncated))
+    }
+
+    async fn info(&mut self) -> Result<()> {
+        let Some(entry) = self.selected() else {
+            return Ok(());
+        };
+
+        let mut lines = Vec::new();
+        let title = match self.side {
+            Side::Remote if entry.is_dir => {
+                let key = self.remote_key(entry);
+                let objects = self.s3_client.list_objects(Some(&key)).await?;
+                let files = objects.iter().filter(|o| !o.key.ends_with('/'));
+                let total: u64 = files.clone().map(|o| o.size).sum();
+                lines.push(format!("Objects: {}", files.count()));
+                lines.push(format!(
+                    "Total Size: {} ({})",
+                    size::format_size(total),
+                    total
+                ));
+                format!("s3://{}/{}", self.bucket, key)
+            }
+            Side::Remote => {
+                let key = self.remote_key(entry);
+                let meta = self.s3_client.head_object(&key).await?;
+                let fields = [
+                    ("Content-Type", &meta.content_type),
+                    ("Content-Encoding", &meta.content_encoding),
+                    ("Cache-Control", &meta.cache_control),
+                    ("ETag", &meta.e_tag),
+                    ("Last-Modified", &meta.last_modified),
+                    ("Storage Class", &meta.storage_class),
+                    ("Server-Side Encryption", &meta.server_side_encryption),
+                    ("Version ID", &meta.version_id),
+                ];
+                lines.push(format!(
+                    "Size: {} ({})",
+                    size::format_size(meta.size),
+                    meta.size
+                ));
+                for (name, value) in fields {
+                    lines.push(format!(
+                        "{}: {}",
+                        name,
+                        value.as_deref().unwrap_or("<not set>")
+                    ));
+                }
+                for (name, value) in &meta.metadata {
+                    lines.push(format!("x-amz-meta-{}: {}", name, value));
+                }
+                format!("s3://{}/{}", self.bucket, key)
+            }
+            Side::Local => {
+                let path = self.local_dir.join(&entry.name);
+                let metadata = fs::metadata(&path).await?;
+                lines.push(format!(
+                    "Type: {}",
+                    if metadata.is_dir() {
+                        "directory"
+                    } else {
+                        "file"
+                    }
+                ));
+                lines.push(format!(
+                    "Size: {} ({})",
+                    size::format_size(metadata.len()),
+                    metadata.len()
+                ));
+                if let Ok(modified) = metadata.modified() {
+                    lines.push(format!("Modified: {}", httpdate::fmt_http_date(modified)));
+                }
+                lines.push(format!("Read-only: {}", metadata.permissions().readonly()));
+                path.display().to_string()
+            }
+        };
+
+        self.mode = Mode::View {
+            title,
+            lines,
+            offset: 0,
+        };
+        Ok(())
+    }
+
+    /// Downloads the selected S3 entry into the local directory, or
+    /// uploads the selected local entry to the current prefix. The
+    /// transfer runs on the normal screen so its progress bars show.
+    async fn copy(&mut self) -> Result<()> {
+        let Some(entry) = self.selected() else {
+            return Ok(());
+        };
+        let name = entry.name.clone();
+        let is_dir = entry.is_dir;
+
+        leave_screen()?;
+        let result = match self.side {
+            Side::Remote => {
+                let key = self.remote_key(entry);
+                println!("Downloading {} to {}", key, self.local_dir.display());
+                dl::execute(
+                    key,
+                    self.local_dir.clone(),
+                    is_dir,
+                    None,
+                    None,
+                    None,
+                    SseSettings::default(),
+                    CseSettings::default(),
+                    false,
+                    self.s3_client,
+                )
+                .await
+            }
+            Side::Local => {
+                let path = self.local_dir.join(&name);
+                println!("Uploading {} to {}", path.display(), self.prefix);
+                up::execute(
+                    path,
+                    (!self.prefix.is_empty()).then(|| self.prefix.clone()),
+                    is_dir,
+                    None,
+                    None,
+                    PutOptions::default(),
+                    SseSettings::default(),
+                    None,
+                    false,
+                    CseSettings::default(),
+                    self.s3_client,
+                )
+                .await
+            }
+        };
+        enter_screen()?;
+
+        result?;
+        self.status = format!("Copied {}", name);
+        self.refresh().await
+    }
+
+    async fn delete(&mut self) -> Result<()> {
+        let Some(entry) = self.selected() else {
+            return Ok(());
+        };
+        let name = entry.name.clone();
+
+        match self.side {
+            Side::Remote => {
+                let key = self.remote_key(entry);
+                if entry.is_dir {
+                    for object in self.s3_client.list_objects_recursive(&key).await? {
+                        self.s3_client.delete(&object).await?;
+                    }
+                } else {
+                    self.s3_client.delete(&key).await?;
+                }
+            }
+            Side::Local => {
+                let path = self.local_dir.join(&entry.name);
+                if entry.is_dir {
+                    fs::remove_dir_all(&path).await?;
+                } else {
+                    fs::remove_file(&path).await?;
+                }
+            }
+        }
+
+        self.status = format!("Deleted {}", name);
+        self.refresh().await
+    }
+
+    async fn rename(&mut self, new_name: &str) -> Result<()> {
+        let Some(entry) = self.selected() else {
+            return Ok(());
+        };
+        let new_name = new_name.trim_end_matches('/');
+        if new_name.is_empty() || new_name == entry.name {
+            return Ok(());
+        }
+        let old_name = entry.name.clone();
+
+        match self.side {
+            Side::Remote => {
+                let source = self.remote_key(entry);
+                if entry.is_dir {
+                    let destination = format!("{}{}/", self.prefix, new_name);
+                    for key in self.s3_client.list_objects_recursive(&source).await? {
+                        let target = format!("{}{}", destination, &key[source.len()..]);
+                        self.s3_client.move_object(&key, &target).await?;
+                    }
+                } else {
+                    let destination = format!("{}{}", self.prefix, new_name);
+                    self.s3_client.move_object(&source, &destination).await?;
+                }
+                self.load_remote(Some(new_name)).await?;
+            }
+            Side::Local => {
+                fs::rename(
+                    self.local_dir.join(&entry.name),
+                    self.local_dir.join(new_name),
+                )
+                .await?;
+                self.load_local(Some(new_name)).await?;
+            }
+        }
+
+        self.status = format!("Renamed {} to {}", old_name, new_name);
+        Ok(())
+    }
+
+    /// Handles a key press, returning false once the browser should quit
+    async fn handle_key(&mut self, key: KeyEvent, page: isize) -> Result<bool> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        if ctrl && key.code == KeyCode::Char('c') {
+            return Ok(false);
+        }
+
+        match &mut self.mode {
+            Mode::View { lines, offset, .. } => {
+                let last = lines.len().saturating_sub(1);
+                match key.code {
+                    KeyCode::Up | KeyCode::Char('k') => *offset = offset.saturating_sub(1),
+                    KeyCode::Down | KeyCode::Char('j') => *offset = (*offset + 1).min(last),
+                    KeyCode::PageUp => *offset = offset.saturating_sub(page as usize),
+                    KeyCode::PageDown => *offset = (*offset + page as usize).min(last),
+                    KeyCode::Home => *offset = 0,
+                    KeyCode::End => *offset = last,
+                    KeyCode::Esc
+                    | KeyCode::Enter
+                    | KeyCode::F(3)
+                    | KeyCode::F(4)
+                    | KeyCode::Char('q') => self.mode = Mode::Normal,
+                    _ => {}
+                }
+                return Ok(true);
+            }
+            Mode::ConfirmDelete => {
+                self.mode = Mode::Normal;
+                if matches!(key.code, KeyCode::Char('y') | KeyCode::Char('Y')) {
+                    self.delete().await?;
+                } else {
+                    self.status = "Delete cancelled".to_string();
+                }
+                return Ok(true);
+            }
+            Mode::Rename(name) => {
+                match key.code {
+                    KeyCode::Char(c) => name.push(c),
+                    KeyCode::Backspace => {
+                        name.pop();
+                    }
+                    KeyCode::Enter => {
+                        let name = name.clone();
+                        self.mode = Mode::Normal;
+                        self.rename(&name).await?;
+                    }
+                    KeyCode::Esc => self.mode = Mode::Normal,
+                    _ => {}
+                }
+                return Ok(true);
+            }
+            Mode::Normal => {}
+        }
+
+        self.status.clear();
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc | KeyCode::F(10) => return Ok(false),
+            KeyCode::Tab | KeyCode::BackTab => {
+                self.side = match self.side {
+                    Side::Remote => Side::Local,
+                    Side::Local => Side::Remote,
+                }
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.pane().move_by(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.pane().move_by(1),
+            KeyCode::PageUp => self.pane().move_by(-page),
+            KeyCode::PageDown => self.pane().move_by(page),
+            KeyCode::Home => self.pane().selected = 0,
+            KeyCode::End => self.pane().move_by(isize::MAX),
+            KeyCode::Enter | KeyCode::Right => self.open().await?,
+            KeyCode::Backspace | KeyCode::Left => self.go_up().await?,
+            KeyCode::F(2) => self.refresh().await?,
+            KeyCode::Char('r') if ctrl => self.refresh().await?,
+            KeyCode::F(3) | KeyCode::Char('v') => self.preview().await?,
+            KeyCode::F(4) | KeyCode::Char('i') => self.info().await?,
+            KeyCode::F(5) | KeyCode::Char('c') => self.copy().await?,
+            KeyCode::F(6) | KeyCode::Char('r') => {
+                if let Some(entry) = self.selected() {
+                    self.mode = Mode::Rename(entry.name.clone());
+                }
+            }
+            KeyCode::F(8) | KeyCode::Delete | KeyCode::Char('d') if self.selected().is_some() => {
+                self.mode = Mode::ConfirmDelete;
+            }
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    fn draw_pane(
+        &mut self,
+        out: &mut impl Write,
+        side: Side,
+        x: u16,
+        width: u16,
+        height: u16,
+    ) -> Result<()> {
+        let active = side == self.side;
+        let (title, pane) = match side {
+            Side::Remote => (
+                format!("s3://{}/{}", self.bucket, self.prefix),
+                &mut self.remote,
+            ),
+            Side::Local => (self.local_dir.display().to_string(), &mut self.local),
+        };
+        let width = width as usize;
+        let rows = height.saturating_sub(CHROME_ROWS) as usize;
+        pane.scroll_into_view(rows);
+
+        queue!(out, MoveTo(x, 0))?;
+        if active {
+            queue!(out, SetAttribute(Attribute::Reverse))?;
+        }
+        queue!(
+            out,
+            Print(fit(&format!(" {}", title), width)),
+            SetAttribute(Attribute::Reset)
+        )?;
+
+        // Leave room for the size column and the gap between the panes
+        let name_width = width.saturating_sub(13);
+        for (row, (i, entry)) in pane
+            .entries
+            .iter()
+            .enumerate()
+            .skip(pane.offset)
+            .take(rows)
+            .enumerate()
+        {
+            let size = match entry.size {
+                Some(size) => size::format_size(size),
+                None if entry.is_parent() => String::new(),
+                None => "<DIR>".to_string(),
+            };
+            let name = if entry.is_dir && !entry.is_parent() {
+                format!("{}/", entry.name)
+            } else {
+                entry.name.clone()
+            };
+            let name = fit(&format!(" {}", name), name_width);
+            let name = if entry.is_dir {
+                colors::fmt_dir(&name)
+            } else {
+                colors::fmt_file(&name)
+            };
+
+            queue!(out, MoveTo(x, row as u16 + 1))?;
+            if i == pane.selected {
+                let attribute = if active {
+                    Attribute::Reverse
+                } else {
+                    Attribute::Underlined
+                };
+                queue!(out, SetAttribute(attribute))?;
+            }
+            queue!(
+                out,
+                Print(name),
+                Print(format!("{:>11} ", size)),
+                SetAttribute(Attribute::Reset)
+            )?;
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, out: &mut impl Write) -> Result<()> {
+        let (width, height) = terminal::size()?;
+        queue!(out, Clear(ClearType::All))?;
+
+        if let Mode::View {
+            title,
+            lines,
+            offset,
+        } = &self.mode
+        {
+            queue!(
+                out,
+                MoveTo(0, 0),
+                SetAttribute(Attribute::Reverse),
+                Print(fit(&format!(" {}", title), width as usize)),
+                SetAttribute(Attribute::Reset)
+            )?;
+            let rows = height.saturating_sub(2) as usize;
+            for (row, line) in lines.iter().skip(*offset).take(rows).enumerate() {
+                queue!(
+                    out,
+                    MoveTo(0, row as u16 + 1),
+                    Print(fit(line, width as usize))
+                )?;
+            }
+            queue!(
+                out,
+                MoveTo(0, height.saturating_sub(1)),
+                SetAttribute(Attribute::Reverse),
+                Print(fit(" Up/Down/PgUp/PgDn scroll  q close", width as usize)),
+                SetAttribute(Attribute::Reset)
+            )?;
+            out.flush()?;
+            return Ok(());
+        }
+
+        let left = width / 2;
+        self.draw_pane(out, Side::Remote, 0, left, height)?;
+        self.draw_pane(out, Side::Local, left, width - left, height)?;
+
+        let status = match &self.mode {
+            Mode::ConfirmDelete => {
+                let name = self.selected().map(|entry| entry.name.as_str());
+                colors::fmt_warn(&format!("Delete {}? (y/n)", name.unwrap_or("")))
+            }
+            Mode::Rename(name) => format!("Rename to: {}", name),
+            _ => self.status.clone(),
+        };
+        queue!(
+            out,
+            MoveTo(0, height.saturating_sub(2)),
+            Print(status),
+            MoveTo(0, height.saturating_sub(1)),
+            SetAttribute(Attribute::Reverse),
+            Print(fit(HELP, width as usize)),
+            SetAttribute(Attribute::Reset)
+        )?;
+        out.flush()?;
+        Ok(())
+    }
+
+    async fn run(&mut self) -> Result<()> {
+        let mut out = io::stdout();
+        loop {
+            self.draw(&mut out)?;
+
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            let (_, height) = terminal::size()?;
+            let page = height.saturating_sub(CHROME_ROWS).max(1) as isize;
+            match self.handle_key(key, page).await {
+                Ok(true) => {}
+                Ok(false) => return Ok(()),
+                // Failed operations are reported without leaving the browser
+                Err(e) => {
+                    self.mode = Mode::Normal;
+                    self.status = colors::fmt_error(&format!("{:#}", e));
+                }
+            }
+        }
+    }
+}
+
+pub async fn execute(
+    prefix: Option<String>,
+    local_dir: Option<PathBuf>,
+    s3_client: &S3Client,
+) -> Result<()> {
+    let prefix = crate::s3::dir_prefix(prefix.as_deref().unwrap_or(""));
+    let local_dir = match local_dir {
+        Some(dir) => dir,
+        None => std::env::current_dir()?,
+    };
+    let local_dir = std::fs::canonicalize(&local_dir)?;
+
+    let config = Config::load()?;
+    let mut browser = Browser::new(s3_client, &config, prefix, local_dir);
+    browser.load_remote(None).await?;
+    browser.load_local(None).await?;
+
+    enter_screen()?;
+    let _guard = ScreenGuard;
+    browser.run().await
+}
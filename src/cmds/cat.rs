@@ -1,9 +1,417 @@
+use crate::cmds::dl::decoding_writer;
+use crate::compress::Compression;
+use crate::config::Config;
+use crate::cse::{CseSettings, Envelope};
 use crate::s3::S3Client;
-use crate::utils::colors;
-use anyhow::Result;
+use crate::sse::SseSettings;
+use crate::utils::{colors, output};
+use anyhow::{anyhow, Result};
+use std::io;
 use std::path::PathBuf;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
 
-pub async fn execute(path: PathBuf, s3_client: &S3Client) -> Result<()> {
+/// Bytes shown on each line of a hex dump
+const HEX_LINE: usize = 16;
+
+/// Byte range given as `START-END` (inclusive), `START-` or `-LAST`
+#[derive(Debug, Clone, Copy)]
+pub struct ByteRange {
+    start: Option<u64>,
+    end: Option<u64>,
+}
+
+pub fn parse_range(s: &str) -> Result<ByteRange> {
+    let (start, end) = s
+        .split_once('-')
+        .ok_or_else(|| anyhow!("Expected START-END, START- or -LAST, got `{}`", s))?;
+    let parse = |value: &str| -> Result<Option<u64>> {
+        match value.trim() {
+            "" => Ok(None),
+            value => Ok(Some(value.parse()?)),
+        }
+    };
+    let range = ByteRange {
+        start: parse(start)?,
+        end: parse(end)?,
+    };
+    match (range.start, range.end) {
+        (None, None) => Err(anyhow!("Range `{}` has neither start nor end", s)),
+        (Some(start), Some(end)) if start > end => {
+            Err(anyhow!("Range start {} is after its end {}", start, end))
+        }
+        _ => Ok(range),
+    }
+}
+
+/// Part of the file to print
+#[derive(Debug, Clone, Copy)]
+pub enum Selection {
+    All,
+    Range(ByteRange),
+    HeadBytes(u64),
+    TailBytes(u64),
+    HeadLines(u64),
+    TailLines(u64),
+}
+
+/// What `SelectWriter` lets through, in terms of the bytes written to it
+#[derive(Debug, Clone, Copy)]
+enum Filter {
+    All,
+    /// Bytes `start..end`, to the end of the data without `end`
+    Range {
+        start: u64,
+        end: Option<u64>,
+    },
+    HeadLines(u64),
+    TailBytes(u64),
+    TailLines(u64),
+}
+
+impl Filter {
+    fn from_selection(selection: Selection) -> Self {
+        match selection {
+            Selection::All => Self::All,
+            Selection::Range(ByteRange {
+                start: None,
+                end: Some(last),
+            }) => Self::TailBytes(last),
+            Selection::Range(ByteRange { start, end }) => Self::Range {
+                start: start.unwrap_or(0),
+                end: end.map(|end| end + 1),
+            },
+            Selection::HeadBytes(count) => Self::Range {
+                start: 0,
+                end: Some(count),
+            },
+            Selection::TailBytes(count) => Self::TailBytes(count),
+            Selection::HeadLines(count) => Self::HeadLines(count),
+            Selection::TailLines(count) => Self::TailLines(count),
+        }
+    }
+}
+
+/// Offset at which the last `count` lines of `data` start. A newline
+/// ending the data ends the last line rather than starting a new one.
+fn tail_lines_offset(data: &[u8], count: u64) -> usize {
+    if count == 0 {
+        return data.len();
+    }
+    let body = data.strip_suffix(b"\n").unwrap_or(data);
+    let mut newlines = 0;
+    for (i, byte) in body.iter().enumerate().rev() {
+        if *byte == b'\n' {
+            newlines += 1;
+            if newlines == count {
+                return i + 1;
+            }
+        }
+    }
+    0
+}
+
+/// Lets only the selected part of the data through to `inner`. Once the
+/// selection is complete further writes fail with `BrokenPipe`, like a
+/// closed pipe would, so the download stops early.
+struct SelectWriter<W> {
+    inner: W,
+    filter: Filter,
+    /// Bytes written to this writer so far
+    position: u64,
+    lines: u64,
+    /// The end of the data, kept for tail selections
+    tail: Vec<u8>,
+    pending: Vec<u8>,
+    done: bool,
+}
+
+impl<W> SelectWriter<W> {
+    fn new(inner: W, filter: Filter) -> Self {
+        Self {
+            inner,
+            filter,
+            position: 0,
+            lines: 0,
+            tail: Vec::new(),
+            pending: Vec::new(),
+            done: false,
+        }
+    }
+
+    /// Picks the selected bytes of `buf` into `pending`
+    fn select(&mut self, buf: &[u8]) {
+        let position = self.position;
+        self.position += buf.len() as u64;
+        match self.filter {
+            Filter::All => self.pending.extend_from_slice(buf),
+            Filter::Range { start, end } => {
+                let from = start.saturating_sub(position).min(buf.len() as u64) as usize;
+                let to = match end {
+                    Some(end) => end.saturating_sub(position).min(buf.len() as u64) as usize,
+                    None => buf.len(),
+                };
+                if from < to {
+                    self.pending.extend_from_slice(&buf[from..to]);
+                }
+                self.done = end.is_some_and(|end| self.position >= end);
+            }
+            Filter::HeadLines(count) => {
+                let mut to = buf.len();
+                for (i, byte) in buf.iter().enumerate() {
+                    if self.lines == count {
+                        to = i;
+                        break;
+                    }
+                    if *byte == b'\n' {
+                        self.lines += 1;
+                    }
+                }
+                self.pending.extend_from_slice(&buf[..to]);
+                self.done = self.lines == count;
+            }
+            Filter::TailBytes(count) => {
+                self.tail.extend_from_slice(buf);
+                let excess = self.tail.len().saturating_sub(count as usize);
+                self.tail.drain(..excess);
+            }
+            Filter::TailLines(count) => {
+                self.tail.extend_from_slice(buf);
+                let offset = tail_lines_offset(&self.tail, count);
+                self.tail.drain(..offset);
+            }
+        }
+    }
+}
+
+impl<W: AsyncWrite + Unpin> SelectWriter<W> {
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.pending.is_empty() {
+            let n = ready!(Pin::new(&mut self.inner).poll_write(cx, &self.pending))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.pending.drain(..n);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for SelectWriter<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        if this.done {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+        this.select(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if !this.tail.is_empty() {
+            this.pending.append(&mut this.tail);
+        }
+        // The output is shut down by its owner, even when the selection
+        // ends early
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+}
+
+/// Formats everything written to it as a hex dump, with the offset, the
+/// bytes in hex and their printable characters on each line.
+struct HexWriter<W> {
+    inner: W,
+    offset: u64,
+    line: Vec<u8>,
+    pending: Vec<u8>,
+}
+
+impl<W> HexWriter<W> {
+    fn new(inner: W, offset: u64) -> Self {
+        Self {
+            inner,
+            offset,
+            line: Vec::with_capacity(HEX_LINE),
+            pending: Vec::new(),
+        }
+    }
+
+    fn format_line(&mut self) {
+        let mut hex = String::new();
+        for (i, byte) in self.line.iter().enumerate() {
+            if i == HEX_LINE / 2 {
+                hex.push(' ');
+            }
+            hex.push_str(&format!("{:02x} ", byte));
+        }
+        let text: String = self
+            .line
+            .iter()
+            .map(|byte| match byte {
+                0x20..=0x7e => *byte as char,
+                _ => '.',
+            })
+            .collect();
+        let line = format!("{:08x}  {:<49} |{}|\n", self.offset, hex, text);
+        self.pending.extend_from_slice(line.as_bytes());
+        self.offset += self.line.len() as u64;
+        self.line.clear();
+    }
+}
+
+impl<W: AsyncWrite + Unpin> HexWriter<W> {
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.pending.is_empty() {
+            let n = ready!(Pin::new(&mut self.inner).poll_write(cx, &self.pending))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.pending.drain(..n);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for HexWriter<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        for byte in buf {
+            this.line.push(*byte);
+            if this.line.len() == HEX_LINE {
+                this.format_line();
+            }
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if !this.line.is_empty() {
+            this.format_line();
+        }
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+/// Reads the last `count` lines of an object backwards, one chunk at a
+/// time, returning where they start along with their bytes.
+async fn tail_lines(
+    path: &str,
+    size: u64,
+    count: u64,
+    chunk_size: usize,
+    s3_client: &S3Client,
+) -> Result<(u64, Vec<u8>)> {
+    let mut chunks = Vec::new();
+    let mut newlines = 0;
+    let mut start = size;
+    while start > 0 && newlines < count {
+        let end = start;
+        start = end.saturating_sub(chunk_size as u64);
+        let mut chunk = s3_client.get_range(path, start, end - 1).await?;
+
+        // A newline ending the object ends the last line rather than
+        // starting a new one
+        let body = if end == size {
+            chunk.strip_suffix(b"\n").unwrap_or(&chunk)
+        } else {
+            &chunk
+        };
+        let mut found = None;
+        for (i, byte) in body.iter().enumerate().rev() {
+            if *byte == b'\n' {
+                newlines += 1;
+                if newlines == count {
+                    found = Some(i + 1);
+                    break;
+                }
+            }
+        }
+        if let Some(offset) = found {
+            chunk.drain(..offset);
+            start += offset as u64;
+        }
+        chunks.push(chunk);
+    }
+
+    chunks.reverse();
+    Ok((start, chunks.concat()))
+}
+
+/// Byte range `start..end` of a plain object holding the selection, when
+/// it can be read with ranged GETs, and the bytes at its start that had
+/// to be read already to find it.
+async fn plain_range(
+    path: &str,
+    size: u64,
+    selection: Selection,
+    chunk_size: usize,
+    s3_client: &S3Client,
+) -> Result<(u64, u64, Vec<u8>)> {
+    let (start, end) = match selection {
+        Selection::All | Selection::HeadLines(_) => (0, size),
+        Selection::Range(ByteRange { start: None, end }) => {
+            (size.saturating_sub(end.unwrap_or(0)), size)
+        }
+        Selection::Range(ByteRange {
+            start: Some(start),
+            end,
+        }) => {
+            let end = end.map_or(size, |end| (end + 1).min(size));
+            (start.min(end), end)
+        }
+        Selection::HeadBytes(count) => (0, count.min(size)),
+        Selection::TailBytes(count) => (size.saturating_sub(count), size),
+        Selection::TailLines(count) => {
+            let (start, data) = tail_lines(path, size, count, chunk_size, s3_client).await?;
+            return Ok((start, size, data));
+        }
+    };
+    Ok((start, end, Vec::new()))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn execute(
+    path: PathBuf,
+    version_id: Option<String>,
+    selection: Selection,
+    hex: bool,
+    sse: SseSettings,
+    cse: CseSettings,
+    decompress: bool,
+    s3_client: &S3Client,
+) -> Result<()> {
+    let config = Config::load()?;
+    let chunk_size = config.download_chunk_size;
+    let s3_client = &s3_client
+        .with_version_id(version_id)?
+        .with_encryption(sse.resolve(&config).await?)?;
     let path_str = match path.to_str() {
         Some(s) => s,
         None => {
@@ -15,28 +423,80 @@ pub async fn execute(path: PathBuf, s3_client: &S3Client) -> Result<()> {
         }
     };
 
-    let is_exists = s3_client.is_exists(path_str).await?;
+    let metadata = match s3_client.find_object(path_str).await? {
+        Some(metadata) if !path_str.ends_with('/') => metadata,
+        found => {
+            let message = if found.is_some() || s3_client.is_directory(path_str).await? {
+                format!("{} is a directory", path_str)
+            } else {
+                format!("File not found: {}", path_str)
+            };
+            println!("{}", colors::fmt_error(&message));
+            return Ok(());
+        }
+    };
 
-    if !is_exists {
-        println!(
-            "{}",
-            colors::fmt_error(&format!("File not found: {}", path_str))
-        );
-        return Ok(());
-    }
+    let compression = Compression::of_object(&metadata, decompress)?;
+    let envelope = Envelope::from_metadata(&metadata.metadata)?;
+    let transformed = compression.is_some() || envelope.is_some();
 
-    let is_dir = s3_client.is_directory(path_str).await?;
+    // Plain objects only download the selected bytes, encrypted or
+    // compressed data has to be decoded from its start
+    let (start, end, read) = if transformed {
+        (0, metadata.size, Vec::new())
+    } else {
+        plain_range(path_str, metadata.size, selection, chunk_size, s3_client).await?
+    };
+    let offset = match selection {
+        _ if !transformed => start,
+        Selection::Range(ByteRange {
+            start: Some(start), ..
+        }) => start,
+        _ => 0,
+    };
+    let mut output: Box<dyn AsyncWrite + Unpin + Send> = if hex {
+        Box::new(HexWriter::new(tokio::io::stdout(), offset))
+    } else {
+        Box::new(tokio::io::stdout())
+    };
 
-    if is_dir {
-        println!(
-            "{}",
-            colors::fmt_error(&format!("{} is a directory", path_str))
-        );
-        return Ok(());
+    let result = async {
+        if transformed {
+            let cipher = match &envelope {
+                Some(envelope) => Some(cse.resolve(&config).await?.for_download(envelope)?),
+                None => None,
+            };
+            let select = SelectWriter::new(&mut output, Filter::from_selection(selection));
+            let mut writer = decoding_writer(select, compression, envelope.as_ref().zip(cipher));
+            s3_client
+                .download_to_writer(path_str, &mut writer, chunk_size, |_| {})
+                .await?;
+            writer.shutdown().await?;
+        } else {
+            let filter = match selection {
+                Selection::HeadLines(count) => Filter::HeadLines(count),
+                _ => Filter::All,
+            };
+            let mut writer = SelectWriter::new(&mut output, filter);
+            writer.write_all(&read).await?;
+            let start = start + read.len() as u64;
+            s3_client
+                .download_range_to_writer(path_str, &mut writer, start, end, chunk_size, |_| {})
+                .await?;
+            writer.shutdown().await?;
+        }
+        anyhow::Ok(())
     }
+    .await;
 
-    let content = s3_client.cat(path_str).await?;
-    println!("{}", content);
-
-    Ok(())
+    // A broken pipe means the selection is complete, or whatever reads the
+    // output is gone
+    match result {
+        Err(e) if !output::is_broken_pipe(&e) => return Err(e),
+        _ => {}
+    }
+    match output.shutdown().await {
+        Err(e) if e.kind() != io::ErrorKind::BrokenPipe => Err(e.into()),
+        _ => Ok(()),
+    }
 }
@@ -0,0 +1,222 @@
+use crate::config::Config;
+use crate::s3::S3Client;
+use anyhow::Result;
+use clap::{Command, ValueEnum};
+use clap_complete::aot::{generate, Bash, Fish, Zsh};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long completing keys waits for S3 before giving up
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Seconds a cached listing is used before listing again
+const CACHE_TTL: u64 = 60;
+
+/// Listings kept in the cache, the oldest are dropped first
+const CACHE_LISTINGS: usize = 32;
+
+/// Larger listings are not cached to keep the cache file small
+const CACHE_MAX_NAMES: usize = 1000;
+
+const CACHE_FILE: &str = "completion-cache.json";
+
+/// Commands whose first argument is an S3 path
+const KEY_COMMANDS: [&str; 13] = [
+    "ls", "cat", "dl", "rm", "mv", "cp", "stat", "presign", "du", "tree", "find", "restore",
+    "undelete",
+];
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// Completes S3 paths by calling `s3mgr __complete`, falling back to the
+/// generated completions for everything else
+fn bash_keys() -> String {
+    format!(
+        r#"
+_s3mgr_keys() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}" prev="${{COMP_WORDS[COMP_CWORD-1]}}" remote=0
+    case "${{COMP_WORDS[1]}}" in
+        mv|cp) [[ $COMP_CWORD -le 3 ]] && remote=1 ;;
+        {}) [[ $COMP_CWORD -eq 2 ]] && remote=1 ;;
+        up) [[ $prev == -d || $prev == --destination ]] && remote=1 ;;
+    esac
+    if [[ $remote -eq 1 && $cur != -* ]]; then
+        local IFS=$'\n'
+        COMPREPLY=($(s3mgr __complete "$cur" 2>/dev/null))
+        if [[ ${{#COMPREPLY[@]}} -eq 1 && ${{COMPREPLY[0]}} == */ ]]; then
+            compopt -o nospace
+        fi
+        return 0
+    fi
+    _s3mgr "$@"
+}}
+complete -F _s3mgr_keys -o nosort -o bashdefault -o default s3mgr
+"#,
+        KEY_COMMANDS.join("|")
+    )
+}
+
+fn zsh_keys() -> String {
+    format!(
+        r#"
+_s3mgr_keys() {{
+    local remote=0
+    case ${{words[2]}} in
+        (mv|cp) (( CURRENT <= 4 )) && remote=1 ;;
+        ({}) (( CURRENT == 3 )) && remote=1 ;;
+        (up) [[ ${{words[CURRENT-1]}} == (-d|--destination) ]] && remote=1 ;;
+    esac
+    if (( remote )) && [[ ${{words[CURRENT]}} != -* ]]; then
+        local -a keys
+        keys=(${{(f)"$(s3mgr __complete "${{words[CURRENT]}}" 2>/dev/null)"}})
+        compadd -Q -S '' -- ${{(M)keys:#*/}}
+        compadd -Q -- ${{keys:#*/}}
+        return
+    fi
+    _s3mgr "$@"
+}}
+compdef _s3mgr_keys s3mgr
+"#,
+        KEY_COMMANDS.join("|")
+    )
+}
+
+fn fish_keys() -> String {
+    // dl keeps file completion for its local destination
+    let commands: Vec<&str> = KEY_COMMANDS
+        .iter()
+        .copied()
+        .filter(|command| *command != "dl")
+        .collect();
+    format!(
+        r#"
+function __s3mgr_keys
+    s3mgr __complete (commandline -ct) 2>/dev/null
+end
+complete -c s3mgr -n '__fish_seen_subcommand_from {}' -f -a '(__s3mgr_keys)'
+complete -c s3mgr -n '__fish_seen_subcommand_from dl' -a '(__s3mgr_keys)'
+complete -c s3mgr -n '__fish_seen_subcommand_from up' -s d -l destination -r -f -a '(__s3mgr_keys)'
+"#,
+        commands.join(" ")
+    )
+}
+
+pub fn execute(shell: CompletionShell, command: &mut Command) {
+    let mut stdout = io::stdout();
+    let keys = match shell {
+        CompletionShell::Bash => {
+            generate(Bash, command, "s3mgr", &mut stdout);
+            bash_keys()
+        }
+        CompletionShell::Zsh => {
+            generate(Zsh, command, "s3mgr", &mut stdout);
+            zsh_keys()
+        }
+        CompletionShell::Fish => {
+            generate(Fish, command, "s3mgr", &mut stdout);
+            fish_keys()
+        }
+    };
+    print!("{}", keys);
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedListing {
+    /// Bucket and listed prefix, e.g. `my-bucket/logs/2024`
+    location: String,
+    listed_at: u64,
+    /// Every key and sub-prefix under the prefix, up to the next `/`
+    keys: Vec<String>,
+}
+
+/// Recent complete delimiter listings, so completing the same word, or a
+/// longer one in the same directory, does not wait for S3
+#[derive(Default, Serialize, Deserialize)]
+struct Cache {
+    listings: Vec<CachedListing>,
+}
+
+impl Cache {
+    /// A missing or unreadable cache is simply empty
+    fn load(path: &Path) -> Self {
+        std::fs::read(path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+
+    /// A listing that holds every completion of `location`
+    fn get(&self, location: &str, now: u64) -> Option<&[String]> {
+        self.listings
+            .iter()
+            .find(|listing| {
+                location
+                    .strip_prefix(&listing.location)
+                    .is_some_and(|rest| !rest.contains('/'))
+                    && listing.listed_at + CACHE_TTL > now
+            })
+            .map(|listing| listing.keys.as_slice())
+    }
+
+    fn insert(&mut self, location: String, now: u64, keys: Vec<String>) {
+        self.listings
+            .retain(|listing| listing.location != location && listing.listed_at + CACHE_TTL > now);
+        self.listings.push(CachedListing {
+            location,
+            listed_at: now,
+            keys,
+        });
+        if self.listings.len() > CACHE_LISTINGS {
+            self.listings.remove(0);
+        }
+    }
+}
+
+/// Prints the keys and sub-prefixes starting with `word`, one per line,
+/// for the shell completion scripts. Only keys starting with `word` are
+/// listed, so large prefixes are not paged through. A slow or failed
+/// lookup prints nothing rather than hang the shell.
+pub async fn keys(word: String, s3_client: &S3Client) -> Result<()> {
+    let location = format!("{}/{}", s3_client.bucket_name(), word);
+    let cache_path = Config::dir()?.join(CACHE_FILE);
+    let mut cache = Cache::load(&cache_path);
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+    let keys = match cache.get(&location, now) {
+        Some(keys) => keys.to_vec(),
+        None => {
+            let listing = tokio::time::timeout(LOOKUP_TIMEOUT, s3_client.list_names(&word)).await;
+            let Ok(Ok((keys, truncated))) = listing else {
+                return Ok(());
+            };
+            // Only a complete listing can answer longer words later
+            if !truncated && keys.len() <= CACHE_MAX_NAMES {
+                cache.insert(location, now, keys.clone());
+                cache.save(&cache_path)?;
+            }
+            keys
+        }
+    };
+
+    let mut stdout = io::stdout().lock();
+    // The marker object of a directory is not a completion of it
+    let completions = keys
+        .iter()
+        .filter(|key| key.starts_with(&word) && !(**key == word && word.ends_with('/')));
+    for key in completions {
+        writeln!(stdout, "{}", key)?;
+    }
+    Ok(())
+}
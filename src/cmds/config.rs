@@ -1,6 +1,9 @@
 use crate::config::Config;
+use crate::sse::SseAlgorithm;
 use crate::utils::{colors, size};
-use anyhow::Result;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::path::PathBuf;
 
 fn display_config(config: &Config, show_all: bool) {
     println!("{}", colors::fmt_head("Current S3 Configuration:"));
@@ -35,6 +38,10 @@ fn display_config(config: &Config, show_all: bool) {
         "Endpoint: {}",
         colors::fmt_val(config.s3.endpoint.as_deref().unwrap_or(""), "<not set>")
     );
+    println!(
+        "Path Style: {}",
+        colors::fmt_info(if config.s3.path_style { "yes" } else { "no" })
+    );
     println!(
         "Upload Chunk Size: {}",
         colors::fmt_info(&format!(
@@ -51,12 +58,70 @@ fn display_config(config: &Config, show_all: bool) {
             config.download_chunk_size
         ))
     );
+    println!(
+        "Limit Rate: {}",
+        colors::fmt_val(
+            config.limit_rate.map(format_rate).unwrap_or_default(),
+            "<not set>"
+        )
+    );
+    println!(
+        "SSE: {}",
+        colors::fmt_val(
+            config.sse.map(SseAlgorithm::as_str).unwrap_or_default(),
+            "<not set>"
+        )
+    );
+    println!(
+        "SSE KMS Key ID: {}",
+        colors::fmt_val(
+            config.sse_kms_key_id.as_deref().unwrap_or_default(),
+            "<not set>"
+        )
+    );
+    println!(
+        "SSE-C Key File: {}",
+        colors::fmt_val(display_path(&config.sse_c_key), "<not set>")
+    );
+    println!(
+        "Encryption Key File: {}",
+        colors::fmt_val(display_path(&config.encryption_key_file), "<not set>")
+    );
+    if config.content_types.is_empty() {
+        println!("Content Types: {}", colors::fmt_val("", "<not set>"));
+    } else {
+        println!("Content Types:");
+        for (ext, content_type) in &config.content_types {
+            println!("  .{}: {}", ext, colors::fmt_info(content_type));
+        }
+    }
+}
+
+fn display_path(path: &Option<PathBuf>) -> String {
+    path.as_ref()
+        .map(|path| path.display().to_string())
+        .unwrap_or_default()
+}
+
+/// Turns a key file argument into the path to store, stored as an absolute
+/// path so it works from any directory. An empty value unsets it.
+fn key_file_setting(key_file: &str, label: &str) -> Result<Option<PathBuf>> {
+    if key_file.is_empty() {
+        return Ok(None);
+    }
+    let path = std::fs::canonicalize(key_file)
+        .with_context(|| format!("{} {} not found", label, key_file))?;
+    Ok(Some(path))
 }
 
 fn format_chunk_size(size: usize) -> String {
     format!("{} ({})", size::format_size(size as u64), size)
 }
 
+fn format_rate(rate: usize) -> String {
+    format!("{}/s", size::format_size(rate as u64))
+}
+
 fn handle_config_change<T: std::fmt::Display>(
     old_value: T,
     new_value: T,
@@ -83,14 +148,22 @@ fn handle_config_change<T: std::fmt::Display>(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn execute(
     access_key: Option<String>,
     secret_key: Option<String>,
     region: Option<String>,
     bucket: Option<String>,
     endpoint: Option<String>,
+    path_style: Option<bool>,
     upload_chunk_size: Option<usize>,
     download_chunk_size: Option<usize>,
+    limit_rate: Option<usize>,
+    content_type_map: Vec<String>,
+    sse: Option<String>,
+    sse_kms_key_id: Option<String>,
+    sse_c_key: Option<String>,
+    encryption_key_file: Option<String>,
     view: bool,
     show_all: bool,
     reset: bool,
@@ -159,6 +232,15 @@ pub fn execute(
         config.s3.endpoint = Some(end);
     }
 
+    if let Some(path_style) = path_style {
+        if let Some(change) =
+            handle_config_change(old_config.s3.path_style, path_style, "Path Style", true)
+        {
+            changes.push(change);
+        }
+        config.s3.path_style = path_style;
+    }
+
     if let Some(size) = upload_chunk_size {
         if let Some(change) = handle_config_change(
             &format_chunk_size(old_config.upload_chunk_size),
@@ -183,6 +265,101 @@ pub fn execute(
         config.download_chunk_size = size;
     }
 
+    if let Some(rate) = limit_rate {
+        let rate = (rate > 0).then_some(rate);
+        if let Some(change) = handle_config_change(
+            &old_config.limit_rate.map(format_rate).unwrap_or_default(),
+            &rate.map(format_rate).unwrap_or_default(),
+            "Limit Rate",
+            true,
+        ) {
+            changes.push(change);
+        }
+        config.limit_rate = rate;
+    }
+
+    if let Some(sse) = sse {
+        let sse = match sse.as_str() {
+            "none" => None,
+            sse => Some(SseAlgorithm::from_str(sse, false).map_err(anyhow::Error::msg)?),
+        };
+        if let Some(change) = handle_config_change(
+            old_config.sse.map(SseAlgorithm::as_str).unwrap_or_default(),
+            sse.map(SseAlgorithm::as_str).unwrap_or_default(),
+            "SSE",
+            true,
+        ) {
+            changes.push(change);
+        }
+        config.sse = sse;
+    }
+
+    if let Some(key_id) = sse_kms_key_id {
+        let key_id = (!key_id.is_empty()).then_some(key_id);
+        if let Some(change) = handle_config_change(
+            old_config.sse_kms_key_id.as_deref().unwrap_or_default(),
+            key_id.as_deref().unwrap_or_default(),
+            "SSE KMS Key ID",
+            true,
+        ) {
+            changes.push(change);
+        }
+        config.sse_kms_key_id = key_id;
+    }
+
+    if let Some(key_file) = sse_c_key {
+        let key_file = key_file_setting(&key_file, "SSE-C key file")?;
+        if let Some(change) = handle_config_change(
+            display_path(&old_config.sse_c_key),
+            display_path(&key_file),
+            "SSE-C Key File",
+            true,
+        ) {
+            changes.push(change);
+        }
+        config.sse_c_key = key_file;
+    }
+
+    if let Some(key_file) = encryption_key_file {
+        let key_file = key_file_setting(&key_file, "Encryption key file")?;
+        if let Some(change) = handle_config_change(
+            display_path(&old_config.encryption_key_file),
+            display_path(&key_file),
+            "Encryption Key File",
+            true,
+        ) {
+            changes.push(change);
+        }
+        config.encryption_key_file = key_file;
+    }
+
+    for mapping in content_type_map {
+        let Some((ext, content_type)) = mapping.split_once('=') else {
+            println!(
+                "{}",
+                colors::fmt_warn(&format!("Invalid mapping `{}`, expected EXT=TYPE", mapping))
+            );
+            return Ok(());
+        };
+        let ext = ext.trim().trim_start_matches('.').to_lowercase();
+        let content_type = content_type.trim();
+        let old = config.content_types.get(&ext).cloned().unwrap_or_default();
+
+        if let Some(change) = handle_config_change(
+            old.as_str(),
+            content_type,
+            &format!("Content Type .{}", ext),
+            true,
+        ) {
+            changes.push(change);
+        }
+        if content_type.is_empty() {
+            config.content_types.remove(&ext);
+        } else {
+            config.content_types.insert(ext, content_type.to_string());
+        }
+    }
+
     if !changes.is_empty() {
         println!("{}", colors::fmt_head("Configuration Changes:"));
         for change in changes {
@@ -0,0 +1,203 @@
+use crate::s3::{CorsConfiguration, CorsRule, S3Client};
+use crate::utils::colors;
+use crate::utils::output::RulesFormat;
+use anyhow::Result;
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+/// Maximum number of rules S3 accepts in one CORS configuration
+const MAX_RULES: usize = 100;
+/// Maximum length of a rule ID
+const MAX_ID_LENGTH: usize = 255;
+const ALLOWED_METHODS: [&str; 5] = ["GET", "PUT", "POST", "DELETE", "HEAD"];
+
+fn validate_rule(rule: &CorsRule) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    if let Some(id) = &rule.id {
+        if id.is_empty() || id.len() > MAX_ID_LENGTH {
+            problems.push(format!("ID must be 1 to {} characters", MAX_ID_LENGTH));
+        }
+    }
+
+    if rule.allowed_origins.is_empty() {
+        problems.push("AllowedOrigin needs at least one origin".to_string());
+    }
+    for origin in &rule.allowed_origins {
+        if origin.matches('*').count() > 1 {
+            problems.push(format!(
+                "AllowedOrigin `{}` can contain at most one wildcard",
+                origin
+            ));
+        }
+    }
+
+    if rule.allowed_methods.is_empty() {
+        problems.push("AllowedMethod needs at least one method".to_string());
+    }
+    for method in &rule.allowed_methods {
+        if !ALLOWED_METHODS.contains(&method.as_str()) {
+            problems.push(format!(
+                "AllowedMethod `{}` must be one of {}",
+                method,
+                ALLOWED_METHODS.join(", ")
+            ));
+        }
+    }
+
+    for header in &rule.allowed_headers {
+        if header.matches('*').count() > 1 {
+            problems.push(format!(
+                "AllowedHeader `{}` can contain at most one wildcard",
+                header
+            ));
+        }
+    }
+    for header in &rule.expose_headers {
+        if header.contains('*') {
+            problems.push(format!("ExposeHeader `{}` cannot be a wildcard", header));
+        }
+    }
+
+    problems
+}
+
+/// Returns every problem S3 would reject the configuration for, prefixed
+/// with the rule it was found in.
+fn validate(configuration: &CorsConfiguration) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    if configuration.rules.is_empty() {
+        problems.push("Configuration has no rules".to_string());
+    }
+    if configuration.rules.len() > MAX_RULES {
+        problems.push(format!("At most {} rules are allowed", MAX_RULES));
+    }
+
+    let mut ids = HashSet::new();
+    for (index, rule) in configuration.rules.iter().enumerate() {
+        let name = match &rule.id {
+            Some(id) => format!("Rule `{}`", id),
+            None => format!("Rule #{}", index + 1),
+        };
+        if let Some(id) = &rule.id {
+            if !ids.insert(id) {
+                problems.push(format!("{}: duplicate ID", name));
+            }
+        }
+        problems.extend(
+            validate_rule(rule)
+                .into_iter()
+                .map(|problem| format!("{}: {}", name, problem)),
+        );
+    }
+
+    problems
+}
+
+pub async fn get(format: RulesFormat, s3_client: &S3Client) -> Result<()> {
+    let Some(configuration) = s3_client.get_cors().await? else {
+        println!("{}", colors::fmt_info("No CORS rules found"));
+        return Ok(());
+    };
+
+    print!("{}", format.render(&configuration)?);
+    Ok(())
+}
+
+pub async fn set(file: PathBuf, s3_client: &S3Client) -> Result<()> {
+    let configuration: CorsConfiguration = RulesFormat::read_file(&file).await?;
+
+    let problems = validate(&configuration);
+    if !problems.is_empty() {
+        println!("{}", colors::fmt_error("Invalid CORS configuration:"));
+        for problem in &problems {
+            println!("  {}", colors::fmt_warn(problem));
+        }
+        return Ok(());
+    }
+
+    s3_client.put_cors(&configuration).await?;
+    println!(
+        "{} {} {}",
+        colors::fmt_success("Applied"),
+        configuration.rules.len(),
+        colors::fmt_success("CORS rule(s)")
+    );
+    Ok(())
+}
+
+pub async fn rm(s3_client: &S3Client) -> Result<()> {
+    s3_client.delete_cors().await?;
+    println!("{}", colors::fmt_success("CORS rules removed"));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(id: &str) -> CorsRule {
+        CorsRule {
+            id: Some(id.to_string()),
+            allowed_origins: vec!["https://*.example.com".to_string()],
+            allowed_methods: vec!["GET".to_string(), "HEAD".to_string()],
+            allowed_headers: vec!["*".to_string()],
+            expose_headers: vec!["ETag".to_string()],
+            max_age_seconds: Some(3600),
+        }
+    }
+
+    #[test]
+    fn accepts_a_valid_configuration() {
+        let configuration = CorsConfiguration {
+            rules: vec![rule("site"), rule("cdn")],
+        };
+        assert!(validate(&configuration).is_empty());
+    }
+
+    #[test]
+    fn refuses_an_empty_configuration() {
+        assert_eq!(
+            validate(&CorsConfiguration::default()),
+            ["Configuration has no rules"]
+        );
+    }
+
+    #[test]
+    fn names_the_rule_with_a_problem() {
+        let mut bad = rule("bad");
+        bad.allowed_origins = vec!["https://*.*.example.com".to_string()];
+        bad.allowed_methods = vec!["PATCH".to_string()];
+        bad.expose_headers = vec!["*".to_string()];
+        let configuration = CorsConfiguration {
+            rules: vec![rule("site"), bad],
+        };
+        assert_eq!(
+            validate(&configuration),
+            [
+                "Rule `bad`: AllowedOrigin `https://*.*.example.com` can contain at most one wildcard",
+                "Rule `bad`: AllowedMethod `PATCH` must be one of GET, PUT, POST, DELETE, HEAD",
+                "Rule `bad`: ExposeHeader `*` cannot be a wildcard",
+            ]
+        );
+    }
+
+    #[test]
+    fn refuses_missing_origins_methods_and_duplicate_ids() {
+        let mut empty = rule("site");
+        empty.allowed_origins.clear();
+        empty.allowed_methods.clear();
+        let configuration = CorsConfiguration {
+            rules: vec![rule("site"), empty],
+        };
+        assert_eq!(
+            validate(&configuration),
+            [
+                "Rule `site`: duplicate ID",
+                "Rule `site`: AllowedOrigin needs at least one origin",
+                "Rule `site`: AllowedMethod needs at least one method",
+            ]
+        );
+    }
+}
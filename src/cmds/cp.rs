@@ -1,20 +1,85 @@
-use crate::s3::S3Client;
-use crate::utils::colors;
+use crate::config::Config;
+use crate::s3::{PutOptions, S3Client};
+use crate::sse::SseSettings;
+use crate::utils::{colors, key};
 use anyhow::{Context, Result};
+use clap::ValueEnum;
 use std::path::PathBuf;
 
-pub async fn execute(source: PathBuf, destination: PathBuf, s3_client: &S3Client) -> Result<()> {
-    s3_client
-        .copy_object(
-            source.to_str().context("Invalid source path")?,
-            destination.to_str().context("Invalid destination path")?,
-        )
-        .await?;
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MetadataDirective {
+    #[value(name = "COPY")]
+    Copy,
+    #[value(name = "REPLACE")]
+    Replace,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn execute(
+    source: PathBuf,
+    destination: PathBuf,
+    limit_rate: Option<usize>,
+    metadata_directive: Option<MetadataDirective>,
+    version_id: Option<String>,
+    put_options: PutOptions,
+    sse: SseSettings,
+    s3_client: &S3Client,
+) -> Result<()> {
+    // CopyObject with COPY keeps every header of the source, only the ACL
+    // can still be set
+    let sets_headers = put_options.content_type.is_some()
+        || put_options.cache_control.is_some()
+        || put_options.content_disposition.is_some()
+        || put_options.content_encoding.is_some()
+        || put_options.content_language.is_some()
+        || put_options.expires.is_some()
+        || !put_options.metadata.is_empty();
+    if metadata_directive == Some(MetadataDirective::Copy) && sets_headers {
+        println!(
+            "{}",
+            colors::fmt_warn(
+                "--metadata-directive COPY keeps the source's headers, use REPLACE to set them"
+            )
+        );
+        return Ok(());
+    }
+
+    let config = Config::load()?;
+    let source_key = match sse.customer_source_key.clone() {
+        Some(path) => Some(key::read_file(&path, "SSE-C source key").await?),
+        None => None,
+    };
+    let s3_client = &s3_client
+        .with_rate_limit(limit_rate.or(config.limit_rate))
+        .with_version_id(version_id)?
+        .with_encryption(sse.resolve(&config).await?)?
+        .with_copy_source_key(source_key)?;
+    let source = source.to_str().context("Invalid source path")?;
+    let destination = destination.to_str().context("Invalid destination path")?;
+
+    match metadata_directive {
+        None => {
+            s3_client
+                .copy_object(source, destination, &put_options)
+                .await?
+        }
+        Some(directive) => {
+            s3_client
+                .copy_object_server_side(
+                    source,
+                    destination,
+                    directive == MetadataDirective::Replace,
+                    &put_options,
+                )
+                .await?
+        }
+    }
+
     println!(
         "`{}` {} `{}`",
-        colors::fmt_path(source.to_str().context("Invalid source path")?),
+        colors::fmt_path(source),
         colors::fmt_success("copied to"),
-        colors::fmt_path(destination.to_str().context("Invalid destination path")?)
+        colors::fmt_path(destination)
     );
     Ok(())
 }
@@ -1,21 +1,145 @@
+use crate::compress::Compression;
 use crate::config::Config;
+use crate::cse::{CseSettings, DecryptWriter, Envelope, Keys};
 use crate::s3::S3Client;
+use crate::sse::SseSettings;
 use crate::utils::{colors, progress};
+use aes_gcm::Aes256Gcm;
 use anyhow::Result;
 use std::path::{Path, PathBuf};
 use tokio::fs;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
 
+/// Client-side encryption keys, only resolved once an encrypted object
+/// shows up so plain downloads never ask for a passphrase.
+pub struct Decryption<'a> {
+    settings: CseSettings,
+    config: &'a Config,
+    keys: Option<Keys>,
+}
+
+impl<'a> Decryption<'a> {
+    pub fn new(settings: CseSettings, config: &'a Config) -> Self {
+        Self {
+            settings,
+            config,
+            keys: None,
+        }
+    }
+
+    async fn keys(&mut self) -> Result<&Keys> {
+        if self.keys.is_none() {
+            self.keys = Some(self.settings.clone().resolve(self.config).await?);
+        }
+        Ok(self.keys.as_ref().unwrap())
+    }
+
+    /// Cipher of an encrypted object, resolving the keys the first time
+    pub async fn cipher(&mut self, envelope: &Envelope) -> Result<Aes256Gcm> {
+        self.keys().await?.for_download(envelope)
+    }
+}
+
+/// Wraps `writer` so the object's data written to it is decrypted, then
+/// decompressed, the reverse of what `up` applies.
+pub fn decoding_writer<'a, W>(
+    writer: W,
+    compression: Option<Compression>,
+    decryption: Option<(&Envelope, Aes256Gcm)>,
+) -> Box<dyn AsyncWrite + Unpin + Send + 'a>
+where
+    W: AsyncWrite + Unpin + Send + 'a,
+{
+    let mut writer: Box<dyn AsyncWrite + Unpin + Send + 'a> = Box::new(writer);
+    if let Some(compression) = compression {
+        writer = compression.decoder(writer);
+    }
+    if let Some((envelope, cipher)) = decryption {
+        writer = Box::new(DecryptWriter::new(writer, envelope, cipher));
+    }
+    writer
+}
+
+pub async fn download_file(
+    s3_path: &str,
+    local_path: &Path,
+    chunk_size: usize,
+    decompress: bool,
+    decryption: &mut Decryption<'_>,
+    s3_client: &S3Client,
+) -> Result<()> {
+    let metadata = s3_client.head_object(s3_path).await?;
+    let pb = progress::create_download_progress_bar(metadata.size, &colors::fmt_path(s3_path));
+
+    let compression = Compression::of_object(&metadata, decompress)?;
+    let envelope = Envelope::from_metadata(&metadata.metadata)?;
+    if compression.is_none() && envelope.is_none() {
+        s3_client
+            .download_with_progress(s3_path, local_path, chunk_size, |downloaded| {
+                pb.set_position(downloaded);
+            })
+            .await?;
+        pb.finish();
+        return Ok(());
+    }
+
+    let cipher = match &envelope {
+        Some(envelope) => Some(decryption.cipher(envelope).await?),
+        None => None,
+    };
+    let file = fs::File::create(local_path).await?;
+    let mut writer = decoding_writer(file, compression, envelope.as_ref().zip(cipher));
+    let result = async {
+        s3_client
+            .download_to_writer(s3_path, &mut writer, chunk_size, |downloaded| {
+                pb.set_position(downloaded);
+            })
+            .await?;
+        writer.shutdown().await?;
+        anyhow::Ok(())
+    }
+    .await;
+    if result.is_err() {
+        // Never leave a partly decoded file behind
+        let _ = fs::remove_file(local_path).await;
+    }
+    result?;
+
+    pb.finish();
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn execute(
     source: String,
     destination: PathBuf,
     recursive: bool,
     chunk_size: Option<usize>,
+    limit_rate: Option<usize>,
+    version_id: Option<String>,
+    sse: SseSettings,
+    cse: CseSettings,
+    decompress: bool,
     s3_client: &S3Client,
 ) -> Result<()> {
     let config = Config::load()?;
     let chunk_size = chunk_size.unwrap_or(config.download_chunk_size);
+    if recursive && version_id.is_some() {
+        println!(
+            "{}",
+            colors::fmt_warn("--version-id can only be used to download a single file")
+        );
+        return Ok(());
+    }
+    let is_versioned = version_id.is_some();
+    let s3_client = &s3_client
+        .with_rate_limit(limit_rate.or(config.limit_rate))
+        .with_version_id(version_id)?
+        .with_encryption(sse.resolve(&config).await?)?;
+    let mut decryption = Decryption::new(cse, &config);
 
-    let is_dir = s3_client.is_directory(&source).await?;
+    // A specific version always names a file, even if its key is gone now
+    let is_dir = !is_versioned && s3_client.is_directory(&source).await?;
     if is_dir && !recursive {
         println!(
             "{}",
@@ -29,7 +153,7 @@ pub async fn execute(
         let source_name = source
             .trim_end_matches('/')
             .split('/')
-            .last()
+            .next_back()
             .unwrap_or(&source);
         for object in objects {
             let relative_path = if object == source {
@@ -56,24 +180,20 @@ pub async fn execute(
                 continue;
             }
 
-            let file_size = s3_client.get_object_size(&object).await?;
-            let pb = progress::create_download_progress_bar(
-                file_size,
-                &format!("{}", colors::fmt_path(&object)),
-            );
-
-            s3_client
-                .download_with_progress(&object, &local_path, chunk_size, |downloaded| {
-                    pb.set_position(downloaded);
-                })
-                .await?;
-
-            pb.finish();
+            download_file(
+                &object,
+                &local_path,
+                chunk_size,
+                decompress,
+                &mut decryption,
+                s3_client,
+            )
+            .await?;
         }
         return Ok(());
     }
 
-    let file_name = source.split('/').last().unwrap_or(&source);
+    let file_name = source.split('/').next_back().unwrap_or(&source);
     let actual_destination = if destination == Path::new(".") {
         PathBuf::from(file_name)
     } else {
@@ -88,18 +208,13 @@ pub async fn execute(
         }
     };
 
-    let file_size = s3_client.get_object_size(&source).await?;
-    let pb = progress::create_download_progress_bar(
-        file_size,
-        &format!("{}", colors::fmt_path(&source)),
-    );
-
-    s3_client
-        .download_with_progress(&source, &actual_destination, chunk_size, |downloaded| {
-            pb.set_position(downloaded);
-        })
-        .await?;
-
-    pb.finish();
-    Ok(())
+    download_file(
+        &source,
+        &actual_destination,
+        chunk_size,
+        decompress,
+        &mut decryption,
+        s3_client,
+    )
+    .await
 }
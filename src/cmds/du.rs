@@ -0,0 +1,151 @@
+use crate::s3::{self, S3Client};
+use crate::utils::{colors, size};
+use anyhow::Result;
+use clap::ValueEnum;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+/// Order of the sub-prefixes printed by `du`
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SortBy {
+    /// Alphabetically, so sub-prefixes follow their parent
+    Name,
+    /// Largest first
+    Size,
+}
+
+#[derive(Default)]
+struct ClassUsage {
+    objects: usize,
+    size: u64,
+}
+
+#[derive(Default)]
+struct Usage {
+    objects: usize,
+    size: u64,
+    storage_classes: BTreeMap<String, ClassUsage>,
+}
+
+impl Usage {
+    fn add(&mut self, size: u64, storage_class: &str) {
+        self.objects += 1;
+        self.size += size;
+        let class = self
+            .storage_classes
+            .entry(storage_class.to_string())
+            .or_default();
+        class.objects += 1;
+        class.size += size;
+    }
+}
+
+/// Sub-prefixes holding `key` below `prefix`, at most `depth` levels deep,
+/// e.g. `a/` and `a/b/` for `a/b/c.txt`
+fn parent_prefixes<'a>(key: &'a str, prefix: &str, depth: usize) -> impl Iterator<Item = &'a str> {
+    let start = prefix.len();
+    key[start..]
+        .match_indices('/')
+        .take(depth)
+        .map(move |(i, _)| &key[..start + i + 1])
+}
+
+/// Lists the size and storage class of every object under `prefix`,
+/// including noncurrent versions if asked to
+async fn list_sizes(
+    prefix: &str,
+    versions: bool,
+    s3_client: &S3Client,
+) -> Result<Vec<(String, u64, Option<String>)>> {
+    if versions {
+        let versions = s3_client.list_versions(prefix).await?;
+        return Ok(versions
+            .into_iter()
+            .filter(|version| !version.is_delete_marker)
+            .map(|version| (version.key, version.size, version.storage_class))
+            .collect());
+    }
+
+    let objects = s3_client.list_objects(Some(prefix)).await?;
+    Ok(objects
+        .into_iter()
+        .map(|object| (object.key, object.size, object.storage_class))
+        .collect())
+}
+
+fn print_usage(name: &str, usage: &Usage, by_storage_class: bool) {
+    println!(
+        "{:>10}  {:>8}  {}",
+        size::format_size(usage.size),
+        usage.objects,
+        colors::fmt_dir_path(name)
+    );
+    if by_storage_class {
+        for (class, stats) in &usage.storage_classes {
+            println!(
+                "{:>10}  {:>8}    {}",
+                size::format_size(stats.size),
+                stats.objects,
+                colors::fmt_info(class)
+            );
+        }
+    }
+}
+
+pub async fn execute(
+    path: Option<String>,
+    depth: usize,
+    sort: SortBy,
+    summarize: bool,
+    by_storage_class: bool,
+    versions: bool,
+    s3_client: &S3Client,
+) -> Result<()> {
+    let prefix = s3::dir_prefix(path.as_deref().unwrap_or(""));
+    let depth = if summarize { 0 } else { depth };
+
+    let objects = list_sizes(&prefix, versions, s3_client).await?;
+
+    let mut total = Usage::default();
+    let mut prefixes: BTreeMap<&str, Usage> = BTreeMap::new();
+    for (key, size, storage_class) in &objects {
+        // Directory markers hold no data
+        if key.ends_with('/') {
+            continue;
+        }
+
+        let storage_class = storage_class
+            .as_deref()
+            .unwrap_or(s3::DEFAULT_STORAGE_CLASS);
+        total.add(*size, storage_class);
+        for parent in parent_prefixes(key, &prefix, depth) {
+            prefixes
+                .entry(parent)
+                .or_default()
+                .add(*size, storage_class);
+        }
+    }
+
+    if total.objects == 0 {
+        println!("{}", colors::fmt_info("No files found"));
+        return Ok(());
+    }
+
+    let mut prefixes: Vec<(&str, Usage)> = prefixes.into_iter().collect();
+    if sort == SortBy::Size {
+        // The sort is stable, so equal sizes stay in name order
+        prefixes.sort_by_key(|(_, usage)| Reverse(usage.size));
+    }
+
+    println!(
+        "{}",
+        colors::fmt_head(&format!("{:>10}  {:>8}  {}", "Size", "Objects", "Prefix"))
+    );
+    for (name, usage) in &prefixes {
+        print_usage(name, usage, by_storage_class);
+    }
+    let name = if prefix.is_empty() { "/" } else { &prefix };
+    print_usage(name, &total, by_storage_class);
+
+    Ok(())
+}
@@ -0,0 +1,429 @@
+use crate::cmds::dl::{self, Decryption};
+use crate::config::Config;
+use crate::cse::CseSettings;
+use crate::s3::{self, S3Client};
+use crate::utils::{colors, output, server, size};
+use ::s3::serde_types::Object;
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::Command;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use tokio::fs;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// How a `-size` or `-mtime` value compares, from its `+` or `-` sign
+#[derive(Clone, Copy)]
+enum Comparison {
+    MoreThan,
+    LessThan,
+    Exactly,
+}
+
+impl Comparison {
+    fn split(value: &str) -> (Self, &str) {
+        if let Some(rest) = value.strip_prefix('+') {
+            (Self::MoreThan, rest)
+        } else if let Some(rest) = value.strip_prefix('-') {
+            (Self::LessThan, rest)
+        } else {
+            (Self::Exactly, value)
+        }
+    }
+
+    fn matches(self, value: u64, limit: u64) -> bool {
+        match self {
+            Self::MoreThan => value > limit,
+            Self::LessThan => value < limit,
+            Self::Exactly => value == limit,
+        }
+    }
+}
+
+enum Predicate {
+    /// `-name` or `-iname`, matched against the last path component
+    Name(Regex),
+    /// `-regex`, matched against the whole key
+    Regex(Regex),
+    Size(Comparison, u64),
+    /// Age in whole days, like find's `-mtime`
+    Mtime(Comparison, u64),
+    StorageClass(String),
+    /// A tag key, and the value it must have if one was given
+    Tag(String, Option<String>),
+    Directory(bool),
+}
+
+enum Action {
+    Print,
+    Print0,
+    Delete,
+    ExecDl(PathBuf),
+    Exec(Vec<String>),
+}
+
+struct Expression {
+    predicates: Vec<Predicate>,
+    actions: Vec<Action>,
+}
+
+/// A listed object, or a directory only implied by the keys below it
+struct Entry {
+    key: String,
+    size: u64,
+    last_modified: Option<String>,
+    storage_class: Option<String>,
+    /// Whether an object exists for the key, false for implied directories
+    is_object: bool,
+}
+
+impl Entry {
+    fn is_dir(&self) -> bool {
+        self.key.ends_with('/')
+    }
+
+    fn name(&self) -> &str {
+        let key = self.key.trim_end_matches('/');
+        key.rsplit('/').next().unwrap_or(key)
+    }
+}
+
+/// Translates a shell glob into an anchored regex. `*` and `?` match any
+/// characters, `[...]` and `[!...]` match a set.
+fn glob_regex(glob: &str, ignore_case: bool) -> Result<Regex> {
+    let mut pattern = String::from(if ignore_case { "(?si)^" } else { "(?s)^" });
+    let mut chars = glob.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => pattern.push_str(".*"),
+            '?' => pattern.push('.'),
+            '[' => {
+                pattern.push('[');
+                let mut set = chars.by_ref().take_while(|c| *c != ']').peekable();
+                if set.next_if(|c| *c == '!').is_some() {
+                    pattern.push('^');
+                }
+                for c in set {
+                    if c == '\\' || c == '[' {
+                        pattern.push('\\');
+                    }
+                    pattern.push(c);
+                }
+                pattern.push(']');
+            }
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+    Regex::new(&pattern).with_context(|| format!("Invalid pattern {}", glob))
+}
+
+fn value<'a>(args: &mut impl Iterator<Item = &'a String>, option: &str) -> Result<&'a str> {
+    match args.next() {
+        Some(value) => Ok(value),
+        None => bail!("{} needs a value", option),
+    }
+}
+
+fn parse_expression(args: &[String]) -> Result<Expression> {
+    let mut predicates = Vec::new();
+    let mut actions = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-name" => predicates.push(Predicate::Name(glob_regex(value(&mut args, arg)?, false)?)),
+            "-iname" => predicates.push(Predicate::Name(glob_regex(value(&mut args, arg)?, true)?)),
+            "-regex" => {
+                let regex = value(&mut args, arg)?;
+                let regex = Regex::new(&format!("^(?:{})$", regex))
+                    .with_context(|| format!("Invalid regex {}", regex))?;
+                predicates.push(Predicate::Regex(regex));
+            }
+            "-size" => {
+                let (comparison, limit) = Comparison::split(value(&mut args, arg)?);
+                let limit = size::parse_human_size(limit)? as u64;
+                predicates.push(Predicate::Size(comparison, limit));
+            }
+            "-mtime" => {
+                let days = value(&mut args, arg)?;
+                let (comparison, limit) = Comparison::split(days);
+                let limit = limit
+                    .parse()
+                    .with_context(|| format!("Invalid number of days {}", days))?;
+                predicates.push(Predicate::Mtime(comparison, limit));
+            }
+            "-storage-class" => predicates.push(Predicate::StorageClass(
+                value(&mut args, arg)?.to_uppercase(),
+            )),
+            "-tag" => {
+                let tag = value(&mut args, arg)?;
+                predicates.push(match tag.split_once('=') {
+                    Some((key, value)) => Predicate::Tag(key.to_string(), Some(value.to_string())),
+                    None => Predicate::Tag(tag.to_string(), None),
+                });
+            }
+            "-type" => match value(&mut args, arg)? {
+                "f" => predicates.push(Predicate::Directory(false)),
+                "d" => predicates.push(Predicate::Directory(true)),
+                other => bail!("-type must be f or d, not {}", other),
+            },
+            "-print" => actions.push(Action::Print),
+            "-print0" => actions.push(Action::Print0),
+            "-delete" => actions.push(Action::Delete),
+            "-exec-dl" => actions.push(Action::ExecDl(PathBuf::from(value(&mut args, arg)?))),
+            "-exec" => {
+                let command: Vec<String> = args
+                    .by_ref()
+                    .take_while(|arg| *arg != ";")
+                    .cloned()
+                    .collect();
+                if command.is_empty() {
+                    bail!("-exec needs a command");
+                }
+                actions.push(Action::Exec(command));
+            }
+            other => bail!("Unknown predicate {}", other),
+        }
+    }
+
+    if actions.is_empty() {
+        actions.push(Action::Print);
+    }
+    Ok(Expression {
+        predicates,
+        actions,
+    })
+}
+
+impl Predicate {
+    fn matches(&self, entry: &Entry, now: OffsetDateTime) -> bool {
+        match self {
+            Self::Name(regex) => regex.is_match(entry.name()),
+            Self::Regex(regex) => regex.is_match(&entry.key),
+            Self::Size(comparison, limit) => comparison.matches(entry.size, *limit),
+            Self::Mtime(comparison, limit) => entry
+                .last_modified
+                .as_deref()
+                .and_then(|modified| OffsetDateTime::parse(modified, &Rfc3339).ok())
+                .is_some_and(|modified| {
+                    let days = (now - modified).whole_seconds().max(0) / SECONDS_PER_DAY;
+                    comparison.matches(days as u64, *limit)
+                }),
+            Self::StorageClass(class) => {
+                entry.is_object
+                    && entry
+                        .storage_class
+                        .as_deref()
+                        .unwrap_or(s3::DEFAULT_STORAGE_CLASS)
+                        == class
+            }
+            // Tags take a request per object, so they are checked last
+            Self::Tag(..) => true,
+            Self::Directory(is_dir) => entry.is_dir() == *is_dir,
+        }
+    }
+}
+
+impl Expression {
+    async fn matches(
+        &self,
+        entry: &Entry,
+        now: OffsetDateTime,
+        s3_client: &S3Client,
+    ) -> Result<bool> {
+        if !self
+            .predicates
+            .iter()
+            .all(|predicate| predicate.matches(entry, now))
+        {
+            return Ok(false);
+        }
+
+        let mut tags = self
+            .predicates
+            .iter()
+            .filter_map(|predicate| match predicate {
+                Predicate::Tag(key, value) => Some((key, value)),
+                _ => None,
+            })
+            .peekable();
+        if tags.peek().is_none() {
+            return Ok(true);
+        }
+        if !entry.is_object {
+            return Ok(false);
+        }
+
+        let object_tags = s3_client.get_tags(&entry.key).await?;
+        Ok(tags.all(|(key, value)| {
+            object_tags.iter().any(|(tag_key, tag_value)| {
+                tag_key == key && value.as_ref().is_none_or(|value| tag_value == value)
+            })
+        }))
+    }
+}
+
+/// Turns a page of objects into entries, adding the directories their keys
+/// imply. Keys are listed in order, so the keys below a directory are
+/// contiguous and comparing with the previous key is enough to tell
+/// whether a directory was seen already.
+fn entries(objects: Vec<Object>, prefix: &str, previous_key: &mut String) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    for object in objects {
+        let relative = &object.key[prefix.len()..];
+        // The prefix's own directory marker is the starting point
+        if relative.is_empty() {
+            continue;
+        }
+
+        for (i, _) in relative.match_indices('/') {
+            let end = prefix.len() + i + 1;
+            let dir = &object.key[..end];
+            if end < object.key.len() && !previous_key.starts_with(dir) {
+                entries.push(Entry {
+                    key: dir.to_string(),
+                    size: 0,
+                    last_modified: None,
+                    storage_class: None,
+                    is_object: false,
+                });
+            }
+        }
+
+        previous_key.clone_from(&object.key);
+        entries.push(Entry {
+            key: object.key,
+            size: object.size,
+            last_modified: Some(object.last_modified),
+            storage_class: object.storage_class,
+            is_object: true,
+        });
+    }
+    entries
+}
+
+/// Runs `action` on `entry`. Like in find, a command that fails counts as
+/// false and skips the actions after it.
+async fn run_action(
+    action: &Action,
+    entry: &Entry,
+    prefix: &str,
+    chunk_size: usize,
+    decryption: &mut Decryption<'_>,
+    s3_client: &S3Client,
+) -> Result<bool> {
+    match action {
+        Action::Print => writeln!(io::stdout(), "{}", entry.key)?,
+        Action::Print0 => write!(io::stdout(), "{}\0", entry.key)?,
+        Action::Delete => {
+            if entry.is_object {
+                s3_client.delete(&entry.key).await?;
+            }
+        }
+        Action::ExecDl(dir) => {
+            let relative = &entry.key[prefix.len()..];
+            // Keys are arbitrary strings, and `..` or a leading `/` would
+            // write outside the directory
+            if !server::is_plain_path(relative) {
+                println!(
+                    "{} `{}`",
+                    colors::fmt_warn("Skipping a key that is not a safe local path:"),
+                    colors::fmt_path(&entry.key)
+                );
+                return Ok(false);
+            }
+            let local_path = dir.join(relative);
+            if entry.is_dir() {
+                fs::create_dir_all(&local_path).await?;
+            } else {
+                if let Some(parent) = local_path.parent() {
+                    fs::create_dir_all(parent).await?;
+                }
+                dl::download_file(
+                    &entry.key,
+                    &local_path,
+                    chunk_size,
+                    false,
+                    decryption,
+                    s3_client,
+                )
+                .await?;
+            }
+        }
+        Action::Exec(command) => {
+            let args: Vec<String> = command
+                .iter()
+                .map(|arg| arg.replace("{}", &entry.key))
+                .collect();
+            let status = Command::new(&args[0])
+                .args(&args[1..])
+                .status()
+                .with_context(|| format!("Failed to run {}", args[0]))?;
+            return Ok(status.success());
+        }
+    }
+    Ok(true)
+}
+
+pub async fn execute(prefix: String, expression: Vec<String>, s3_client: &S3Client) -> Result<()> {
+    let expression = match parse_expression(&expression) {
+        Ok(expression) => expression,
+        Err(e) => {
+            println!("{}", colors::fmt_warn(&e.to_string()));
+            return Ok(());
+        }
+    };
+
+    let config = Config::load()?;
+    let mut decryption = Decryption::new(CseSettings::default(), &config);
+    let prefix = if prefix == "/" {
+        String::new()
+    } else {
+        s3::dir_prefix(&prefix)
+    };
+    let now = OffsetDateTime::now_utc();
+
+    let result = async {
+        let mut previous_key = String::new();
+        let mut continuation_token = None;
+        loop {
+            let (objects, next_token) = s3_client.list_page(&prefix, continuation_token).await?;
+            for entry in entries(objects, &prefix, &mut previous_key) {
+                if !expression.matches(&entry, now, s3_client).await? {
+                    continue;
+                }
+                for action in &expression.actions {
+                    let done = run_action(
+                        action,
+                        &entry,
+                        &prefix,
+                        config.download_chunk_size,
+                        &mut decryption,
+                        s3_client,
+                    )
+                    .await?;
+                    if !done {
+                        break;
+                    }
+                }
+            }
+
+            match next_token {
+                Some(token) => continuation_token = Some(token),
+                None => break,
+            }
+        }
+        io::stdout().flush()?;
+        anyhow::Ok(())
+    }
+    .await;
+
+    match result {
+        Err(e) if !output::is_broken_pipe(&e) => Err(e),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,36 @@
+use crate::config::Config;
+use crate::gateway;
+use crate::s3::S3Client;
+use crate::sse::SseSettings;
+use crate::utils::colors;
+use anyhow::Result;
+use std::net::SocketAddr;
+
+pub async fn execute(
+    listen: SocketAddr,
+    prefix: Option<String>,
+    s3_client: &S3Client,
+) -> Result<()> {
+    let config = Config::load()?;
+    let s3_client = s3_client.with_encryption(SseSettings::default().resolve(&config).await?)?;
+    let prefix = prefix.unwrap_or_default();
+
+    println!(
+        "{}",
+        colors::fmt_info(&format!(
+            "Serving {}/{} read-only at http://{}",
+            s3_client.bucket_name(),
+            prefix.trim_start_matches('/'),
+            listen
+        ))
+    );
+    if !listen.ip().is_loopback() {
+        println!(
+            "{}",
+            colors::fmt_warn(
+                "Requests are not authenticated, anyone reaching this address can read the objects"
+            )
+        );
+    }
+    gateway::run(s3_client, &prefix, config.download_chunk_size, listen).await
+}
@@ -0,0 +1,403 @@
+use crate::s3::S3Client;
+use crate::utils::colors;
+use crate::utils::output::RulesFormat;
+use anyhow::Result;
+use s3::serde_types::{
+    AbortIncompleteMultipartUpload, BucketLifecycleConfiguration, Expiration, LifecycleFilter,
+    LifecycleRule,
+};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use time::format_description::FormatItem;
+use time::macros::format_description;
+use time::Date;
+
+/// Maximum number of rules S3 accepts in one lifecycle configuration
+const MAX_RULES: usize = 1000;
+/// Maximum length of a rule ID
+const MAX_ID_LENGTH: usize = 255;
+
+const DAY_FORMAT: &[FormatItem<'static>] = format_description!("[year]-[month]-[day]");
+
+/// Checks an ISO 8601 date at midnight UTC, the only time S3 accepts,
+/// e.g. `2026-01-31T00:00:00Z`.
+fn is_midnight_date(date: &str) -> bool {
+    let Some((day, time)) = date.split_once('T') else {
+        return false;
+    };
+    Date::parse(day, DAY_FORMAT).is_ok() && matches!(time, "00:00:00Z" | "00:00:00.000Z")
+}
+
+fn validate_filter(filter: &LifecycleFilter, problems: &mut Vec<String>) {
+    let conditions = [
+        filter.prefix.is_some(),
+        filter.tag.is_some(),
+        filter.object_size_greater_than.is_some(),
+        filter.object_size_less_than.is_some(),
+    ];
+    let count = conditions.iter().filter(|set| **set).count();
+    if filter.and.is_some() && count > 0 {
+        problems.push("Filter cannot combine And with other conditions".to_string());
+    } else if count > 1 {
+        problems.push("Filter with several conditions must wrap them in And".to_string());
+    }
+
+    let (greater, less) = match &filter.and {
+        Some(and) => (and.object_size_greater_than, and.object_size_less_than),
+        None => (
+            filter.object_size_greater_than,
+            filter.object_size_less_than,
+        ),
+    };
+    if let (Some(greater), Some(less)) = (greater, less) {
+        if greater >= less {
+            problems.push("ObjectSizeGreaterThan must be less than ObjectSizeLessThan".to_string());
+        }
+    }
+    if greater.is_some_and(|size| size < 0) || less.is_some_and(|size| size < 0) {
+        problems.push("Object sizes in Filter cannot be negative".to_string());
+    }
+}
+
+fn has_tag_filter(rule: &LifecycleRule) -> bool {
+    rule.filter.as_ref().is_some_and(|filter| {
+        filter.tag.is_some()
+            || filter
+                .and
+                .as_ref()
+                .is_some_and(|and| and.tags.as_ref().is_some_and(|tags| !tags.is_empty()))
+    })
+}
+
+fn validate_rule(rule: &LifecycleRule) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    if rule.status != "Enabled" && rule.status != "Disabled" {
+        problems.push(format!(
+            "Status must be Enabled or Disabled, got `{}`",
+            rule.status
+        ));
+    }
+    if let Some(id) = &rule.id {
+        if id.is_empty() || id.len() > MAX_ID_LENGTH {
+            problems.push(format!("ID must be 1 to {} characters", MAX_ID_LENGTH));
+        }
+    }
+    if let Some(filter) = &rule.filter {
+        validate_filter(filter, &mut problems);
+    }
+
+    let has_action = rule.expiration.is_some()
+        || rule.noncurrent_version_expiration.is_some()
+        || rule.transition.as_ref().is_some_and(|t| !t.is_empty())
+        || rule
+            .noncurrent_version_transition
+            .as_ref()
+            .is_some_and(|t| !t.is_empty())
+        || rule.abort_incomplete_multipart_upload.is_some();
+    if !has_action {
+        problems.push("Rule has no action".to_string());
+    }
+
+    if let Some(expiration) = &rule.expiration {
+        let set = [
+            expiration.days.is_some(),
+            expiration.date.is_some(),
+            expiration.expired_object_delete_marker.is_some(),
+        ];
+        if set.iter().filter(|set| **set).count() != 1 {
+            problems.push(
+                "Expiration needs exactly one of Days, Date or ExpiredObjectDeleteMarker"
+                    .to_string(),
+            );
+        }
+        if expiration.days == Some(0) {
+            problems.push("Expiration Days must be positive".to_string());
+        }
+        if let Some(date) = &expiration.date {
+            if !is_midnight_date(date) {
+                problems.push(format!(
+                    "Expiration Date must be midnight UTC like 2026-01-31T00:00:00Z, got `{}`",
+                    date
+                ));
+            }
+        }
+        if expiration.expired_object_delete_marker.is_some() && has_tag_filter(rule) {
+            problems.push("ExpiredObjectDeleteMarker cannot be used with a tag filter".to_string());
+        }
+    }
+
+    if let Some(expiration) = &rule.noncurrent_version_expiration {
+        if expiration.noncurrent_days.is_none_or(|days| days <= 0) {
+            problems.push("NoncurrentVersionExpiration needs positive NoncurrentDays".to_string());
+        }
+    }
+
+    for transition in rule.transition.iter().flatten() {
+        if transition.days.is_some() == transition.date.is_some() {
+            problems.push("Transition needs exactly one of Days or Date".to_string());
+        }
+        if let Some(date) = &transition.date {
+            if !is_midnight_date(date) {
+                problems.push(format!(
+                    "Transition Date must be midnight UTC like 2026-01-31T00:00:00Z, got `{}`",
+                    date
+                ));
+            }
+        }
+        if transition
+            .storage_class
+            .as_deref()
+            .is_none_or(str::is_empty)
+        {
+            problems.push("Transition needs a StorageClass".to_string());
+        }
+    }
+
+    for transition in rule.noncurrent_version_transition.iter().flatten() {
+        if transition.noncurrent_days.is_none_or(|days| days < 0) {
+            problems.push("NoncurrentVersionTransition needs NoncurrentDays".to_string());
+        }
+        if transition
+            .storage_class
+            .as_deref()
+            .is_none_or(str::is_empty)
+        {
+            problems.push("NoncurrentVersionTransition needs a StorageClass".to_string());
+        }
+    }
+
+    if let Some(abort) = &rule.abort_incomplete_multipart_upload {
+        if abort.days_after_initiation.is_none_or(|days| days <= 0) {
+            problems.push(
+                "AbortIncompleteMultipartUpload needs positive DaysAfterInitiation".to_string(),
+            );
+        }
+        if has_tag_filter(rule) {
+            problems.push(
+                "AbortIncompleteMultipartUpload cannot be used with a tag filter".to_string(),
+            );
+        }
+    }
+
+    problems
+}
+
+/// Returns every problem S3 would reject the configuration for, prefixed
+/// with the rule it was found in.
+fn validate(configuration: &BucketLifecycleConfiguration) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    if configuration.rules.is_empty() {
+        problems.push("Configuration has no rules".to_string());
+    }
+    if configuration.rules.len() > MAX_RULES {
+        problems.push(format!("At most {} rules are allowed", MAX_RULES));
+    }
+
+    let mut ids = HashSet::new();
+    for (index, rule) in configuration.rules.iter().enumerate() {
+        let name = match &rule.id {
+            Some(id) => format!("Rule `{}`", id),
+            None => format!("Rule #{}", index + 1),
+        };
+        if let Some(id) = &rule.id {
+            if !ids.insert(id) {
+                problems.push(format!("{}: duplicate ID", name));
+            }
+        }
+        problems.extend(
+            validate_rule(rule)
+                .into_iter()
+                .map(|problem| format!("{}: {}", name, problem)),
+        );
+    }
+
+    problems
+}
+
+/// Validates the configuration and uploads it, printing every problem
+/// instead when it is invalid.
+async fn apply(configuration: BucketLifecycleConfiguration, s3_client: &S3Client) -> Result<bool> {
+    let problems = validate(&configuration);
+    if !problems.is_empty() {
+        println!("{}", colors::fmt_error("Invalid lifecycle configuration:"));
+        for problem in &problems {
+            println!("  {}", colors::fmt_warn(problem));
+        }
+        return Ok(false);
+    }
+
+    s3_client.put_lifecycle(configuration).await?;
+    Ok(true)
+}
+
+pub async fn get(format: RulesFormat, s3_client: &S3Client) -> Result<()> {
+    let Some(configuration) = s3_client.get_lifecycle().await? else {
+        println!("{}", colors::fmt_info("No lifecycle rules found"));
+        return Ok(());
+    };
+
+    print!("{}", format.render(&configuration)?);
+    Ok(())
+}
+
+pub async fn set(file: PathBuf, s3_client: &S3Client) -> Result<()> {
+    let configuration: BucketLifecycleConfiguration = RulesFormat::read_file(&file).await?;
+
+    let count = configuration.rules.len();
+    if apply(configuration, s3_client).await? {
+        println!(
+            "{} {} {}",
+            colors::fmt_success("Applied"),
+            count,
+            colors::fmt_success("lifecycle rule(s)")
+        );
+    }
+    Ok(())
+}
+
+/// Adds a rule to the bucket's existing ones, replacing a rule with the
+/// same ID.
+async fn add_rule(rule: LifecycleRule, s3_client: &S3Client) -> Result<()> {
+    let mut configuration = s3_client
+        .get_lifecycle()
+        .await?
+        .unwrap_or_else(|| BucketLifecycleConfiguration::new(Vec::new()));
+    let id = rule.id.clone().unwrap_or_default();
+    match configuration
+        .rules
+        .iter_mut()
+        .find(|existing| existing.id == rule.id)
+    {
+        Some(existing) => *existing = rule,
+        None => configuration.rules.push(rule),
+    }
+
+    if apply(configuration, s3_client).await? {
+        println!(
+            "{} `{}`",
+            colors::fmt_success("Added lifecycle rule"),
+            colors::fmt_info(&id)
+        );
+    }
+    Ok(())
+}
+
+fn prefix_filter(prefix: &str) -> LifecycleFilter {
+    LifecycleFilter::new(None, None, None, Some(prefix.to_string()), None)
+}
+
+fn default_id(action: &str, prefix: &str) -> String {
+    if prefix.is_empty() {
+        format!("{}-all", action)
+    } else {
+        format!("{}-{}", action, prefix.trim_end_matches('/'))
+    }
+}
+
+pub async fn add_expire(
+    prefix: String,
+    days: u32,
+    id: Option<String>,
+    s3_client: &S3Client,
+) -> Result<()> {
+    let id = id.unwrap_or_else(|| default_id("expire", &prefix));
+    let rule = LifecycleRule::builder("Enabled")
+        .id(&id)
+        .filter(prefix_filter(&prefix))
+        .expiration(Expiration::new(None, Some(days), None))
+        .build();
+    add_rule(rule, s3_client).await
+}
+
+pub async fn add_abort_multipart(
+    prefix: String,
+    days: i32,
+    id: Option<String>,
+    s3_client: &S3Client,
+) -> Result<()> {
+    let id = id.unwrap_or_else(|| default_id("abort-multipart", &prefix));
+    let rule = LifecycleRule::builder("Enabled")
+        .id(&id)
+        .filter(prefix_filter(&prefix))
+        .abort_incomplete_multipart_upload(AbortIncompleteMultipartUpload::new(Some(days)))
+        .build();
+    add_rule(rule, s3_client).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use s3::serde_types::And;
+
+    fn expire_after(days: u32) -> LifecycleRule {
+        LifecycleRule::builder("Enabled")
+            .id("expire")
+            .filter(prefix_filter("logs/"))
+            .expiration(Expiration::new(None, Some(days), None))
+            .build()
+    }
+
+    #[test]
+    fn accepts_a_valid_rule() {
+        assert!(validate_rule(&expire_after(30)).is_empty());
+    }
+
+    #[test]
+    fn rule_needs_an_action() {
+        let rule = LifecycleRule::builder("Enabled").id("none").build();
+        assert_eq!(validate_rule(&rule), ["Rule has no action"]);
+    }
+
+    #[test]
+    fn expiration_takes_days_or_date() {
+        let mut rule = expire_after(30);
+        rule.expiration = Some(Expiration::new(
+            Some("2026-01-31T00:00:00Z".to_string()),
+            Some(30),
+            None,
+        ));
+        assert_eq!(
+            validate_rule(&rule),
+            ["Expiration needs exactly one of Days, Date or ExpiredObjectDeleteMarker"]
+        );
+    }
+
+    #[test]
+    fn expiration_date_must_be_a_midnight() {
+        for date in [
+            "2026-01-31T12:00:00Z",
+            "2026-02-30T00:00:00Z",
+            "2026-13-01T00:00:00Z",
+            "2026-1-31T00:00:00Z",
+            "2026-01-31",
+        ] {
+            let mut rule = expire_after(30);
+            rule.expiration = Some(Expiration::new(Some(date.to_string()), None, None));
+            assert_eq!(validate_rule(&rule).len(), 1, "{} was accepted", date);
+        }
+
+        for date in ["2026-01-31T00:00:00Z", "2028-02-29T00:00:00.000Z"] {
+            let mut rule = expire_after(30);
+            rule.expiration = Some(Expiration::new(Some(date.to_string()), None, None));
+            assert!(validate_rule(&rule).is_empty(), "{} was refused", date);
+        }
+    }
+
+    #[test]
+    fn filter_cannot_mix_and_with_prefix() {
+        let mut rule = expire_after(30);
+        rule.filter = Some(LifecycleFilter::new(
+            Some(And::new(Some(1024), None, Some("logs/".to_string()), None)),
+            None,
+            None,
+            Some("logs/".to_string()),
+            None,
+        ));
+        assert_eq!(
+            validate_rule(&rule),
+            ["Filter cannot combine And with other conditions"]
+        );
+    }
+}
@@ -1,19 +1,113 @@
-use crate::s3::S3Client;
+use crate::s3::{self, S3Client};
 use crate::utils::{colors, progress, size};
 use anyhow::Result;
-use futures::future;
+use futures::{future, stream, StreamExt, TryStreamExt};
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
+/// Number of tag lookups in flight at once when filtering by tag
+const TAG_CONCURRENCY: usize = 16;
+
 struct FileInfo {
     name: String,
     size: u64,
     is_dir: bool,
 }
 
-pub async fn execute(path: Option<PathBuf>, s3_client: &S3Client) -> Result<()> {
+async fn filter_by_tags(
+    files: Vec<(String, u64)>,
+    tags: &[(String, String)],
+    s3_client: &S3Client,
+) -> Result<Vec<(String, u64)>> {
+    let pb = progress::create_list_progress_bar(files.len() as u64);
+    pb.set_message("Getting file tags...");
+
+    let matches: Vec<Option<(String, u64)>> = stream::iter(files)
+        .map(|(key, size)| {
+            let pb = pb.clone();
+            async move {
+                let matched = if key.ends_with('/') {
+                    false
+                } else {
+                    let object_tags = s3_client.get_tags(&key).await?;
+                    tags.iter().all(|tag| object_tags.contains(tag))
+                };
+                pb.inc(1);
+                Ok::<_, anyhow::Error>(matched.then_some((key, size)))
+            }
+        })
+        .buffered(TAG_CONCURRENCY)
+        .try_collect()
+        .await?;
+
+    pb.finish_and_clear();
+    Ok(matches.into_iter().flatten().collect())
+}
+
+async fn list_versions(prefix: Option<&str>, s3_client: &S3Client) -> Result<()> {
+    let prefix = s3::dir_prefix(prefix.unwrap_or(""));
+    let versions = s3_client.list_versions(&prefix).await?;
+    if versions.is_empty() {
+        println!("{}", colors::fmt_info("No versions found"));
+        return Ok(());
+    }
+
+    for version in &versions {
+        let size_str = if version.is_delete_marker {
+            "-".to_string()
+        } else {
+            size::format_size(version.size)
+        };
+        let state = match (version.is_latest, version.is_delete_marker) {
+            (true, true) => colors::fmt_error("latest, delete marker"),
+            (true, false) => colors::fmt_success("latest"),
+            (false, true) => colors::fmt_warn("delete marker"),
+            (false, false) => String::new(),
+        };
+
+        println!(
+            "{:>10}  {}  {}  {} {}",
+            size_str,
+            version.last_modified,
+            colors::fmt_info(&version.version_id),
+            colors::fmt_nested_path(&version.key),
+            state
+        );
+    }
+
+    let delete_markers = versions.iter().filter(|v| v.is_delete_marker).count();
+    let total_size: u64 = versions.iter().map(|v| v.size).sum();
+
+    println!(
+        "\n{}: {}",
+        colors::fmt_info("Total"),
+        size::format_size(total_size)
+    );
+    println!(
+        "{}: {}",
+        colors::fmt_info("Versions"),
+        versions.len() - delete_markers
+    );
+    println!("{}: {}", colors::fmt_info("Delete markers"), delete_markers);
+
+    Ok(())
+}
+
+pub async fn execute(
+    path: Option<PathBuf>,
+    tags: Vec<(String, String)>,
+    versions: bool,
+    s3_client: &S3Client,
+) -> Result<()> {
     let prefix = path.as_deref().and_then(|p| p.to_str());
-    let files = s3_client.list(prefix).await?;
+    if versions {
+        return list_versions(prefix, s3_client).await;
+    }
+
+    let mut files = s3_client.list(prefix).await?;
+    if !tags.is_empty() {
+        files = filter_by_tags(files, &tags, s3_client).await?;
+    }
     if files.is_empty() {
         println!("{}", colors::fmt_info("No files found"));
         return Ok(());
@@ -1,9 +1,9 @@
-use crate::s3::S3Client;
+use crate::s3::{PutOptions, S3Client};
 use crate::utils::colors;
 use anyhow::Result;
 use std::path::PathBuf;
 
-pub async fn execute(path: PathBuf, s3_client: &S3Client) -> Result<()> {
+pub async fn execute(path: PathBuf, acl: Option<String>, s3_client: &S3Client) -> Result<()> {
     let path_str = path.to_string_lossy();
 
     let key = if path_str.ends_with('/') {
@@ -12,7 +12,15 @@ pub async fn execute(path: PathBuf, s3_client: &S3Client) -> Result<()> {
         format!("{}/", path_str)
     };
 
-    s3_client.put_empty_object(&key).await?;
+    s3_client
+        .put_empty_object(
+            &key,
+            &PutOptions {
+                acl,
+                ..Default::default()
+            },
+        )
+        .await?;
     println!(
         "{} `{}` {}",
         colors::fmt_success("Directory"),
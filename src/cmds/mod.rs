@@ -1,9 +1,28 @@
+pub mod acl;
+pub mod browse;
 pub mod cat;
+pub mod completions;
 pub mod config;
+pub mod cors;
 pub mod cp;
 pub mod dl;
+pub mod du;
+pub mod find;
+pub mod gateway;
+pub mod lifecycle;
 pub mod ls;
 pub mod md;
 pub mod mv;
+pub mod policy;
+pub mod presign;
+pub mod restore;
 pub mod rm;
+pub mod serve;
+pub mod shell;
+pub mod stat;
+pub mod tag;
+pub mod tree;
+pub mod undelete;
 pub mod up;
+pub mod versioning;
+pub mod webdav;
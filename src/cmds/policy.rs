@@ -0,0 +1,282 @@
+use crate::s3::S3Client;
+use crate::utils::colors;
+use anyhow::{Context, Result};
+use serde_json::{json, Map, Value};
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+const POLICY_VERSIONS: [&str; 2] = ["2012-10-17", "2008-10-17"];
+const POLICY_KEYS: [&str; 3] = ["Version", "Id", "Statement"];
+
+/// Whether `value` is a string or a non-empty array of strings, the shape
+/// of Action and Resource elements.
+fn is_string_or_list(value: &Value) -> bool {
+    match value {
+        Value::String(_) => true,
+        Value::Array(items) => !items.is_empty() && items.iter().all(Value::is_string),
+        _ => false,
+    }
+}
+
+/// Checks that exactly one of `key` and its `Not` variant is present and
+/// well formed.
+fn validate_pair(statement: &Map<String, Value>, key: &str, problems: &mut Vec<String>) {
+    let not_key = format!("Not{}", key);
+    match (statement.get(key), statement.get(&not_key)) {
+        (Some(_), Some(_)) => problems.push(format!("cannot have both {} and {}", key, not_key)),
+        (None, None) => problems.push(format!("needs {} or {}", key, not_key)),
+        (Some(value), None) | (None, Some(value)) => {
+            let valid = if key == "Principal" {
+                value == "*" || value.is_object()
+            } else {
+                is_string_or_list(value)
+            };
+            if !valid {
+                problems.push(format!("{} has an invalid value", key));
+            }
+        }
+    }
+}
+
+fn validate_statement(statement: &Value) -> Vec<String> {
+    let Some(statement) = statement.as_object() else {
+        return vec!["must be an object".to_string()];
+    };
+    let mut problems = Vec::new();
+
+    match statement.get("Effect").and_then(Value::as_str) {
+        Some("Allow" | "Deny") => {}
+        _ => problems.push("Effect must be Allow or Deny".to_string()),
+    }
+    if let Some(sid) = statement.get("Sid") {
+        let valid = sid
+            .as_str()
+            .is_some_and(|sid| sid.chars().all(|c| c.is_ascii_alphanumeric()));
+        if !valid {
+            problems.push("Sid may only contain letters and digits".to_string());
+        }
+    }
+    validate_pair(statement, "Principal", &mut problems);
+    validate_pair(statement, "Action", &mut problems);
+    validate_pair(statement, "Resource", &mut problems);
+    if let Some(condition) = statement.get("Condition") {
+        if !condition.is_object() {
+            problems.push("Condition must be an object".to_string());
+        }
+    }
+
+    problems
+}
+
+/// Returns every problem found in a policy document that S3 would reject.
+fn validate(policy: &Value) -> Vec<String> {
+    let Some(document) = policy.as_object() else {
+        return vec!["Policy must be a JSON object".to_string()];
+    };
+    let mut problems = Vec::new();
+
+    for key in document.keys() {
+        if !POLICY_KEYS.contains(&key.as_str()) {
+            problems.push(format!("Unknown element `{}`", key));
+        }
+    }
+    match document.get("Version") {
+        Some(Value::String(version)) if POLICY_VERSIONS.contains(&version.as_str()) => {}
+        Some(_) => problems.push(format!(
+            "Version must be one of {}",
+            POLICY_VERSIONS.join(", ")
+        )),
+        None => {}
+    }
+
+    let statements = match document.get("Statement") {
+        Some(Value::Array(statements)) if !statements.is_empty() => statements.clone(),
+        Some(statement @ Value::Object(_)) => vec![statement.clone()],
+        _ => {
+            problems.push("Statement must be an object or a non-empty array".to_string());
+            return problems;
+        }
+    };
+
+    let mut sids = HashSet::new();
+    for (index, statement) in statements.iter().enumerate() {
+        let sid = statement.get("Sid").and_then(Value::as_str);
+        let name = match sid {
+            Some(sid) => format!("Statement `{}`", sid),
+            None => format!("Statement #{}", index + 1),
+        };
+        if let Some(sid) = sid {
+            if !sids.insert(sid) {
+                problems.push(format!("{}: duplicate Sid", name));
+            }
+        }
+        problems.extend(
+            validate_statement(statement)
+                .into_iter()
+                .map(|problem| format!("{}: {}", name, problem)),
+        );
+    }
+
+    problems
+}
+
+/// Validates the policy and uploads it, printing every problem instead
+/// when it is invalid.
+async fn apply(policy: &Value, s3_client: &S3Client) -> Result<bool> {
+    let problems = validate(policy);
+    if !problems.is_empty() {
+        println!("{}", colors::fmt_error("Invalid bucket policy:"));
+        for problem in &problems {
+            println!("  {}", colors::fmt_warn(problem));
+        }
+        return Ok(false);
+    }
+
+    s3_client
+        .put_policy(&serde_json::to_string(policy)?)
+        .await?;
+    Ok(true)
+}
+
+pub async fn get(s3_client: &S3Client) -> Result<()> {
+    let Some(policy) = s3_client.get_policy().await? else {
+        println!("{}", colors::fmt_info("No bucket policy found"));
+        return Ok(());
+    };
+
+    // Pretty print when possible, some providers return it minified
+    match serde_json::from_str::<Value>(&policy) {
+        Ok(policy) => println!("{}", serde_json::to_string_pretty(&policy)?),
+        Err(_) => println!("{}", policy),
+    }
+    Ok(())
+}
+
+pub async fn set(file: PathBuf, s3_client: &S3Client) -> Result<()> {
+    let content = tokio::fs::read_to_string(&file)
+        .await
+        .with_context(|| format!("Failed to read {}", file.display()))?;
+    let policy: Value = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse {}", file.display()))?;
+
+    if apply(&policy, s3_client).await? {
+        println!("{}", colors::fmt_success("Bucket policy updated"));
+    }
+    Ok(())
+}
+
+pub async fn rm(s3_client: &S3Client) -> Result<()> {
+    s3_client.delete_policy().await?;
+    println!("{}", colors::fmt_success("Bucket policy removed"));
+    Ok(())
+}
+
+/// Adds a statement letting anyone read the files under `prefix`, keeping
+/// the rest of the existing policy.
+pub async fn public_read(prefix: String, s3_client: &S3Client) -> Result<()> {
+    let sid: String = format!("PublicRead{}", prefix)
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect();
+    let statement = json!({
+        "Sid": sid,
+        "Effect": "Allow",
+        "Principal": "*",
+        "Action": "s3:GetObject",
+        "Resource": format!("arn:aws:s3:::{}/{}*", s3_client.bucket_name(), prefix),
+    });
+
+    let mut policy = match s3_client.get_policy().await? {
+        Some(policy) => {
+            serde_json::from_str(&policy).context("Existing policy is not valid JSON")?
+        }
+        None => json!({ "Version": "2012-10-17", "Statement": [] }),
+    };
+    let Some(document) = policy.as_object_mut() else {
+        anyhow::bail!("Existing policy is not a JSON object");
+    };
+    let mut statements = match document.remove("Statement") {
+        Some(Value::Array(statements)) => statements,
+        Some(statement) => vec![statement],
+        None => Vec::new(),
+    };
+    statements.retain(|existing| existing.get("Sid").and_then(Value::as_str) != Some(&sid));
+    statements.push(statement);
+    document.insert("Statement".to_string(), Value::Array(statements));
+
+    if apply(&policy, s3_client).await? {
+        println!(
+            "`{}` {}",
+            colors::fmt_path(&format!("{}*", prefix)),
+            colors::fmt_success("is now publicly readable")
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_only() -> Value {
+        json!({
+            "Version": "2012-10-17",
+            "Statement": [{
+                "Sid": "PublicRead",
+                "Effect": "Allow",
+                "Principal": "*",
+                "Action": "s3:GetObject",
+                "Resource": "arn:aws:s3:::bucket/*"
+            }]
+        })
+    }
+
+    #[test]
+    fn accepts_a_valid_policy() {
+        assert!(validate(&read_only()).is_empty());
+    }
+
+    #[test]
+    fn refuses_a_malformed_document() {
+        assert_eq!(validate(&json!([])), ["Policy must be a JSON object"]);
+        assert_eq!(
+            validate(&json!({ "Version": "2012-10-17", "Statment": [] })),
+            [
+                "Unknown element `Statment`",
+                "Statement must be an object or a non-empty array"
+            ]
+        );
+
+        let mut policy = read_only();
+        policy["Version"] = json!("2020-01-01");
+        assert_eq!(
+            validate(&policy),
+            ["Version must be one of 2012-10-17, 2008-10-17"]
+        );
+    }
+
+    #[test]
+    fn names_the_statement_with_a_problem() {
+        let mut policy = read_only();
+        let statement = &mut policy["Statement"][0];
+        statement["Effect"] = json!("Permit");
+        statement["NotAction"] = json!("s3:PutObject");
+        statement["Resource"] = json!([]);
+        assert_eq!(
+            validate(&policy),
+            [
+                "Statement `PublicRead`: Effect must be Allow or Deny",
+                "Statement `PublicRead`: cannot have both Action and NotAction",
+                "Statement `PublicRead`: Resource has an invalid value",
+            ]
+        );
+    }
+
+    #[test]
+    fn refuses_duplicate_sids() {
+        let mut policy = read_only();
+        let statement = policy["Statement"][0].clone();
+        policy["Statement"] = json!([statement.clone(), statement]);
+        assert_eq!(validate(&policy), ["Statement `PublicRead`: duplicate Sid"]);
+    }
+}
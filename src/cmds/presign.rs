@@ -0,0 +1,158 @@
+use crate::s3::S3Client;
+use crate::utils::colors;
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Longest expiration S3 accepts for SigV4 presigned URLs (7 days)
+const MAX_EXPIRES: u64 = 7 * 24 * 60 * 60;
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Method {
+    #[value(name = "GET")]
+    Get,
+    #[value(name = "PUT")]
+    Put,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+#[derive(Serialize)]
+struct PresignedUrl {
+    key: String,
+    url: String,
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn response_overrides(
+    key: &str,
+    content_type: Option<&str>,
+    disposition: Option<&str>,
+) -> HashMap<String, String> {
+    let mut queries = HashMap::new();
+    if let Some(content_type) = content_type {
+        queries.insert(
+            "response-content-type".to_string(),
+            content_type.to_string(),
+        );
+    }
+    if let Some(disposition) = disposition {
+        // A bare `attachment` would make browsers save the file under the
+        // URL path, so the key's file name is suggested explicitly.
+        let disposition = if disposition == "attachment" {
+            let file_name = key.split('/').next_back().unwrap_or(key);
+            format!("attachment; filename=\"{}\"", file_name.replace('"', ""))
+        } else {
+            disposition.to_string()
+        };
+        queries.insert("response-content-disposition".to_string(), disposition);
+    }
+    queries
+}
+
+async fn presign_key(
+    key: &str,
+    method: Method,
+    expires: u32,
+    content_type: Option<&str>,
+    disposition: Option<&str>,
+    s3_client: &S3Client,
+) -> Result<String> {
+    match method {
+        Method::Get => {
+            s3_client
+                .presign_get(
+                    key,
+                    expires,
+                    response_overrides(key, content_type, disposition),
+                )
+                .await
+        }
+        Method::Put => s3_client.presign_put(key, expires).await,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn execute(
+    key: String,
+    expires: u64,
+    method: Method,
+    content_type: Option<String>,
+    disposition: Option<String>,
+    recursive: bool,
+    format: Format,
+    s3_client: &S3Client,
+) -> Result<()> {
+    if expires == 0 || expires > MAX_EXPIRES {
+        println!(
+            "{}",
+            colors::fmt_warn("Expiration must be between 1 second and 7 days")
+        );
+        return Ok(());
+    }
+    let expires = expires as u32;
+
+    if method == Method::Put && (content_type.is_some() || disposition.is_some()) {
+        println!(
+            "{}",
+            colors::fmt_warn("--content-type and --disposition apply only to GET URLs")
+        );
+        return Ok(());
+    }
+
+    if !recursive {
+        let url = presign_key(
+            &key,
+            method,
+            expires,
+            content_type.as_deref(),
+            disposition.as_deref(),
+            s3_client,
+        )
+        .await?;
+        println!("{}", url);
+        return Ok(());
+    }
+
+    let objects = s3_client.list_objects_recursive(&key).await?;
+    let mut urls = Vec::new();
+    for object in objects {
+        if object.ends_with('/') {
+            continue;
+        }
+        let url = presign_key(
+            &object,
+            method,
+            expires,
+            content_type.as_deref(),
+            disposition.as_deref(),
+            s3_client,
+        )
+        .await?;
+        urls.push(PresignedUrl { key: object, url });
+    }
+
+    match format {
+        Format::Csv => {
+            println!("key,url");
+            for entry in &urls {
+                println!("{},{}", csv_field(&entry.key), csv_field(&entry.url));
+            }
+        }
+        Format::Json => println!("{}", serde_json::to_string_pretty(&urls)?),
+    }
+
+    Ok(())
+}
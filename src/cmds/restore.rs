@@ -0,0 +1,19 @@
+use crate::s3::{PutOptions, S3Client};
+use crate::utils::colors;
+use anyhow::Result;
+
+pub async fn execute(path: String, version_id: String, s3_client: &S3Client) -> Result<()> {
+    // Copying an old version on top of the key makes it the newest version
+    s3_client
+        .with_version_id(Some(version_id.clone()))?
+        .copy_object_server_side(&path, &path, false, &PutOptions::default())
+        .await?;
+
+    println!(
+        "`{}` {} {}",
+        colors::fmt_path(&path),
+        colors::fmt_success("restored to version"),
+        colors::fmt_info(&version_id)
+    );
+    Ok(())
+}
@@ -0,0 +1,40 @@
+use crate::serve::{self, Credentials};
+use crate::utils::colors;
+use anyhow::Result;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+
+pub async fn execute(
+    root: PathBuf,
+    address: IpAddr,
+    port: u16,
+    access_key: String,
+    secret_key: String,
+) -> Result<()> {
+    if !root.is_dir() {
+        println!(
+            "{}",
+            colors::fmt_warn(&format!("No such directory: {}", root.display()))
+        );
+        return Ok(());
+    }
+
+    let address = SocketAddr::new(address, port);
+    println!(
+        "{}",
+        colors::fmt_info(&format!(
+            "Serving the directories of {} as buckets at http://{}",
+            root.display(),
+            address
+        ))
+    );
+    serve::run(
+        &root,
+        address,
+        Credentials {
+            access_key,
+            secret_key,
+        },
+    )
+    .await
+}
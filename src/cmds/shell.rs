@@ -0,0 +1,398 @@
+use crate::cmds::cat::Selection;
+use crate::cmds::{cat, cp, dl, mv, rm, up};
+use crate::config::Config;
+use crate::cse::CseSettings;
+use crate::s3::{self, PutOptions, S3Client};
+use crate::sse::SseSettings;
+use crate::utils::{colors, size};
+use anyhow::Result;
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{CompletionType, Context, Editor, Helper};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Commands of the shell with their arguments and description
+const COMMANDS: [(&str, &str, &str); 11] = [
+    ("ls", "[path]", "List a directory"),
+    (
+        "cd",
+        "[path]",
+        "Change the current prefix, / is the bucket root",
+    ),
+    ("pwd", "", "Print the current prefix"),
+    ("cat", "<path>", "Print a file"),
+    ("get", "<path> [local]", "Download a file or directory"),
+    ("put", "<local> [path]", "Upload a file or directory"),
+    ("rm", "[-r] <path>", "Remove a file, or a directory with -r"),
+    ("mv", "<source> <destination>", "Move a file"),
+    ("cp", "<source> <destination>", "Copy a file"),
+    ("help", "", "Show this help"),
+    ("exit", "", "Leave the shell"),
+];
+
+/// Resolves `path` against the current prefix `cwd`. A leading `/` starts
+/// at the bucket root, `.` and `..` work like on a filesystem, and a path
+/// naming a directory keeps its trailing slash.
+fn resolve(cwd: &str, path: &str) -> String {
+    let joined = match path.strip_prefix('/') {
+        Some(absolute) => absolute.to_string(),
+        None => format!("{}{}", cwd, path),
+    };
+
+    let mut parts = Vec::new();
+    for part in joined.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+
+    let mut resolved = parts.join("/");
+    let last = path.rsplit('/').next().unwrap_or(path);
+    let is_dir = path.is_empty() || matches!(last, "" | "." | "..");
+    if is_dir && !resolved.is_empty() {
+        resolved.push('/');
+    }
+    resolved
+}
+
+/// Completes command names, and keys from delimiter listings cached per
+/// prefix so pressing tab again does not list again
+struct ShellHelper<'a> {
+    s3_client: &'a S3Client,
+    cwd: String,
+    listings: RefCell<HashMap<String, Vec<String>>>,
+    files: FilenameCompleter,
+}
+
+impl ShellHelper<'_> {
+    /// Names of the entries directly under `prefix`, directories ending
+    /// with `/`. Completion runs inside `block_in_place`, so blocking on the
+    /// listing is fine.
+    fn children(&self, prefix: &str) -> Vec<String> {
+        if let Some(names) = self.listings.borrow().get(prefix) {
+            return names.clone();
+        }
+
+        let listing = tokio::runtime::Handle::current().block_on(self.s3_client.list_dir(prefix));
+        let Ok((prefixes, objects)) = listing else {
+            return Vec::new();
+        };
+        let names: Vec<String> = prefixes
+            .into_iter()
+            .map(|dir| dir[prefix.len()..].to_string())
+            .chain(
+                objects
+                    .into_iter()
+                    .map(|object| object.key[prefix.len()..].to_string()),
+            )
+            .collect();
+        self.listings
+            .borrow_mut()
+            .insert(prefix.to_string(), names.clone());
+        names
+    }
+}
+
+impl Completer for ShellHelper<'_> {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let before = &line[..pos];
+        let start = before.rfind(' ').map_or(0, |i| i + 1);
+        let word = &before[start..];
+
+        if start == 0 {
+            let commands = COMMANDS
+                .iter()
+                .filter(|(name, _, _)| name.starts_with(word))
+                .map(|(name, _, _)| Pair {
+                    display: name.to_string(),
+                    replacement: format!("{} ", name),
+                })
+                .collect();
+            return Ok((0, commands));
+        }
+
+        // put's first argument and get's second are local paths
+        let command = before.split_whitespace().next().unwrap_or("");
+        let argument = before[..start].split_whitespace().count() - 1;
+        if matches!((command, argument), ("put", 0) | ("get", 1)) {
+            return self.files.complete(line, pos, ctx);
+        }
+
+        let dir = &word[..word.rfind('/').map_or(0, |i| i + 1)];
+        let name = &word[dir.len()..];
+        let candidates = self
+            .children(&resolve(&self.cwd, dir))
+            .into_iter()
+            .filter(|child| child.starts_with(name))
+            .map(|child| Pair {
+                replacement: format!("{}{}", dir, child),
+                display: child,
+            })
+            .collect();
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for ShellHelper<'_> {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper<'_> {}
+
+impl Validator for ShellHelper<'_> {}
+
+impl Helper for ShellHelper<'_> {}
+
+fn print_help() {
+    for (name, args, description) in COMMANDS {
+        let usage = format!("{} {}", name, args);
+        println!("  {:<30} {}", colors::fmt_info(&usage), description);
+    }
+}
+
+fn usage(command: &str) -> Option<String> {
+    COMMANDS
+        .iter()
+        .find(|(name, _, _)| *name == command)
+        .map(|(name, args, _)| format!("Usage: {} {}", name, args))
+}
+
+struct Shell<'a> {
+    s3_client: &'a S3Client,
+    cwd: String,
+}
+
+impl Shell<'_> {
+    fn resolve(&self, path: &str) -> String {
+        resolve(&self.cwd, path)
+    }
+
+    /// Resolves the destination of `mv` or `cp`, appending the source's
+    /// name when it is a directory
+    fn destination(&self, source: &str, destination: &str) -> String {
+        let destination = self.resolve(destination);
+        if destination.is_empty() || destination.ends_with('/') {
+            let name = source.rsplit('/').next().unwrap_or(source);
+            format!("{}{}", destination, name)
+        } else {
+            destination
+        }
+    }
+
+    async fn cd(&mut self, path: &str) -> Result<()> {
+        let prefix = s3::dir_prefix(&self.resolve(path));
+        if prefix.is_empty() || self.s3_client.is_directory(&prefix).await? {
+            self.cwd = prefix;
+        } else {
+            println!(
+                "{}",
+                colors::fmt_warn(&format!("No such directory: {}", path))
+            );
+        }
+        Ok(())
+    }
+
+    async fn ls(&self, path: &str) -> Result<()> {
+        let prefix = s3::dir_prefix(&self.resolve(path));
+        let (dirs, objects) = self.s3_client.list_dir(&prefix).await?;
+        if dirs.is_empty() && objects.is_empty() {
+            println!("{}", colors::fmt_info("No files found"));
+            return Ok(());
+        }
+
+        for dir in &dirs {
+            println!("{:>10}  {}", "", colors::fmt_dir_path(&dir[prefix.len()..]));
+        }
+        for object in &objects {
+            println!(
+                "{:>10}  {}",
+                size::format_size(object.size),
+                colors::fmt_file(&object.key[prefix.len()..])
+            );
+        }
+        Ok(())
+    }
+
+    /// Runs one command line, returning false once the shell should exit
+    async fn run(&mut self, args: &[String]) -> Result<bool> {
+        let s3_client = self.s3_client;
+        match (args[0].as_str(), &args[1..]) {
+            ("exit" | "quit", []) => return Ok(false),
+            ("help", []) => print_help(),
+            ("pwd", []) => println!("/{}", self.cwd),
+            ("cd", []) => self.cwd.clear(),
+            ("cd", [path]) => self.cd(path).await?,
+            ("ls", []) => self.ls("").await?,
+            ("ls", [path]) => self.ls(path).await?,
+            ("cat", [path]) => {
+                cat::execute(
+                    PathBuf::from(self.resolve(path)),
+                    None,
+                    Selection::All,
+                    false,
+                    SseSettings::default(),
+                    CseSettings::default(),
+                    false,
+                    s3_client,
+                )
+                .await?
+            }
+            ("get", [path, local @ ..]) if local.len() <= 1 => {
+                let local = local.first().map_or(".", String::as_str);
+                dl::execute(
+                    self.resolve(path),
+                    PathBuf::from(local),
+                    true,
+                    None,
+                    None,
+                    None,
+                    SseSettings::default(),
+                    CseSettings::default(),
+                    false,
+                    s3_client,
+                )
+                .await?
+            }
+            ("put", [local, path @ ..]) if path.len() <= 1 => {
+                let destination = match path.first() {
+                    Some(path) => self.resolve(path),
+                    None => self.cwd.clone(),
+                };
+                up::execute(
+                    PathBuf::from(local),
+                    (!destination.is_empty()).then_some(destination),
+                    true,
+                    None,
+                    None,
+                    PutOptions::default(),
+                    SseSettings::default(),
+                    None,
+                    false,
+                    CseSettings::default(),
+                    s3_client,
+                )
+                .await?
+            }
+            ("rm", [path]) => {
+                rm::execute(PathBuf::from(self.resolve(path)), false, s3_client).await?
+            }
+            ("rm", [flag, path]) if flag == "-r" => {
+                rm::execute(PathBuf::from(self.resolve(path)), true, s3_client).await?
+            }
+            ("mv", [source, destination]) => {
+                let destination = self.destination(source, destination);
+                mv::execute(
+                    PathBuf::from(self.resolve(source)),
+                    PathBuf::from(destination),
+                    s3_client,
+                )
+                .await?
+            }
+            ("cp", [source, destination]) => {
+                let destination = self.destination(source, destination);
+                cp::execute(
+                    PathBuf::from(self.resolve(source)),
+                    PathBuf::from(destination),
+                    None,
+                    None,
+                    None,
+                    PutOptions::default(),
+                    SseSettings::default(),
+                    s3_client,
+                )
+                .await?
+            }
+            (command, _) => {
+                let message = usage(command).unwrap_or_else(|| {
+                    format!("Unknown command {}, type help for the list", command)
+                });
+                println!("{}", colors::fmt_warn(&message));
+            }
+        }
+        Ok(true)
+    }
+}
+
+pub async fn execute(s3_client: &S3Client) -> Result<()> {
+    let history = Config::dir()?.join("history");
+    let config = rustyline::Config::builder()
+        .auto_add_history(true)
+        .completion_type(CompletionType::List)
+        .build();
+    let mut editor = Editor::with_config(config)?;
+    editor.set_helper(Some(ShellHelper {
+        s3_client,
+        cwd: String::new(),
+        listings: RefCell::new(HashMap::new()),
+        files: FilenameCompleter::new(),
+    }));
+    // There is no history before the first session
+    let _ = editor.load_history(&history);
+
+    let bucket = s3_client.bucket_name();
+    println!(
+        "{}",
+        colors::fmt_info(&format!(
+            "Connected to {}, type help for the commands",
+            bucket
+        ))
+    );
+
+    let mut shell = Shell {
+        s3_client,
+        cwd: String::new(),
+    };
+    loop {
+        let prompt = format!("s3://{}/{}> ", bucket, shell.cwd);
+        let line = match tokio::task::block_in_place(|| editor.readline(&prompt)) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+
+        let Some(args) = shlex::split(&line) else {
+            println!("{}", colors::fmt_warn("Unbalanced quotes"));
+            continue;
+        };
+        if args.is_empty() {
+            continue;
+        }
+
+        match shell.run(&args).await {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => println!("{}", colors::fmt_error(&format!("{:#}", e))),
+        }
+
+        if let Some(helper) = editor.helper_mut() {
+            helper.cwd.clone_from(&shell.cwd);
+            // Any command but a listing may have changed the bucket
+            if !matches!(
+                args[0].as_str(),
+                "ls" | "cd" | "pwd" | "cat" | "get" | "help"
+            ) {
+                helper.listings.borrow_mut().clear();
+            }
+        }
+    }
+
+    editor.save_history(&history)?;
+    Ok(())
+}
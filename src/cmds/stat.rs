@@ -0,0 +1,205 @@
+use crate::config::Config;
+use crate::s3::{self, ObjectMetadata, S3Client};
+use crate::sse::SseSettings;
+use crate::utils::output::OutputFormat;
+use crate::utils::{colors, size};
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Default, Serialize)]
+struct ClassSummary {
+    objects: usize,
+    size: u64,
+}
+
+#[derive(Serialize)]
+struct PrefixSummary {
+    prefix: String,
+    objects: usize,
+    directories: usize,
+    total_size: u64,
+    oldest_modified: Option<String>,
+    newest_modified: Option<String>,
+    storage_classes: BTreeMap<String, ClassSummary>,
+}
+
+fn print_field(name: &str, value: Option<&str>) {
+    println!(
+        "{}: {}",
+        name,
+        colors::fmt_val(value.unwrap_or(""), "<not set>")
+    );
+}
+
+fn display_object(meta: &ObjectMetadata) {
+    println!("{}", colors::fmt_head(&format!("Object: {}", meta.key)));
+    println!(
+        "Size: {}",
+        colors::fmt_info(&format!("{} ({})", size::format_size(meta.size), meta.size))
+    );
+    print_field("Content-Type", meta.content_type.as_deref());
+    print_field("Content-Encoding", meta.content_encoding.as_deref());
+    print_field("Content-Disposition", meta.content_disposition.as_deref());
+    print_field("Content-Language", meta.content_language.as_deref());
+    print_field("Cache-Control", meta.cache_control.as_deref());
+    print_field("Expires", meta.expires.as_deref());
+    print_field("ETag", meta.e_tag.as_deref());
+    print_field("Last-Modified", meta.last_modified.as_deref());
+    print_field("Storage Class", meta.storage_class.as_deref());
+    print_field(
+        "Server-Side Encryption",
+        meta.server_side_encryption.as_deref(),
+    );
+    if meta.sse_kms_key_id.is_some() {
+        print_field("KMS Key ID", meta.sse_kms_key_id.as_deref());
+    }
+    if meta.sse_customer_algorithm.is_some() {
+        print_field("SSE-C Algorithm", meta.sse_customer_algorithm.as_deref());
+    }
+    print_field("Version ID", meta.version_id.as_deref());
+    print_field("Object Lock Mode", meta.object_lock_mode.as_deref());
+    print_field(
+        "Object Lock Retain Until",
+        meta.object_lock_retain_until_date.as_deref(),
+    );
+    print_field(
+        "Object Lock Legal Hold",
+        meta.object_lock_legal_hold_status.as_deref(),
+    );
+    if meta.replication_status.is_some() {
+        print_field("Replication Status", meta.replication_status.as_deref());
+    }
+    if meta.restore.is_some() {
+        print_field("Restore", meta.restore.as_deref());
+    }
+    if let Some(parts) = meta.parts_count {
+        println!("Parts: {}", colors::fmt_info(&parts.to_string()));
+    }
+
+    if meta.metadata.is_empty() {
+        println!("User Metadata: {}", colors::fmt_val("", "<none>"));
+    } else {
+        println!("{}", colors::fmt_head("User Metadata:"));
+        for (key, value) in &meta.metadata {
+            println!("  x-amz-meta-{}: {}", key, colors::fmt_success(value));
+        }
+    }
+}
+
+fn display_prefix(summary: &PrefixSummary) {
+    println!(
+        "{}",
+        colors::fmt_head(&format!("Prefix: {}", summary.prefix))
+    );
+    println!(
+        "Objects: {}",
+        colors::fmt_info(&summary.objects.to_string())
+    );
+    println!(
+        "Directories: {}",
+        colors::fmt_info(&summary.directories.to_string())
+    );
+    println!(
+        "Total Size: {}",
+        colors::fmt_info(&format!(
+            "{} ({})",
+            size::format_size(summary.total_size),
+            summary.total_size
+        ))
+    );
+    print_field("Oldest Modified", summary.oldest_modified.as_deref());
+    print_field("Newest Modified", summary.newest_modified.as_deref());
+
+    if !summary.storage_classes.is_empty() {
+        println!("{}", colors::fmt_head("Storage Classes:"));
+        for (class, stats) in &summary.storage_classes {
+            println!(
+                "  {}: {} objects, {}",
+                colors::fmt_info(class),
+                stats.objects,
+                size::format_size(stats.size)
+            );
+        }
+    }
+}
+
+async fn summarize_prefix(prefix: &str, s3_client: &S3Client) -> Result<PrefixSummary> {
+    let objects = s3_client.list_objects(Some(prefix)).await?;
+    let mut summary = PrefixSummary {
+        prefix: prefix.to_string(),
+        objects: 0,
+        directories: 0,
+        total_size: 0,
+        oldest_modified: None,
+        newest_modified: None,
+        storage_classes: BTreeMap::new(),
+    };
+
+    for object in objects {
+        if object.key.ends_with('/') {
+            summary.directories += 1;
+            continue;
+        }
+
+        summary.objects += 1;
+        summary.total_size += object.size;
+
+        // ListObjects timestamps are ISO 8601, so they sort lexicographically
+        if summary
+            .oldest_modified
+            .as_ref()
+            .is_none_or(|oldest| object.last_modified < *oldest)
+        {
+            summary.oldest_modified = Some(object.last_modified.clone());
+        }
+        if summary
+            .newest_modified
+            .as_ref()
+            .is_none_or(|newest| object.last_modified > *newest)
+        {
+            summary.newest_modified = Some(object.last_modified.clone());
+        }
+
+        let class = object
+            .storage_class
+            .unwrap_or_else(|| s3::DEFAULT_STORAGE_CLASS.to_string());
+        let stats = summary.storage_classes.entry(class).or_default();
+        stats.objects += 1;
+        stats.size += object.size;
+    }
+
+    Ok(summary)
+}
+
+pub async fn execute(
+    path: String,
+    output: OutputFormat,
+    version_id: Option<String>,
+    sse: SseSettings,
+    s3_client: &S3Client,
+) -> Result<()> {
+    let config = Config::load()?;
+    let is_versioned = version_id.is_some();
+    let s3_client = &s3_client
+        .with_version_id(version_id)?
+        .with_encryption(sse.resolve(&config).await?)?;
+
+    let is_prefix = path.is_empty() || path.ends_with('/');
+    if !is_versioned && (is_prefix || s3_client.is_directory(&path).await?) {
+        let summary = summarize_prefix(&path, s3_client).await?;
+        match output {
+            OutputFormat::Text => display_prefix(&summary),
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&summary)?),
+        }
+        return Ok(());
+    }
+
+    let meta = s3_client.head_object(&path).await?;
+    match output {
+        OutputFormat::Text => display_object(&meta),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&meta)?),
+    }
+
+    Ok(())
+}
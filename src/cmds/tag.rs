@@ -0,0 +1,87 @@
+use crate::s3::S3Client;
+use crate::utils::colors;
+use crate::utils::output::OutputFormat;
+use anyhow::Result;
+use std::collections::BTreeMap;
+
+/// Maximum number of tags S3 allows on a single object
+const MAX_TAGS: usize = 10;
+
+pub async fn get(path: String, output: OutputFormat, s3_client: &S3Client) -> Result<()> {
+    let tags: BTreeMap<String, String> = s3_client.get_tags(&path).await?.into_iter().collect();
+
+    match output {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&tags)?),
+        OutputFormat::Text => {
+            if tags.is_empty() {
+                println!("{}", colors::fmt_info("No tags found"));
+            }
+            for (key, value) in &tags {
+                println!("{} = {}", colors::fmt_info(key), colors::fmt_success(value));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn set(
+    path: String,
+    tags: Vec<(String, String)>,
+    replace: bool,
+    s3_client: &S3Client,
+) -> Result<()> {
+    let mut tag_set: BTreeMap<String, String> = if replace {
+        BTreeMap::new()
+    } else {
+        s3_client.get_tags(&path).await?.into_iter().collect()
+    };
+    tag_set.extend(tags);
+
+    if tag_set.len() > MAX_TAGS {
+        println!(
+            "{}",
+            colors::fmt_warn(&format!(
+                "An object can have at most {} tags, got {}",
+                MAX_TAGS,
+                tag_set.len()
+            ))
+        );
+        return Ok(());
+    }
+
+    let tag_set: Vec<(String, String)> = tag_set.into_iter().collect();
+    s3_client.put_tags(&path, &tag_set).await?;
+    println!(
+        "{} `{}`",
+        colors::fmt_success("Tags updated for"),
+        colors::fmt_path(&path)
+    );
+    Ok(())
+}
+
+pub async fn rm(path: String, keys: Vec<String>, s3_client: &S3Client) -> Result<()> {
+    if keys.is_empty() {
+        s3_client.delete_tags(&path).await?;
+    } else {
+        let tag_set: Vec<(String, String)> = s3_client
+            .get_tags(&path)
+            .await?
+            .into_iter()
+            .filter(|(key, _)| !keys.contains(key))
+            .collect();
+
+        if tag_set.is_empty() {
+            s3_client.delete_tags(&path).await?;
+        } else {
+            s3_client.put_tags(&path, &tag_set).await?;
+        }
+    }
+
+    println!(
+        "{} `{}`",
+        colors::fmt_success("Tags removed from"),
+        colors::fmt_path(&path)
+    );
+    Ok(())
+}
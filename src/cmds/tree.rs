@@ -0,0 +1,149 @@
+use crate::s3::{self, S3Client};
+use crate::utils::{colors, size};
+use anyhow::Result;
+use std::collections::BTreeMap;
+
+#[derive(Default)]
+struct Node {
+    /// Total size of the files below this node, or the file's own size
+    size: u64,
+    is_dir: bool,
+    children: BTreeMap<String, Node>,
+}
+
+impl Node {
+    /// Adds the object at `relative`, a key relative to the tree's root,
+    /// creating the directories on the way
+    fn insert(&mut self, relative: &str, size: u64) {
+        self.size += size;
+        match relative.split_once('/') {
+            Some((name, rest)) => {
+                let child = self.children.entry(name.to_string()).or_default();
+                child.is_dir = true;
+                // A directory marker ends with the slash
+                if !rest.is_empty() {
+                    child.insert(rest, size);
+                }
+            }
+            None => {
+                let child = self.children.entry(relative.to_string()).or_default();
+                child.size = size;
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Counts {
+    directories: usize,
+    files: usize,
+}
+
+fn render(
+    node: &Node,
+    indent: &str,
+    level: usize,
+    max_depth: Option<usize>,
+    sizes: bool,
+    dirs_only: bool,
+    counts: &mut Counts,
+) {
+    if max_depth.is_some_and(|max| level > max) {
+        return;
+    }
+
+    let children: Vec<(&String, &Node)> = node
+        .children
+        .iter()
+        .filter(|(_, child)| child.is_dir || !dirs_only)
+        .collect();
+
+    for (i, (name, child)) in children.iter().enumerate() {
+        let is_last = i + 1 == children.len();
+        let (branch, continuation) = if is_last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        let size_str = if sizes {
+            format!("[{:>10}]  ", size::format_size(child.size))
+        } else {
+            String::new()
+        };
+
+        if child.is_dir {
+            counts.directories += 1;
+            println!("{}{}{}{}", indent, branch, size_str, colors::fmt_dir(name));
+            render(
+                child,
+                &format!("{}{}", indent, continuation),
+                level + 1,
+                max_depth,
+                sizes,
+                dirs_only,
+                counts,
+            );
+        } else {
+            counts.files += 1;
+            println!("{}{}{}{}", indent, branch, size_str, colors::fmt_file(name));
+        }
+    }
+}
+
+pub async fn execute(
+    path: Option<String>,
+    max_depth: Option<usize>,
+    sizes: bool,
+    dirs_only: bool,
+    s3_client: &S3Client,
+) -> Result<()> {
+    let prefix = s3::dir_prefix(path.as_deref().unwrap_or(""));
+    let objects = s3_client.list_objects(Some(&prefix)).await?;
+
+    let mut root = Node {
+        is_dir: true,
+        ..Default::default()
+    };
+    for object in &objects {
+        let relative = &object.key[prefix.len()..];
+        if !relative.is_empty() {
+            root.insert(relative, object.size);
+        }
+    }
+
+    if objects.is_empty() {
+        println!("{}", colors::fmt_info("No files found"));
+        return Ok(());
+    }
+
+    let root_name = if prefix.is_empty() { "." } else { &prefix };
+    if sizes {
+        println!(
+            "[{:>10}]  {}",
+            size::format_size(root.size),
+            colors::fmt_dir(root_name)
+        );
+    } else {
+        println!("{}", colors::fmt_dir(root_name));
+    }
+
+    let mut counts = Counts::default();
+    render(&root, "", 1, max_depth, sizes, dirs_only, &mut counts);
+
+    let directories = if counts.directories == 1 {
+        "directory"
+    } else {
+        "directories"
+    };
+    if dirs_only {
+        println!("\n{} {}", counts.directories, directories);
+    } else {
+        let files = if counts.files == 1 { "file" } else { "files" };
+        println!(
+            "\n{} {}, {} {}",
+            counts.directories, directories, counts.files, files
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use crate::s3::{self, S3Client};
+use crate::utils::colors;
+use anyhow::Result;
+
+pub async fn execute(path: String, s3_client: &S3Client) -> Result<()> {
+    let versions = s3_client.list_versions(&path).await?;
+
+    // An exact key match wins, otherwise the path is treated as a directory
+    let prefix = s3::dir_prefix(&path);
+    let exact = versions.iter().any(|version| version.key == path);
+    let in_scope = |key: &str| {
+        if exact {
+            key == path
+        } else {
+            key.starts_with(&prefix)
+        }
+    };
+
+    let mut restored = 0;
+    let mut lost = 0;
+    // S3 lists the versions of a key newest first, so a key is hidden when its
+    // newest entries are delete markers, maybe several in a row
+    for history in versions.chunk_by(|a, b| a.key == b.key) {
+        let key = &history[0].key;
+        if !history[0].is_delete_marker || !in_scope(key) {
+            continue;
+        }
+        let markers = history
+            .iter()
+            .take_while(|version| version.is_delete_marker)
+            .count();
+        if markers == history.len() {
+            println!(
+                "`{}` {}",
+                colors::fmt_path(key),
+                colors::fmt_warn("has no version left to restore")
+            );
+            lost += 1;
+            continue;
+        }
+
+        for marker in &history[..markers] {
+            s3_client.delete_version(key, &marker.version_id).await?;
+        }
+        println!(
+            "`{}` {}",
+            colors::fmt_path(key),
+            colors::fmt_success("undeleted")
+        );
+        restored += 1;
+    }
+
+    if restored == 0 && lost == 0 {
+        println!(
+            "{}",
+            colors::fmt_info(&format!("No deleted files found at {}", path))
+        );
+    }
+
+    Ok(())
+}
@@ -1,19 +1,203 @@
+use crate::compress::{Compression, META_COMPRESSION};
 use crate::config::Config;
-use crate::s3::S3Client;
-use crate::utils::{colors, progress};
+use crate::cse::{CseSettings, EncryptReader, Keys};
+use crate::s3::{PutOptions, S3Client};
+use crate::sse::SseSettings;
+use crate::utils::{colors, mime, progress};
 use anyhow::{Context, Result};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use tokio::fs;
+use tokio::io::AsyncRead;
 
+struct UploadOptions<'a> {
+    chunk_size: usize,
+    put_options: PutOptions,
+    content_types: &'a BTreeMap<String, String>,
+    compression: Option<Compression>,
+    /// Keys for client-side encryption, if files are encrypted
+    keys: Option<Keys>,
+}
+
+async fn upload_file(
+    path: &Path,
+    s3_path: &str,
+    file_size: u64,
+    options: &UploadOptions<'_>,
+    s3_client: &S3Client,
+) -> Result<()> {
+    let mut put_options = options.put_options.clone();
+    // The Content-Type of an encrypted file would tell the provider what
+    // it holds
+    if put_options.content_type.is_none() && options.keys.is_none() {
+        put_options.content_type =
+            Some(mime::detect_content_type(path, options.content_types).await);
+    }
+
+    let pb =
+        progress::create_upload_progress_bar(file_size, &colors::fmt_path(&path.to_string_lossy()));
+
+    if options.compression.is_none() && options.keys.is_none() {
+        s3_client
+            .upload_with_progress(
+                path,
+                s3_path,
+                options.chunk_size,
+                &put_options,
+                |uploaded| {
+                    pb.set_position(uploaded);
+                },
+            )
+            .await?;
+        pb.finish();
+        return Ok(());
+    }
+
+    // The bytes sent differ from the file's, so progress follows the file
+    let file = fs::File::open(path)
+        .await
+        .context(format!("Failed to open {}", path.display()))?;
+    let reader = progress::ProgressReader::new(file, pb.clone());
+    let mut reader = encode_reader(reader, Some(file_size), &mut put_options, options)?;
+
+    s3_client
+        .upload_reader(
+            &mut reader,
+            s3_path,
+            options.chunk_size,
+            &put_options,
+            |_| {},
+        )
+        .await?;
+
+    pb.finish();
+    Ok(())
+}
+
+/// Applies the compression and client-side encryption of `options` to
+/// `reader`, adding what is needed to read the object back to `put_options`.
+fn encode_reader<'a, R>(
+    reader: R,
+    size: Option<u64>,
+    put_options: &mut PutOptions,
+    options: &UploadOptions<'_>,
+) -> Result<Box<dyn AsyncRead + Unpin + Send + 'a>>
+where
+    R: AsyncRead + Unpin + Send + 'a,
+{
+    let mut reader: Box<dyn AsyncRead + Unpin + Send + 'a> = Box::new(reader);
+    if let Some(compression) = options.compression {
+        reader = compression.encoder(reader);
+        put_options.metadata.insert(
+            META_COMPRESSION.to_string(),
+            compression.as_str().to_string(),
+        );
+        // Once encrypted the data is no longer a gzip or zstd stream
+        if options.keys.is_none() {
+            put_options.content_encoding = Some(compression.as_str().to_string());
+        }
+    }
+    if let Some(keys) = &options.keys {
+        let (envelope, cipher) = keys.for_upload(size)?;
+        put_options.metadata.extend(envelope.to_metadata());
+        reader = Box::new(EncryptReader::new(reader, &envelope, cipher));
+    }
+    Ok(reader)
+}
+
+/// Streams stdin to `s3_path`. Its length is unknown, so it goes up one
+/// part at a time and a spinner counts the bytes instead of a bar.
+async fn upload_stdin(
+    s3_path: &str,
+    options: &UploadOptions<'_>,
+    s3_client: &S3Client,
+) -> Result<()> {
+    let mut put_options = options.put_options.clone();
+    if put_options.content_type.is_none() && options.keys.is_none() {
+        put_options.content_type =
+            mime::content_type_from_name(Path::new(s3_path), options.content_types);
+    }
+
+    let pb = progress::create_stream_progress_bar(&colors::fmt_path(s3_path));
+    let reader = progress::ProgressReader::new(tokio::io::stdin(), pb.clone());
+    let mut reader = encode_reader(reader, None, &mut put_options, options)?;
+
+    s3_client
+        .upload_reader(
+            &mut reader,
+            s3_path,
+            options.chunk_size,
+            &put_options,
+            |_| {},
+        )
+        .await?;
+
+    pb.finish();
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn execute(
     path: PathBuf,
     destination: Option<String>,
     recursive: bool,
     chunk_size: Option<usize>,
+    limit_rate: Option<usize>,
+    put_options: PutOptions,
+    sse: SseSettings,
+    compression: Option<Compression>,
+    encrypt: bool,
+    cse: CseSettings,
     s3_client: &S3Client,
 ) -> Result<()> {
+    if !encrypt && cse.key_file.is_some() {
+        println!("{}", colors::fmt_warn("--key-file requires --encrypt"));
+        return Ok(());
+    }
+    if compression.is_some() && put_options.content_encoding.is_some() {
+        println!(
+            "{}",
+            colors::fmt_warn("--compress sets the Content-Encoding, drop --content-encoding")
+        );
+        return Ok(());
+    }
+
     let config = Config::load()?;
-    let chunk_size = chunk_size.unwrap_or(config.upload_chunk_size);
+    let s3_client = &s3_client
+        .with_rate_limit(limit_rate.or(config.limit_rate))
+        .with_encryption(sse.resolve(&config).await?)?;
+    let options = UploadOptions {
+        chunk_size: chunk_size.unwrap_or(config.upload_chunk_size),
+        put_options,
+        content_types: &config.content_types,
+        compression,
+        keys: if encrypt {
+            Some(cse.resolve(&config).await?)
+        } else {
+            None
+        },
+    };
+
+    if path == Path::new("-") {
+        let Some(s3_path) = destination.filter(|dest| !dest.is_empty() && !dest.ends_with('/'))
+        else {
+            println!(
+                "{}",
+                colors::fmt_warn(
+                    "Uploading stdin needs the destination key, e.g. -d backups/db.sql"
+                )
+            );
+            return Ok(());
+        };
+        if recursive {
+            println!(
+                "{}",
+                colors::fmt_warn("stdin cannot be uploaded recursively")
+            );
+            return Ok(());
+        }
+        return upload_stdin(&s3_path, &options, s3_client).await;
+    }
 
     let metadata = fs::metadata(&path)
         .await
@@ -45,7 +229,7 @@ pub async fn execute(
             }
         };
 
-        upload_directory(&path, s3_destination, "", chunk_size, s3_client).await?;
+        upload_directory(&path, s3_destination, "", &options, s3_client).await?;
         println!("{}", colors::fmt_success("Directory uploaded successfully"));
     } else {
         let filename = path
@@ -64,19 +248,7 @@ pub async fn execute(
             }
         };
 
-        let file_size = metadata.len();
-        let pb = progress::create_upload_progress_bar(
-            file_size,
-            &format!("{}", colors::fmt_path(&path.to_string_lossy()),),
-        );
-
-        s3_client
-            .upload_with_progress(&path, &s3_path, chunk_size, |uploaded| {
-                pb.set_position(uploaded);
-            })
-            .await?;
-
-        pb.finish();
+        upload_file(&path, &s3_path, metadata.len(), &options, s3_client).await?;
     }
 
     Ok(())
@@ -86,7 +258,7 @@ async fn upload_directory(
     dir_path: &Path,
     base_destination: Option<String>,
     relative_path: &str,
-    chunk_size: usize,
+    options: &UploadOptions<'_>,
     s3_client: &S3Client,
 ) -> Result<()> {
     let mut dir = fs::read_dir(dir_path)
@@ -105,7 +277,7 @@ async fn upload_directory(
             if dest.is_empty() || dest.ends_with('/') {
                 format!("{}{}", dest, relative_path)
             } else if relative_path.is_empty() {
-                format!("{}/", dest)
+                dest.clone()
             } else {
                 format!("{}/{}", dest, relative_path)
             }
@@ -115,7 +287,13 @@ async fn upload_directory(
     // Create an empty object to mark the directory
     if !s3_prefix.is_empty() {
         s3_client
-            .put_empty_object(&format!("{}/", s3_prefix))
+            .put_empty_object(
+                &format!("{}/", s3_prefix),
+                &PutOptions {
+                    acl: options.put_options.acl.clone(),
+                    ..Default::default()
+                },
+            )
             .await?;
     }
 
@@ -139,7 +317,7 @@ async fn upload_directory(
                 &path,
                 base_destination.clone(),
                 &next_relative_path,
-                chunk_size,
+                options,
                 s3_client,
             );
             Box::pin(future).await?;
@@ -150,19 +328,7 @@ async fn upload_directory(
                 format!("{}/{}", s3_prefix, entry_name)
             };
 
-            let file_size = metadata.len();
-            let pb = progress::create_upload_progress_bar(
-                file_size,
-                &format!("{}", colors::fmt_path(&path.to_string_lossy())),
-            );
-
-            s3_client
-                .upload_with_progress(&path, &s3_path, chunk_size, |uploaded| {
-                    pb.set_position(uploaded);
-                })
-                .await?;
-
-            pb.finish();
+            upload_file(&path, &s3_path, metadata.len(), options, s3_client).await?;
         }
     }
 
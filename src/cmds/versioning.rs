@@ -0,0 +1,65 @@
+use crate::s3::S3Client;
+use crate::utils::colors;
+use crate::utils::output::OutputFormat;
+use anyhow::Result;
+use clap::ValueEnum;
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MfaDelete {
+    Enabled,
+    Disabled,
+}
+
+pub async fn status(output: OutputFormat, s3_client: &S3Client) -> Result<()> {
+    let versioning = s3_client.get_versioning().await?;
+
+    match output {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&versioning)?),
+        OutputFormat::Text => {
+            let status = match versioning.status.as_deref() {
+                Some("Enabled") => colors::fmt_success("Enabled"),
+                Some(status) => colors::fmt_warn(status),
+                None => colors::fmt_warn("Disabled (never enabled)"),
+            };
+            let mfa_delete = match versioning.mfa_delete.as_deref() {
+                Some("Enabled") => colors::fmt_success("Enabled"),
+                _ => colors::fmt_warn("Disabled"),
+            };
+            println!("{}: {}", colors::fmt_info("Versioning"), status);
+            println!("{}: {}", colors::fmt_info("MFA Delete"), mfa_delete);
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn set(
+    enabled: bool,
+    mfa_delete: Option<MfaDelete>,
+    mfa: Option<String>,
+    s3_client: &S3Client,
+) -> Result<()> {
+    if mfa_delete.is_some() && mfa.is_none() {
+        println!(
+            "{}",
+            colors::fmt_warn("Changing MFA delete requires --mfa \"<serial> <code>\"")
+        );
+        return Ok(());
+    }
+
+    s3_client
+        .put_versioning(
+            enabled,
+            mfa_delete.map(|mfa_delete| mfa_delete == MfaDelete::Enabled),
+            mfa.as_deref(),
+        )
+        .await?;
+
+    let state = if enabled { "enabled" } else { "suspended" };
+    println!(
+        "{} {}",
+        colors::fmt_info("Versioning"),
+        colors::fmt_success(state)
+    );
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use crate::config::Config;
+use crate::s3::S3Client;
+use crate::sse::SseSettings;
+use crate::utils::colors;
+use crate::webdav;
+use anyhow::Result;
+use std::net::SocketAddr;
+
+pub async fn execute(
+    listen: SocketAddr,
+    prefix: Option<String>,
+    s3_client: &S3Client,
+) -> Result<()> {
+    let config = Config::load()?;
+    // Objects are copied within the prefix, so sources carry the same SSE-C
+    // key as the copies
+    let encryption = SseSettings::default().resolve(&config).await?;
+    let source_key = encryption.customer_key.clone();
+    let s3_client = s3_client
+        .with_encryption(encryption)?
+        .with_copy_source_key(source_key)?;
+    let prefix = prefix.unwrap_or_default();
+
+    println!(
+        "{}",
+        colors::fmt_info(&format!(
+            "Serving {}/{} over WebDAV at http://{}",
+            s3_client.bucket_name(),
+            prefix.trim_start_matches('/'),
+            listen
+        ))
+    );
+    // Writes are always allowed, so the warning is printed even on loopback
+    println!(
+        "{}",
+        colors::fmt_warn(if listen.ip().is_loopback() {
+            "Requests are not authenticated, any local user or program can change the objects"
+        } else {
+            "Requests are not authenticated, anyone reaching this address can change the objects"
+        })
+    );
+    webdav::run(
+        s3_client,
+        &prefix,
+        config.upload_chunk_size,
+        config.download_chunk_size,
+        config.content_types,
+        listen,
+    )
+    .await
+}
@@ -0,0 +1,95 @@
+use crate::s3::ObjectMetadata;
+use anyhow::{bail, Result};
+use async_compression::tokio::bufread::{GzipEncoder, ZstdEncoder};
+use async_compression::tokio::write::{GzipDecoder, ZstdDecoder};
+use clap::ValueEnum;
+use tokio::io::{AsyncRead, AsyncWrite, BufReader};
+
+/// User metadata marking objects compressed by s3mgr, without the
+/// `x-amz-meta-` prefix
+pub const META_COMPRESSION: &str = "s3mgr-compression";
+
+/// Compression applied to files while they are uploaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Name used for both Content-Encoding and the metadata marker
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Some(Self::Gzip),
+            "zstd" => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    fn from_extension(key: &str) -> Option<Self> {
+        let (_, extension) = key.rsplit_once('.')?;
+        match extension.to_ascii_lowercase().as_str() {
+            "gz" | "gzip" => Some(Self::Gzip),
+            "zst" | "zstd" => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Tells how an object is compressed. Objects marked by s3mgr are
+    /// always detected; with `force`, Content-Encoding and then the key's
+    /// extension are used for objects uploaded by other tools.
+    pub fn of_object(metadata: &ObjectMetadata, force: bool) -> Result<Option<Self>> {
+        if let Some(name) = metadata.metadata.get(META_COMPRESSION) {
+            return match Self::from_name(name) {
+                Some(compression) => Ok(Some(compression)),
+                None => bail!("Unsupported compression `{}` on {}", name, metadata.key),
+            };
+        }
+        if !force {
+            return Ok(None);
+        }
+
+        let compression = metadata
+            .content_encoding
+            .as_deref()
+            .and_then(Self::from_name)
+            .or_else(|| Self::from_extension(&metadata.key));
+        match compression {
+            Some(compression) => Ok(Some(compression)),
+            None => bail!(
+                "Cannot tell how {} is compressed, it has no gzip or zstd Content-Encoding",
+                metadata.key
+            ),
+        }
+    }
+
+    /// Compresses everything read from `reader`
+    pub fn encoder<'a, R>(self, reader: R) -> Box<dyn AsyncRead + Unpin + Send + 'a>
+    where
+        R: AsyncRead + Unpin + Send + 'a,
+    {
+        let reader = BufReader::new(reader);
+        match self {
+            Self::Gzip => Box::new(GzipEncoder::new(reader)),
+            Self::Zstd => Box::new(ZstdEncoder::new(reader)),
+        }
+    }
+
+    /// Decompresses everything written to it into `writer`
+    pub fn decoder<'a, W>(self, writer: W) -> Box<dyn AsyncWrite + Unpin + Send + 'a>
+    where
+        W: AsyncWrite + Unpin + Send + 'a,
+    {
+        match self {
+            Self::Gzip => Box::new(GzipDecoder::new(writer)),
+            Self::Zstd => Box::new(ZstdDecoder::new(writer)),
+        }
+    }
+}
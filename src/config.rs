@@ -1,5 +1,7 @@
+use crate::sse::SseAlgorithm;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -9,6 +11,10 @@ pub struct S3Config {
     pub region: String,
     pub bucket: String,
     pub endpoint: Option<String>,
+    /// Address buckets in the path (`endpoint/bucket/key`) rather than as
+    /// a subdomain, for endpoints like IP addresses or `localhost`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub path_style: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -16,6 +22,25 @@ pub struct Config {
     pub s3: S3Config,
     pub upload_chunk_size: usize,
     pub download_chunk_size: usize,
+    /// Default bandwidth limit for transfers in bytes per second
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit_rate: Option<usize>,
+    /// Custom file extension to Content-Type mappings used on upload
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub content_types: BTreeMap<String, String>,
+    /// Default server-side encryption for uploads and copies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sse: Option<SseAlgorithm>,
+    /// Default KMS key for SSE-KMS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sse_kms_key_id: Option<String>,
+    /// Default SSE-C key file, used for uploads and downloads alike
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sse_c_key: Option<PathBuf>,
+    /// Default key file for client-side encryption, a passphrase is asked
+    /// for without one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption_key_file: Option<PathBuf>,
 }
 
 impl Config {
@@ -55,7 +80,9 @@ impl Config {
         Ok(())
     }
 
-    fn get_config_path() -> Result<PathBuf> {
+    /// Directory holding the config file and other state such as the
+    /// shell history, created if missing
+    pub fn dir() -> Result<PathBuf> {
         let home =
             dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?;
         let config_dir = home.join(".config").join("s3mgr");
@@ -63,7 +90,11 @@ impl Config {
             "Failed to create config directory at {}",
             config_dir.display()
         ))?;
-        Ok(config_dir.join("config.toml"))
+        Ok(config_dir)
+    }
+
+    fn get_config_path() -> Result<PathBuf> {
+        Ok(Self::dir()?.join("config.toml"))
     }
 
     pub fn save(&self) -> Result<()> {
@@ -87,9 +118,16 @@ impl Default for Config {
                 region: "us-east-1".to_string(),
                 bucket: String::new(),
                 endpoint: None,
+                path_style: false,
             },
             upload_chunk_size: 2 * 1024 * 1024,
             download_chunk_size: 2 * 1024 * 1024,
+            limit_rate: None,
+            content_types: BTreeMap::new(),
+            sse: None,
+            sse_kms_key_id: None,
+            sse_c_key: None,
+            encryption_key_file: None,
         }
     }
 }
@@ -0,0 +1,556 @@
+use crate::config::Config;
+use crate::utils::key;
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::{anyhow, bail, Context, Result};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::io;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{ready, Context as TaskContext, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Cipher written to the object metadata, the only one supported so far
+const CIPHER: &str = "AES-256-GCM";
+/// Plaintext bytes sealed together, each followed by its own tag
+const CHUNK_SIZE: usize = 64 * 1024;
+const TAG_SIZE: usize = 16;
+const SALT_SIZE: usize = 16;
+/// Environment variable read before prompting for a passphrase
+const PASSPHRASE_ENV: &str = "S3MGR_PASSPHRASE";
+
+// User metadata holding the envelope, without the `x-amz-meta-` prefix
+const META_CIPHER: &str = "s3mgr-encryption";
+const META_CHUNK_SIZE: &str = "s3mgr-encryption-chunk-size";
+const META_SALT: &str = "s3mgr-encryption-salt";
+const META_KDF: &str = "s3mgr-encryption-kdf";
+const META_SIZE: &str = "s3mgr-encryption-size";
+
+/// How the master key of an object was obtained
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kdf {
+    /// Read as is from a key file
+    KeyFile,
+    /// Derived from a passphrase and the object's salt
+    Argon2id {
+        m_cost: u32,
+        t_cost: u32,
+        p_cost: u32,
+    },
+}
+
+impl Kdf {
+    fn to_metadata(self) -> String {
+        match self {
+            Self::KeyFile => "none".to_string(),
+            Self::Argon2id {
+                m_cost,
+                t_cost,
+                p_cost,
+            } => format!("argon2id:m={},t={},p={}", m_cost, t_cost, p_cost),
+        }
+    }
+
+    fn from_metadata(value: &str) -> Result<Self> {
+        if value == "none" {
+            return Ok(Self::KeyFile);
+        }
+        let params = value
+            .strip_prefix("argon2id:")
+            .ok_or_else(|| anyhow!("Unsupported key derivation `{}`", value))?;
+        let mut costs = BTreeMap::new();
+        for param in params.split(',') {
+            let (name, cost) = param
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Invalid key derivation parameter `{}`", param))?;
+            costs.insert(name, cost.parse::<u32>()?);
+        }
+        let cost = |name: &str| {
+            costs
+                .get(name)
+                .copied()
+                .ok_or_else(|| anyhow!("Key derivation is missing `{}`", name))
+        };
+        Ok(Self::Argon2id {
+            m_cost: cost("m")?,
+            t_cost: cost("t")?,
+            p_cost: cost("p")?,
+        })
+    }
+}
+
+/// Parameters needed to decrypt an object, stored in its user metadata.
+/// The data itself is a sequence of AES-256-GCM chunks, so it can be
+/// encrypted and decrypted while streaming, whatever the part size of a
+/// multipart upload.
+#[derive(Debug, Clone)]
+pub struct Envelope {
+    chunk_size: usize,
+    salt: [u8; SALT_SIZE],
+    kdf: Kdf,
+    /// Size of the original file, before any compression, unknown for
+    /// streams like stdin
+    size: Option<u64>,
+}
+
+impl Envelope {
+    /// Reads the envelope from an object's user metadata, or `None` if the
+    /// object is not client-side encrypted.
+    pub fn from_metadata(metadata: &BTreeMap<String, String>) -> Result<Option<Self>> {
+        let Some(cipher) = metadata.get(META_CIPHER) else {
+            return Ok(None);
+        };
+        if cipher != CIPHER {
+            bail!("Unsupported client-side encryption `{}`", cipher);
+        }
+
+        let field = |name: &str| {
+            metadata
+                .get(name)
+                .map(String::as_str)
+                .ok_or_else(|| anyhow!("Encrypted object is missing `{}` metadata", name))
+        };
+        let chunk_size: usize = field(META_CHUNK_SIZE)?.parse()?;
+        if chunk_size == 0 {
+            bail!("Invalid encryption chunk size 0");
+        }
+        let salt = BASE64
+            .decode(field(META_SALT)?)?
+            .try_into()
+            .map_err(|_| anyhow!("Invalid encryption salt"))?;
+
+        Ok(Some(Self {
+            chunk_size,
+            salt,
+            kdf: Kdf::from_metadata(field(META_KDF)?)?,
+            size: metadata
+                .get(META_SIZE)
+                .map(|size| size.parse())
+                .transpose()?,
+        }))
+    }
+
+    pub fn to_metadata(&self) -> BTreeMap<String, String> {
+        let mut metadata = BTreeMap::from([
+            (META_CIPHER.to_string(), CIPHER.to_string()),
+            (META_CHUNK_SIZE.to_string(), self.chunk_size.to_string()),
+            (META_SALT.to_string(), BASE64.encode(self.salt)),
+            (META_KDF.to_string(), self.kdf.to_metadata()),
+        ]);
+        if let Some(size) = self.size {
+            metadata.insert(META_SIZE.to_string(), size.to_string());
+        }
+        metadata
+    }
+}
+
+/// Client-side encryption options as given on the command line, before
+/// the defaults from the config are applied.
+#[derive(Debug, Clone, Default)]
+pub struct CseSettings {
+    pub key_file: Option<PathBuf>,
+}
+
+impl CseSettings {
+    /// Resolves the key to use. Without a key file on the command line or
+    /// in the config, a passphrase is asked for when first needed.
+    pub async fn resolve(self, config: &Config) -> Result<Keys> {
+        let key_file = match self.key_file.or(config.encryption_key_file.clone()) {
+            Some(path) => Some(key::read_file(&path, "Encryption key").await?),
+            None => None,
+        };
+        Ok(Keys {
+            key_file,
+            passphrase: Mutex::new(None),
+        })
+    }
+}
+
+/// Source of the master keys, shared by all the files of a transfer so a
+/// passphrase is only asked for once.
+pub struct Keys {
+    key_file: Option<Vec<u8>>,
+    passphrase: Mutex<Option<String>>,
+}
+
+impl Keys {
+    fn passphrase(&self, confirm: bool) -> Result<String> {
+        let mut cached = self.passphrase.lock().unwrap();
+        if let Some(passphrase) = cached.as_ref() {
+            return Ok(passphrase.clone());
+        }
+
+        let passphrase = match std::env::var(PASSPHRASE_ENV) {
+            Ok(passphrase) => passphrase,
+            Err(_) => {
+                let prompt = dialoguer::Password::new().with_prompt("Encryption passphrase");
+                let prompt = if confirm {
+                    prompt.with_confirmation("Confirm passphrase", "Passphrases do not match")
+                } else {
+                    prompt
+                };
+                prompt.interact().context("Failed to read the passphrase")?
+            }
+        };
+        if passphrase.is_empty() {
+            bail!("The encryption passphrase cannot be empty");
+        }
+        *cached = Some(passphrase.clone());
+        Ok(passphrase)
+    }
+
+    fn master_key(&self, kdf: Kdf, salt: &[u8], confirm: bool) -> Result<Vec<u8>> {
+        match kdf {
+            Kdf::KeyFile => self
+                .key_file
+                .clone()
+                .context("Object was encrypted with a key file, pass it with --key-file"),
+            Kdf::Argon2id {
+                m_cost,
+                t_cost,
+                p_cost,
+            } => {
+                let params = Params::new(m_cost, t_cost, p_cost, Some(key::KEY_LENGTH))
+                    .map_err(|e| anyhow!("Invalid key derivation parameters: {}", e))?;
+                let mut master = vec![0; key::KEY_LENGTH];
+                Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                    .hash_password_into(self.passphrase(confirm)?.as_bytes(), salt, &mut master)
+                    .map_err(|e| anyhow!("Failed to derive the key: {}", e))?;
+                Ok(master)
+            }
+        }
+    }
+
+    /// Creates the envelope and cipher for a new object of `size` bytes,
+    /// if known
+    pub fn for_upload(&self, size: Option<u64>) -> Result<(Envelope, Aes256Gcm)> {
+        let kdf = match self.key_file {
+            Some(_) => Kdf::KeyFile,
+            None => Kdf::Argon2id {
+                m_cost: Params::DEFAULT_M_COST,
+                t_cost: Params::DEFAULT_T_COST,
+                p_cost: Params::DEFAULT_P_COST,
+            },
+        };
+        let mut salt = [0; SALT_SIZE];
+        OsRng.fill_bytes(&mut salt);
+
+        let envelope = Envelope {
+            chunk_size: CHUNK_SIZE,
+            salt,
+            kdf,
+            size,
+        };
+        let cipher = object_cipher(&self.master_key(kdf, &salt, true)?, &salt)?;
+        Ok((envelope, cipher))
+    }
+
+    pub fn for_download(&self, envelope: &Envelope) -> Result<Aes256Gcm> {
+        let master = self.master_key(envelope.kdf, &envelope.salt, false)?;
+        object_cipher(&master, &envelope.salt)
+    }
+}
+
+/// Every object gets its own key derived from the master key and its salt,
+/// so chunk nonces can simply count from zero.
+fn object_cipher(master: &[u8], salt: &[u8]) -> Result<Aes256Gcm> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(master)?;
+    mac.update(CIPHER.as_bytes());
+    mac.update(salt);
+    Ok(Aes256Gcm::new(&mac.finalize().into_bytes()))
+}
+
+/// Nonce of a chunk: its index and whether it is the last one, so chunks
+/// cannot be reordered and the stream cannot be truncated unnoticed.
+fn chunk_nonce(index: u32, last: bool) -> [u8; 12] {
+    let mut nonce = [0; 12];
+    nonce[7..11].copy_from_slice(&index.to_be_bytes());
+    nonce[11] = last as u8;
+    nonce
+}
+
+fn next_index(index: &mut u32) -> io::Result<u32> {
+    let current = *index;
+    *index = index
+        .checked_add(1)
+        .ok_or_else(|| io::Error::other("Too many chunks to encrypt"))?;
+    Ok(current)
+}
+
+/// Encrypts everything read from `inner`
+pub struct EncryptReader<R> {
+    inner: R,
+    cipher: Aes256Gcm,
+    chunk_size: usize,
+    index: u32,
+    /// Plaintext read ahead, a chunk is only sealed once it is known
+    /// whether it is the last one
+    input: Vec<u8>,
+    output: Vec<u8>,
+    position: usize,
+    eof: bool,
+    finished: bool,
+}
+
+impl<R> EncryptReader<R> {
+    pub fn new(inner: R, envelope: &Envelope, cipher: Aes256Gcm) -> Self {
+        Self {
+            inner,
+            cipher,
+            chunk_size: envelope.chunk_size,
+            index: 0,
+            input: Vec::with_capacity(envelope.chunk_size + 1),
+            output: Vec::new(),
+            position: 0,
+            eof: false,
+            finished: false,
+        }
+    }
+
+    fn seal(&mut self, len: usize, last: bool) -> io::Result<()> {
+        let nonce = chunk_nonce(next_index(&mut self.index)?, last);
+        self.output = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), &self.input[..len])
+            .map_err(|_| io::Error::other("Failed to encrypt"))?;
+        self.position = 0;
+        self.input.drain(..len);
+        Ok(())
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for EncryptReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            if this.position < this.output.len() {
+                let n = buf.remaining().min(this.output.len() - this.position);
+                buf.put_slice(&this.output[this.position..this.position + n]);
+                this.position += n;
+                return Poll::Ready(Ok(()));
+            }
+            if this.finished {
+                return Poll::Ready(Ok(()));
+            }
+            if this.input.len() > this.chunk_size {
+                this.seal(this.chunk_size, false)?;
+                continue;
+            }
+            if this.eof {
+                this.seal(this.input.len(), true)?;
+                this.finished = true;
+                continue;
+            }
+
+            let start = this.input.len();
+            this.input.resize(this.chunk_size + 1, 0);
+            let mut read_buf = ReadBuf::new(&mut this.input[start..]);
+            let result = Pin::new(&mut this.inner).poll_read(cx, &mut read_buf);
+            let n = read_buf.filled().len();
+            this.input.truncate(start + n);
+            ready!(result)?;
+            if n == 0 {
+                this.eof = true;
+            }
+        }
+    }
+}
+
+/// Decrypts everything written to it into `inner`. The last chunk is only
+/// checked on shutdown, which must not be skipped.
+pub struct DecryptWriter<W> {
+    inner: W,
+    cipher: Aes256Gcm,
+    /// Size of a sealed chunk, tag included
+    chunk_size: usize,
+    index: u32,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    position: usize,
+    finished: bool,
+}
+
+impl<W> DecryptWriter<W> {
+    pub fn new(inner: W, envelope: &Envelope, cipher: Aes256Gcm) -> Self {
+        let chunk_size = envelope.chunk_size + TAG_SIZE;
+        Self {
+            inner,
+            cipher,
+            chunk_size,
+            index: 0,
+            input: Vec::with_capacity(chunk_size + 1),
+            output: Vec::new(),
+            position: 0,
+            finished: false,
+        }
+    }
+
+    fn open(&mut self, len: usize, last: bool) -> io::Result<()> {
+        let nonce = chunk_nonce(next_index(&mut self.index)?, last);
+        self.output = self
+            .cipher
+            .decrypt(Nonce::from_slice(&nonce), &self.input[..len])
+            .map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Decryption failed, wrong key or corrupted object",
+                )
+            })?;
+        self.position = 0;
+        self.input.drain(..len);
+        Ok(())
+    }
+}
+
+impl<W: AsyncWrite + Unpin> DecryptWriter<W> {
+    fn poll_drain(&mut self, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        while self.position < self.output.len() {
+            let n =
+                ready!(Pin::new(&mut self.inner).poll_write(cx, &self.output[self.position..]))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.position += n;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for DecryptWriter<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        loop {
+            ready!(this.poll_drain(cx))?;
+            if this.input.len() > this.chunk_size {
+                this.open(this.chunk_size, false)?;
+                continue;
+            }
+            break;
+        }
+
+        let n = buf.len().min(this.chunk_size + 1 - this.input.len());
+        this.input.extend_from_slice(&buf[..n]);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            ready!(this.poll_drain(cx))?;
+            if this.input.len() > this.chunk_size {
+                this.open(this.chunk_size, false)?;
+            } else if !this.finished {
+                this.open(this.input.len(), true)?;
+                this.finished = true;
+            } else {
+                break;
+            }
+        }
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Small chunks so a few bytes already span several of them
+    const TEST_CHUNK_SIZE: usize = 4;
+    const SEALED_SIZE: usize = TEST_CHUNK_SIZE + TAG_SIZE;
+
+    fn envelope() -> Envelope {
+        Envelope {
+            chunk_size: TEST_CHUNK_SIZE,
+            salt: [7; SALT_SIZE],
+            kdf: Kdf::KeyFile,
+            size: None,
+        }
+    }
+
+    fn cipher(key: u8) -> Aes256Gcm {
+        let keys = Keys {
+            key_file: Some(vec![key; key::KEY_LENGTH]),
+            passphrase: Mutex::new(None),
+        };
+        keys.for_download(&envelope()).unwrap()
+    }
+
+    async fn encrypt(data: &[u8]) -> Vec<u8> {
+        let mut sealed = Vec::new();
+        EncryptReader::new(data, &envelope(), cipher(1))
+            .read_to_end(&mut sealed)
+            .await
+            .unwrap();
+        sealed
+    }
+
+    async fn decrypt(sealed: &[u8], key: u8) -> io::Result<Vec<u8>> {
+        let mut writer = DecryptWriter::new(Vec::new(), &envelope(), cipher(key));
+        writer.write_all(sealed).await?;
+        writer.shutdown().await?;
+        Ok(writer.inner)
+    }
+
+    #[tokio::test]
+    async fn round_trips() {
+        for data in [&b""[..], b"abcd", b"abcdefghij", b"abcdefghijkl"] {
+            let sealed = encrypt(data).await;
+            let chunks = data.len().div_ceil(TEST_CHUNK_SIZE).max(1);
+            assert_eq!(sealed.len(), data.len() + chunks * TAG_SIZE);
+            assert_eq!(decrypt(&sealed, 1).await.unwrap(), data);
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_a_truncated_stream() {
+        let sealed = encrypt(b"abcdefghijkl").await;
+        assert!(decrypt(&sealed[..2 * SEALED_SIZE], 1).await.is_err());
+        assert!(decrypt(&sealed[..sealed.len() - 1], 1).await.is_err());
+        assert!(decrypt(b"", 1).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_a_tampered_chunk() {
+        let mut sealed = encrypt(b"abcdefghijkl").await;
+        sealed[SEALED_SIZE + 1] ^= 1;
+        assert!(decrypt(&sealed, 1).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_reordered_chunks() {
+        let sealed = encrypt(b"abcdefghijkl").await;
+        let mut reordered = sealed[SEALED_SIZE..2 * SEALED_SIZE].to_vec();
+        reordered.extend_from_slice(&sealed[..SEALED_SIZE]);
+        reordered.extend_from_slice(&sealed[2 * SEALED_SIZE..]);
+        assert!(decrypt(&reordered, 1).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_a_wrong_key() {
+        let sealed = encrypt(b"abcdefghij").await;
+        let error = decrypt(&sealed, 2).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,327 @@
+//! A read-only HTTP gateway to the bucket: objects are served to plain,
+//! unauthenticated GETs, and prefixes as HTML directory listings.
+
+use crate::s3::{dir_prefix, S3Client};
+use crate::utils::server::{self, header, Range, LINK};
+use crate::utils::size::format_size;
+use crate::utils::{colors, xml};
+use http::header::{
+    ACCEPT_RANGES, ALLOW, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_ENCODING, CONTENT_LANGUAGE,
+    CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, EXPIRES, IF_NONE_MATCH, LAST_MODIFIED,
+    LOCATION, RANGE,
+};
+use http::{HeaderMap, HeaderName, HeaderValue, Method, Request, Response, StatusCode};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Server};
+use percent_encoding::utf8_percent_encode;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// Answers GET and HEAD requests for the keys under a prefix
+pub struct Gateway {
+    s3_client: S3Client,
+    /// Prefix the URL paths are resolved under, empty or ending with `/`
+    prefix: String,
+    /// Bytes fetched from S3 with each ranged GET
+    chunk_size: usize,
+}
+
+fn insert(headers: &mut HeaderMap, name: HeaderName, value: Option<&str>) {
+    if let Some(value) = value.and_then(|value| HeaderValue::from_str(value).ok()) {
+        headers.insert(name, value);
+    }
+}
+
+/// An HTML page listing the sub-prefixes and objects of `dir`
+async fn listing(s3_client: &S3Client, path: &str, dir: &str) -> anyhow::Result<Option<String>> {
+    let (prefixes, objects) = s3_client.list_dir(dir).await?;
+    if prefixes.is_empty()
+        && objects.is_empty()
+        && !path.is_empty()
+        && s3_client.find_object(dir).await?.is_none()
+    {
+        return Ok(None);
+    }
+
+    let title = xml::escape(&format!("Index of /{}", path));
+    let mut rows = String::new();
+    if !path.is_empty() {
+        rows.push_str("<tr><td><a href=\"../\">../</a></td><td></td><td></td></tr>\n");
+    }
+    let mut row = |name: &str, size: &str, modified: &str| {
+        rows.push_str(&format!(
+            "<tr><td><a href=\"{}\">{}</a></td><td>{}</td><td>{}</td></tr>\n",
+            xml::escape(&utf8_percent_encode(name, LINK).to_string()),
+            xml::escape(name),
+            size,
+            xml::escape(modified)
+        ));
+    };
+    for prefix in &prefixes {
+        row(&prefix[dir.len()..], "-", "");
+    }
+    for object in &objects {
+        row(
+            &object.key[dir.len()..],
+            &format_size(object.size),
+            &object.last_modified,
+        );
+    }
+
+    Ok(Some(format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{title}</title></head>\n<body>\n<h1>{title}</h1>\n<table>\n<tr><th>Name</th><th>Size</th><th>Last modified</th></tr>\n{rows}</table>\n</body>\n</html>\n"
+    )))
+}
+
+async fn get_object(
+    s3_client: &S3Client,
+    chunk_size: usize,
+    request: &Request<Body>,
+    key: &str,
+) -> anyhow::Result<Response<Body>> {
+    let Some(metadata) = s3_client.find_object(key).await? else {
+        if s3_client.is_directory(key).await? {
+            let mut response = server::text(StatusCode::MOVED_PERMANENTLY, "Moved Permanently");
+            insert(
+                response.headers_mut(),
+                LOCATION,
+                Some(&format!("{}/", request.uri().path())),
+            );
+            return Ok(response);
+        }
+        return Ok(server::text(StatusCode::NOT_FOUND, "Not Found"));
+    };
+
+    let mut headers = HeaderMap::new();
+    headers.insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    insert(&mut headers, ETAG, metadata.e_tag.as_deref());
+    insert(
+        &mut headers,
+        LAST_MODIFIED,
+        metadata.last_modified.as_deref(),
+    );
+
+    let not_modified = header(request.headers(), IF_NONE_MATCH)
+        .zip(metadata.e_tag.as_deref())
+        .is_some_and(|(list, etag)| server::etag_matches(list, etag));
+    if not_modified {
+        let mut response = Response::new(Body::empty());
+        *response.status_mut() = StatusCode::NOT_MODIFIED;
+        *response.headers_mut() = headers;
+        return Ok(response);
+    }
+
+    let size = metadata.size;
+    let (status, start, length) =
+        match header(request.headers(), RANGE).and_then(|range| server::parse_range(range, size)) {
+            Some(Range::Unsatisfiable) => {
+                let mut response =
+                    server::text(StatusCode::RANGE_NOT_SATISFIABLE, "Range Not Satisfiable");
+                insert(
+                    response.headers_mut(),
+                    CONTENT_RANGE,
+                    Some(&format!("bytes */{}", size)),
+                );
+                return Ok(response);
+            }
+            Some(Range::Bytes(first, last)) => {
+                let content_range = format!("bytes {}-{}/{}", first, last, size);
+                insert(&mut headers, CONTENT_RANGE, Some(&content_range));
+                (StatusCode::PARTIAL_CONTENT, first, last - first + 1)
+            }
+            None => (StatusCode::OK, 0, size),
+        };
+
+    insert(
+        &mut headers,
+        CONTENT_TYPE,
+        Some(
+            metadata
+                .content_type
+                .as_deref()
+                .unwrap_or("application/octet-stream"),
+        ),
+    );
+    insert(
+        &mut headers,
+        CACHE_CONTROL,
+        metadata.cache_control.as_deref(),
+    );
+    insert(
+        &mut headers,
+        CONTENT_DISPOSITION,
+        metadata.content_disposition.as_deref(),
+    );
+    insert(
+        &mut headers,
+        CONTENT_ENCODING,
+        metadata.content_encoding.as_deref(),
+    );
+    insert(
+        &mut headers,
+        CONTENT_LANGUAGE,
+        metadata.content_language.as_deref(),
+    );
+    insert(&mut headers, EXPIRES, metadata.expires.as_deref());
+    headers.insert(CONTENT_LENGTH, length.into());
+
+    let body = if request.method() == Method::HEAD || length == 0 {
+        Body::empty()
+    } else {
+        // Fetched one chunk at a time as the client reads, so large
+        // objects are never held in memory
+        let s3_client = s3_client.clone();
+        let key = key.to_string();
+        let chunk_size = chunk_size as u64;
+        let end = start + length;
+        let stream = futures::stream::try_unfold(start, move |position| {
+            let s3_client = s3_client.clone();
+            let key = key.clone();
+            async move {
+                if position >= end {
+                    return anyhow::Ok(None);
+                }
+                let last = (position + chunk_size).min(end) - 1;
+                let chunk = s3_client.get_range(&key, position, last).await?;
+                if chunk.is_empty() {
+                    anyhow::bail!("{} ended before byte {}", key, position);
+                }
+                let next = position + chunk.len() as u64;
+                Ok(Some((chunk, next)))
+            }
+        });
+        Body::wrap_stream(stream)
+    };
+    let mut response = Response::new(body);
+    *response.status_mut() = status;
+    *response.headers_mut() = headers;
+    Ok(response)
+}
+
+impl Gateway {
+    pub fn new(s3_client: S3Client, prefix: &str, chunk_size: usize) -> Self {
+        Self {
+            s3_client,
+            prefix: dir_prefix(prefix.trim_start_matches('/')),
+            chunk_size,
+        }
+    }
+
+    pub fn s3_client(&self) -> &S3Client {
+        &self.s3_client
+    }
+
+    /// The key a URL path from `server::request_path` stands for
+    pub fn key(&self, path: &str) -> String {
+        format!("{}{}", self.prefix, path)
+    }
+
+    /// Streams the object at the request's path, or lists the prefix when
+    /// the path ends with `/`
+    pub async fn get(&self, request: &Request<Body>) -> anyhow::Result<Response<Body>> {
+        let Some(path) = server::request_path(request.uri()) else {
+            return Ok(server::text(StatusCode::BAD_REQUEST, "Invalid path"));
+        };
+        let key = self.key(&path);
+
+        if !path.is_empty() && !path.ends_with('/') {
+            return get_object(&self.s3_client, self.chunk_size, request, &key).await;
+        }
+        let Some(page) = listing(&self.s3_client, &path, &key).await? else {
+            return Ok(server::text(StatusCode::NOT_FOUND, "Not Found"));
+        };
+        let length = page.len();
+        let mut response = Response::new(if request.method() == Method::HEAD {
+            Body::empty()
+        } else {
+            Body::from(page)
+        });
+        let headers = response.headers_mut();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/html; charset=utf-8"),
+        );
+        headers.insert(CONTENT_LENGTH, length.into());
+        Ok(response)
+    }
+}
+
+async fn route(gateway: &Gateway, request: Request<Body>) -> anyhow::Result<Response<Body>> {
+    if request.method() != Method::GET && request.method() != Method::HEAD {
+        let mut response = server::text(StatusCode::METHOD_NOT_ALLOWED, "Method Not Allowed");
+        response
+            .headers_mut()
+            .insert(ALLOW, HeaderValue::from_static("GET, HEAD"));
+        return Ok(response);
+    }
+    gateway.get(&request).await
+}
+
+async fn handle(
+    gateway: Arc<Gateway>,
+    request: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let response = match route(&gateway, request).await {
+        Ok(response) => response,
+        Err(e) => {
+            println!("{}", colors::fmt_error(&format!("{}: {}", path, e)));
+            server::text(StatusCode::BAD_GATEWAY, "The bucket could not be read")
+        }
+    };
+    server::log_request(&method, &path, response.status());
+    Ok(response)
+}
+
+/// Serves the keys under `prefix` at `address` until the process is stopped
+pub async fn run(
+    s3_client: S3Client,
+    prefix: &str,
+    chunk_size: usize,
+    address: SocketAddr,
+) -> anyhow::Result<()> {
+    let gateway = Arc::new(Gateway::new(s3_client, prefix, chunk_size));
+    let make_service = make_service_fn(move |_| {
+        let gateway = Arc::clone(&gateway);
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                handle(Arc::clone(&gateway), request)
+            }))
+        }
+    });
+    Server::try_bind(&address)?.serve(make_service).await?;
+    Ok(())
+}
+
+/// A gateway over `public/` of a bucket nothing answers for, so tests
+/// only pass when a request is refused before S3 is asked
+#[cfg(test)]
+pub fn unreachable_gateway() -> Gateway {
+    let s3_client = S3Client::new(&crate::config::S3Config {
+        access_key: "test".to_string(),
+        secret_key: "test".to_string(),
+        region: "us-east-1".to_string(),
+        bucket: "bucket".to_string(),
+        endpoint: Some("http://127.0.0.1:9".to_string()),
+        path_style: true,
+    })
+    .unwrap();
+    Gateway::new(s3_client, "public/", 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn refuses_paths_outside_the_prefix() {
+        let gateway = unreachable_gateway();
+        for uri in ["/../secret/s.txt", "/%2e%2e/secret/s.txt", "/a//b"] {
+            let request = Request::get(uri).body(Body::empty()).unwrap();
+            let response = gateway.get(&request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", uri);
+        }
+    }
+}
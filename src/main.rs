@@ -1,12 +1,27 @@
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
 mod cmds;
+mod compress;
 mod config;
+mod cse;
+mod gateway;
 mod s3;
+mod serve;
+mod sse;
 mod utils;
+mod webdav;
+use cmds::cat::Selection;
+use compress::Compression;
 use config::Config;
-use s3::S3Client;
+use cse::CseSettings;
+use s3::{PutOptions, S3Client};
+use sse::{SseAlgorithm, SseSettings};
+use utils::duration::{parse_duration, parse_expires};
+use utils::kv::parse_key_value;
+use utils::output::{OutputFormat, RulesFormat};
 use utils::size::parse_human_size;
 
 #[derive(Parser)]
@@ -21,17 +36,230 @@ struct Cli {
     command: Commands,
 }
 
+/// Headers and metadata applied to objects written by `up` and `cp`
+#[derive(Args, Clone)]
+struct PutArgs {
+    /// Content-Type for written files (detected from the file by default)
+    #[arg(long = "content-type")]
+    content_type: Option<String>,
+    /// Cache-Control header (e.g., "public, max-age=3600")
+    #[arg(long = "cache-control")]
+    cache_control: Option<String>,
+    /// Content-Disposition header (e.g., attachment)
+    #[arg(long = "content-disposition")]
+    content_disposition: Option<String>,
+    /// Content-Encoding header (e.g., gzip)
+    #[arg(long = "content-encoding")]
+    content_encoding: Option<String>,
+    /// Content-Language header (e.g., en-US)
+    #[arg(long = "content-language")]
+    content_language: Option<String>,
+    /// Expires header, as an HTTP date or a duration from now (e.g., 7d)
+    #[arg(long, value_parser = parse_expires)]
+    expires: Option<String>,
+    /// User metadata stored as x-amz-meta-* (repeatable, e.g., --meta owner=alice)
+    #[arg(long = "meta", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+    meta: Vec<(String, String)>,
+    /// Canned ACL for written files
+    #[arg(long, value_enum)]
+    acl: Option<cmds::acl::CannedAcl>,
+}
+
+impl PutArgs {
+    fn to_put_options(&self) -> PutOptions {
+        PutOptions {
+            content_type: self.content_type.clone(),
+            cache_control: self.cache_control.clone(),
+            content_disposition: self.content_disposition.clone(),
+            content_encoding: self.content_encoding.clone(),
+            content_language: self.content_language.clone(),
+            expires: self.expires.clone(),
+            metadata: self.meta.iter().cloned().collect(),
+            tags: BTreeMap::new(),
+            acl: self.acl.map(|acl| acl.as_str().to_string()),
+        }
+    }
+}
+
+#[derive(Args, Clone)]
+struct SseArgs {
+    /// Server-side encryption for written files
+    #[arg(long, value_enum)]
+    sse: Option<SseAlgorithm>,
+    /// KMS key ID for SSE-KMS (implies --sse aws:kms)
+    #[arg(long = "sse-kms-key-id")]
+    sse_kms_key_id: Option<String>,
+    #[command(flatten)]
+    customer: SseCustomerArgs,
+}
+
+impl SseArgs {
+    fn to_settings(&self) -> SseSettings {
+        SseSettings {
+            algorithm: self.sse,
+            kms_key_id: self.sse_kms_key_id.clone(),
+            customer_key: self.customer.sse_c_key.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Args, Clone)]
+struct SseCustomerArgs {
+    /// File holding a 32-byte SSE-C key, raw or as base64 or hex
+    #[arg(long = "sse-c-key", value_name = "FILE")]
+    sse_c_key: Option<PathBuf>,
+}
+
+impl SseCustomerArgs {
+    fn to_settings(&self) -> SseSettings {
+        SseSettings {
+            customer_key: self.sse_c_key.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Args, Clone)]
+struct CseArgs {
+    /// Key file for client-side encryption, 32 bytes raw or as base64 or hex
+    /// (a passphrase is asked for without one)
+    #[arg(long = "key-file", value_name = "FILE")]
+    key_file: Option<PathBuf>,
+}
+
+impl CseArgs {
+    fn to_settings(&self) -> CseSettings {
+        CseSettings {
+            key_file: self.key_file.clone(),
+        }
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// List files in the S3 bucket
     Ls {
         /// Optional path prefix to filter files
         path: Option<PathBuf>,
+        /// Only list files with this tag (repeatable, all must match)
+        #[arg(long = "tag", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+        tags: Vec<(String, String)>,
+        /// List every version and delete marker instead of the current files
+        #[arg(long)]
+        versions: bool,
+    },
+    /// Show the storage used by each sub-prefix
+    Du {
+        /// Prefix to measure, the whole bucket by default
+        path: Option<String>,
+        /// How many levels of sub-prefixes to show
+        #[arg(short, long, default_value_t = 1)]
+        depth: usize,
+        /// Order of the sub-prefixes
+        #[arg(long, value_enum, default_value = "name")]
+        sort: cmds::du::SortBy,
+        /// Only show the total
+        #[arg(short, long, conflicts_with = "depth")]
+        summarize: bool,
+        /// Break the totals down by storage class
+        #[arg(long)]
+        by_storage_class: bool,
+        /// Include noncurrent versions
+        #[arg(long)]
+        versions: bool,
+    },
+    /// Show the files and directories under a prefix as a tree
+    Tree {
+        /// Prefix to show, the whole bucket by default
+        path: Option<String>,
+        /// Descend at most this many directories deep
+        #[arg(short = 'L', long = "level")]
+        level: Option<usize>,
+        /// Show the size of every file and directory
+        #[arg(long)]
+        sizes: bool,
+        /// Only show directories
+        #[arg(long)]
+        dirs_only: bool,
+    },
+    /// Search for files and directories under a prefix, like find(1)
+    Find {
+        /// Prefix to search, / for the whole bucket
+        prefix: String,
+        /// Predicates: -name, -iname, -regex, -size, -mtime, -storage-class,
+        /// -tag and -type. Actions: -print, -print0, -delete, -exec-dl DIR
+        /// and -exec CMD {} ;
+        #[arg(
+            trailing_var_arg = true,
+            allow_hyphen_values = true,
+            value_name = "EXPRESSION"
+        )]
+        expression: Vec<String>,
+    },
+    /// Browse the bucket and the local filesystem side by side
+    Browse {
+        /// Prefix to start in, the bucket root by default
+        prefix: Option<String>,
+        /// Local directory to start in, the current one by default
+        #[arg(short, long)]
+        local: Option<PathBuf>,
+    },
+    /// Open an interactive shell with a current prefix
+    Shell,
+    /// Print the completion script for a shell, including S3 key completion
+    Completions {
+        #[arg(value_enum)]
+        shell: cmds::completions::CompletionShell,
+    },
+    /// Serve a local directory as an S3-compatible endpoint, one bucket per subdirectory
+    Serve {
+        /// Directory whose subdirectories are the buckets
+        #[arg(long, default_value = ".")]
+        root: PathBuf,
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1")]
+        address: IpAddr,
+        #[arg(short, long, default_value_t = 9000)]
+        port: u16,
+        /// Access key requests must be signed with
+        #[arg(long)]
+        access_key: String,
+        /// Secret key requests must be signed with
+        #[arg(long)]
+        secret_key: String,
+    },
+    /// Serve the bucket read-only over plain HTTP, with directory listings for prefixes
+    Gateway {
+        /// Address and port to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        listen: SocketAddr,
+        /// Prefix to expose, the whole bucket by default
+        #[arg(long)]
+        prefix: Option<String>,
+    },
+    /// Serve the bucket over WebDAV, so file managers can mount it (without authentication)
+    Webdav {
+        /// Address and port to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        listen: SocketAddr,
+        /// Prefix to expose, the whole bucket by default
+        #[arg(long)]
+        prefix: Option<String>,
+    },
+    /// List the keys starting with a prefix, used by the completion scripts
+    #[command(name = "__complete", hide = true)]
+    Complete {
+        #[arg(default_value = "", allow_hyphen_values = true)]
+        word: String,
     },
     /// Create a new directory
     Md {
         /// Path of directory to create
         path: PathBuf,
+        /// Canned ACL for the directory marker
+        #[arg(long, value_enum)]
+        acl: Option<cmds::acl::CannedAcl>,
     },
     /// Move a file from source to destination
     Mv {
@@ -46,11 +274,52 @@ enum Commands {
         source: PathBuf,
         /// Destination path in S3
         destination: PathBuf,
+        /// Limit transfer bandwidth in bytes per second (e.g., 10M, 512K)
+        #[arg(long = "limit-rate", value_parser = parse_human_size)]
+        limit_rate: Option<usize>,
+        /// Copy on the server, keeping (COPY) or replacing (REPLACE) the metadata
+        #[arg(long = "metadata-directive", value_enum)]
+        metadata_directive: Option<cmds::cp::MetadataDirective>,
+        /// Copy a specific version of the source
+        #[arg(long = "version-id")]
+        version_id: Option<String>,
+        #[command(flatten)]
+        put_args: PutArgs,
+        #[command(flatten)]
+        sse_args: SseArgs,
+        /// File holding the SSE-C key the source is encrypted with
+        #[arg(long = "sse-c-source-key", value_name = "FILE")]
+        sse_c_source_key: Option<PathBuf>,
     },
     /// Display the contents of a file
     Cat {
         /// Path of the file to display
         path: PathBuf,
+        /// Display a specific version of the file
+        #[arg(long = "version-id")]
+        version_id: Option<String>,
+        /// Only display bytes START-END (inclusive), START- or the last -COUNT
+        #[arg(long, value_name = "RANGE", value_parser = cmds::cat::parse_range, group = "part", allow_hyphen_values = true)]
+        range: Option<cmds::cat::ByteRange>,
+        /// Only display the first COUNT bytes (e.g., 1K), or lines with -n
+        #[arg(long, value_name = "COUNT", value_parser = parse_human_size, group = "part")]
+        head: Option<usize>,
+        /// Only display the last COUNT bytes (e.g., 1K), or lines with -n
+        #[arg(long, value_name = "COUNT", value_parser = parse_human_size, group = "part")]
+        tail: Option<usize>,
+        /// Count --head and --tail in lines instead of bytes
+        #[arg(short = 'n', long, requires = "part", conflicts_with = "range")]
+        lines: bool,
+        /// Display a hex dump, for binary files
+        #[arg(long)]
+        hex: bool,
+        #[command(flatten)]
+        sse_args: SseCustomerArgs,
+        #[command(flatten)]
+        cse_args: CseArgs,
+        /// Decompress by Content-Encoding or extension, even if not compressed by up
+        #[arg(long)]
+        decompress: bool,
     },
     /// Remove a file or directory
     Rm {
@@ -62,7 +331,7 @@ enum Commands {
     },
     /// Upload a local file to S3
     Up {
-        /// Local file path to upload
+        /// Local file path to upload, or - to stream stdin
         path: PathBuf,
         /// Destination path in S3 (optional)
         #[arg(short, long)]
@@ -73,6 +342,24 @@ enum Commands {
         /// Chunk size for uploading files (e.g., 5M, 1G, 512K, default: 5MB)
         #[arg(short = 'c', long = "chunk-size", value_parser = parse_human_size)]
         chunk_size: Option<usize>,
+        /// Limit transfer bandwidth in bytes per second (e.g., 10M, 512K)
+        #[arg(long = "limit-rate", value_parser = parse_human_size)]
+        limit_rate: Option<usize>,
+        #[command(flatten)]
+        put_args: PutArgs,
+        /// Tag attached to uploaded files (repeatable, e.g., --tag env=prod)
+        #[arg(long = "tag", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+        tags: Vec<(String, String)>,
+        #[command(flatten)]
+        sse_args: SseArgs,
+        /// Compress files while uploading, they are decompressed again by dl and cat
+        #[arg(long, value_enum, value_name = "ALGORITHM")]
+        compress: Option<Compression>,
+        /// Encrypt files before uploading, so the provider never sees the plaintext
+        #[arg(long)]
+        encrypt: bool,
+        #[command(flatten)]
+        cse_args: CseArgs,
     },
     /// Download a file from S3
     Dl {
@@ -87,6 +374,98 @@ enum Commands {
         /// Chunk size for downloading files (e.g., 5M, 1G, 512K, default: 5MB)
         #[arg(short = 'c', long = "chunk-size", value_parser = parse_human_size)]
         chunk_size: Option<usize>,
+        /// Limit transfer bandwidth in bytes per second (e.g., 10M, 512K)
+        #[arg(long = "limit-rate", value_parser = parse_human_size)]
+        limit_rate: Option<usize>,
+        /// Download a specific version of the file
+        #[arg(long = "version-id")]
+        version_id: Option<String>,
+        #[command(flatten)]
+        sse_args: SseCustomerArgs,
+        #[command(flatten)]
+        cse_args: CseArgs,
+        /// Decompress by Content-Encoding or extension, even if not compressed by up
+        #[arg(long)]
+        decompress: bool,
+    },
+    /// Generate a presigned URL for a file
+    Presign {
+        /// Path of the file in S3 (or a prefix with -r)
+        key: String,
+        /// Time until the URL expires (e.g., 30m, 1h, 7d, default: 1h)
+        #[arg(short = 'e', long, default_value = "1h", value_parser = parse_duration)]
+        expires: u64,
+        /// HTTP method the URL is valid for
+        #[arg(short = 'm', long, value_enum, default_value = "GET")]
+        method: cmds::presign::Method,
+        /// Override the Content-Type returned for GET requests
+        #[arg(long = "content-type")]
+        content_type: Option<String>,
+        /// Override the Content-Disposition returned for GET requests (e.g., attachment)
+        #[arg(long)]
+        disposition: Option<String>,
+        /// Presign every file under the prefix
+        #[arg(short, long)]
+        recursive: bool,
+        /// Output format for recursive presigning
+        #[arg(short = 'f', long, value_enum, default_value = "csv")]
+        format: cmds::presign::Format,
+    },
+    /// Show the metadata of a file, or aggregated information for a prefix
+    Stat {
+        /// Path of the file or prefix in S3
+        path: String,
+        /// Output format
+        #[arg(short = 'o', long, value_enum, default_value = "text")]
+        output: OutputFormat,
+        /// Show a specific version of the file
+        #[arg(long = "version-id")]
+        version_id: Option<String>,
+        #[command(flatten)]
+        sse_args: SseCustomerArgs,
+    },
+    /// Show, set or remove the tags of a file
+    Tag {
+        #[command(subcommand)]
+        action: TagAction,
+    },
+    /// Show, enable or suspend versioning of the bucket
+    Versioning {
+        #[command(subcommand)]
+        action: VersioningAction,
+    },
+    /// Show or change the lifecycle rules of the bucket
+    Lifecycle {
+        #[command(subcommand)]
+        action: LifecycleAction,
+    },
+    /// Show or change the ACL of a file
+    Acl {
+        #[command(subcommand)]
+        action: AclAction,
+    },
+    /// Show or change the bucket policy
+    Policy {
+        #[command(subcommand)]
+        action: PolicyAction,
+    },
+    /// Show or change the CORS rules of the bucket
+    Cors {
+        #[command(subcommand)]
+        action: CorsAction,
+    },
+    /// Make an old version of a file its current version
+    Restore {
+        /// Path of the file in S3
+        path: String,
+        /// Version to restore
+        #[arg(long = "version-id")]
+        version_id: String,
+    },
+    /// Remove the delete markers hiding deleted files
+    Undelete {
+        /// Path of the file or directory in S3
+        path: String,
     },
     /// Configure S3 credentials and settings
     Config {
@@ -105,12 +484,33 @@ enum Commands {
         /// Custom S3 endpoint URL (for non-AWS S3-compatible services)
         #[arg(short = 'e', long)]
         endpoint: Option<String>,
+        /// Put the bucket in the URL path rather than the host name (e.g., for an IP address endpoint)
+        #[arg(long = "path-style", value_name = "BOOL")]
+        path_style: Option<bool>,
         /// Default chunk size for uploading files (e.g., 5M, 1G, 512K)
         #[arg(long = "upload-chunk-size", value_parser = parse_human_size)]
         upload_chunk_size: Option<usize>,
         /// Default chunk size for downloading files (e.g., 5M, 1G, 512K)
         #[arg(long = "download-chunk-size", value_parser = parse_human_size)]
         download_chunk_size: Option<usize>,
+        /// Default bandwidth limit for transfers (e.g., 10M, 512K, 0 to disable)
+        #[arg(long = "limit-rate", value_parser = parse_human_size)]
+        limit_rate: Option<usize>,
+        /// Map a file extension to a Content-Type for uploads (e.g., md=text/markdown, md= to remove)
+        #[arg(long = "content-type-map", value_name = "EXT=TYPE")]
+        content_type_map: Vec<String>,
+        /// Default server-side encryption for uploads (none to disable)
+        #[arg(long, value_parser = ["AES256", "aws:kms", "none"])]
+        sse: Option<String>,
+        /// Default KMS key ID for SSE-KMS (empty to unset)
+        #[arg(long = "sse-kms-key-id")]
+        sse_kms_key_id: Option<String>,
+        /// Default SSE-C key file (empty to unset)
+        #[arg(long = "sse-c-key", value_name = "FILE")]
+        sse_c_key: Option<String>,
+        /// Default key file for client-side encryption (empty to unset)
+        #[arg(long = "encryption-key-file", value_name = "FILE")]
+        encryption_key_file: Option<String>,
         /// View current configuration
         #[arg(short = 'v', long = "view")]
         view: bool,
@@ -123,10 +523,227 @@ enum Commands {
     },
 }
 
+#[derive(Subcommand)]
+enum TagAction {
+    /// Show the tags of a file
+    Get {
+        /// Path of the file in S3
+        path: String,
+        /// Output format
+        #[arg(short = 'o', long, value_enum, default_value = "text")]
+        output: OutputFormat,
+    },
+    /// Add or update tags of a file
+    Set {
+        /// Path of the file in S3
+        path: String,
+        /// Tags to set
+        #[arg(required = true, value_name = "KEY=VALUE", value_parser = parse_key_value)]
+        tags: Vec<(String, String)>,
+        /// Replace all existing tags instead of merging with them
+        #[arg(long)]
+        replace: bool,
+    },
+    /// Remove some or all tags of a file
+    Rm {
+        /// Path of the file in S3
+        path: String,
+        /// Tag keys to remove (all tags if omitted)
+        keys: Vec<String>,
+    },
+}
+
+#[derive(Args)]
+struct MfaArgs {
+    /// Also enable or disable MFA delete (requires --mfa)
+    #[arg(long = "mfa-delete", value_enum)]
+    mfa_delete: Option<cmds::versioning::MfaDelete>,
+    /// Serial number and current code of the root account's MFA device
+    #[arg(long, value_name = "SERIAL CODE")]
+    mfa: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum VersioningAction {
+    /// Show whether versioning and MFA delete are enabled
+    Status {
+        /// Output format
+        #[arg(short = 'o', long, value_enum, default_value = "text")]
+        output: OutputFormat,
+    },
+    /// Enable versioning
+    Enable {
+        #[command(flatten)]
+        mfa_args: MfaArgs,
+    },
+    /// Suspend versioning, keeping the existing versions
+    Suspend {
+        #[command(flatten)]
+        mfa_args: MfaArgs,
+    },
+}
+
+#[derive(Subcommand)]
+enum LifecycleAction {
+    /// Print the lifecycle rules of the bucket
+    Get {
+        /// Output format
+        #[arg(short = 'f', long, value_enum, default_value = "toml")]
+        format: RulesFormat,
+    },
+    /// Replace the lifecycle rules with the ones from a TOML or JSON file
+    Set {
+        /// Rules file, parsed as JSON if it ends in .json and as TOML otherwise
+        file: PathBuf,
+    },
+    /// Add a rule deleting files some days after they were created
+    AddExpire {
+        /// Prefix the rule applies to (whole bucket if omitted)
+        #[arg(default_value = "")]
+        prefix: String,
+        /// Days after creation before files are deleted
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        days: u32,
+        /// Rule ID (defaults to expire-<prefix>)
+        #[arg(long)]
+        id: Option<String>,
+    },
+    /// Add a rule aborting multipart uploads that were never completed
+    AddAbortMultipart {
+        /// Prefix the rule applies to (whole bucket if omitted)
+        #[arg(default_value = "")]
+        prefix: String,
+        /// Days after initiation before uploads are aborted
+        #[arg(long, value_parser = clap::value_parser!(i32).range(1..))]
+        days: i32,
+        /// Rule ID (defaults to abort-multipart-<prefix>)
+        #[arg(long)]
+        id: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum AclAction {
+    /// Show the owner and grants of a file
+    Get {
+        /// Path of the file in S3
+        path: String,
+        /// Output format
+        #[arg(short = 'o', long, value_enum, default_value = "text")]
+        output: OutputFormat,
+    },
+    /// Replace the ACL of a file with a canned ACL or explicit grants
+    Set {
+        /// Path of the file in S3
+        path: String,
+        /// Canned ACL to apply
+        #[arg(value_enum)]
+        acl: Option<cmds::acl::CannedAcl>,
+        /// Grant READ (repeatable, e.g., uri=http://acs.amazonaws.com/groups/global/AllUsers)
+        #[arg(long = "grant-read", value_name = "GRANTEE", value_parser = cmds::acl::parse_grantee)]
+        grant_read: Vec<String>,
+        /// Grant READ_ACP (repeatable, e.g., id=<canonical user id>)
+        #[arg(long = "grant-read-acp", value_name = "GRANTEE", value_parser = cmds::acl::parse_grantee)]
+        grant_read_acp: Vec<String>,
+        /// Grant WRITE_ACP (repeatable, e.g., email=alice@example.com)
+        #[arg(long = "grant-write-acp", value_name = "GRANTEE", value_parser = cmds::acl::parse_grantee)]
+        grant_write_acp: Vec<String>,
+        /// Grant FULL_CONTROL (repeatable)
+        #[arg(long = "grant-full-control", value_name = "GRANTEE", value_parser = cmds::acl::parse_grantee)]
+        grant_full_control: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum PolicyAction {
+    /// Print the bucket policy
+    Get,
+    /// Replace the bucket policy with a JSON document
+    Set {
+        /// Policy file
+        file: PathBuf,
+    },
+    /// Remove the bucket policy
+    Rm,
+    /// Add a statement letting anyone download the files under a prefix
+    PublicRead {
+        /// Prefix to make public (whole bucket if omitted)
+        #[arg(default_value = "")]
+        prefix: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum CorsAction {
+    /// Print the CORS rules of the bucket
+    Get {
+        /// Output format
+        #[arg(short = 'f', long, value_enum, default_value = "toml")]
+        format: RulesFormat,
+    },
+    /// Replace the CORS rules with the ones from a TOML or JSON file
+    Set {
+        /// Rules file, parsed as JSON if it ends in .json and as TOML otherwise
+        file: PathBuf,
+    },
+    /// Remove all CORS rules
+    Rm,
+}
+
 async fn handle_s3_command(command: &Commands, s3_client: &S3Client) -> Result<()> {
     match command {
-        Commands::Ls { path } => cmds::ls::execute(path.clone(), s3_client).await,
-        Commands::Md { path } => cmds::md::execute(path.clone(), s3_client).await,
+        Commands::Ls {
+            path,
+            tags,
+            versions,
+        } => cmds::ls::execute(path.clone(), tags.clone(), *versions, s3_client).await,
+        Commands::Du {
+            path,
+            depth,
+            sort,
+            summarize,
+            by_storage_class,
+            versions,
+        } => {
+            cmds::du::execute(
+                path.clone(),
+                *depth,
+                *sort,
+                *summarize,
+                *by_storage_class,
+                *versions,
+                s3_client,
+            )
+            .await
+        }
+        Commands::Tree {
+            path,
+            level,
+            sizes,
+            dirs_only,
+        } => cmds::tree::execute(path.clone(), *level, *sizes, *dirs_only, s3_client).await,
+        Commands::Find { prefix, expression } => {
+            cmds::find::execute(prefix.clone(), expression.clone(), s3_client).await
+        }
+        Commands::Browse { prefix, local } => {
+            cmds::browse::execute(prefix.clone(), local.clone(), s3_client).await
+        }
+        Commands::Shell => cmds::shell::execute(s3_client).await,
+        Commands::Gateway { listen, prefix } => {
+            cmds::gateway::execute(*listen, prefix.clone(), s3_client).await
+        }
+        Commands::Webdav { listen, prefix } => {
+            cmds::webdav::execute(*listen, prefix.clone(), s3_client).await
+        }
+        Commands::Complete { word } => cmds::completions::keys(word.clone(), s3_client).await,
+        Commands::Md { path, acl } => {
+            cmds::md::execute(
+                path.clone(),
+                acl.map(|acl| acl.as_str().to_string()),
+                s3_client,
+            )
+            .await
+        }
         Commands::Mv {
             source,
             destination,
@@ -134,8 +751,60 @@ async fn handle_s3_command(command: &Commands, s3_client: &S3Client) -> Result<(
         Commands::Cp {
             source,
             destination,
-        } => cmds::cp::execute(source.clone(), destination.clone(), s3_client).await,
-        Commands::Cat { path } => cmds::cat::execute(path.clone(), s3_client).await,
+            limit_rate,
+            metadata_directive,
+            version_id,
+            put_args,
+            sse_args,
+            sse_c_source_key,
+        } => {
+            cmds::cp::execute(
+                source.clone(),
+                destination.clone(),
+                *limit_rate,
+                *metadata_directive,
+                version_id.clone(),
+                put_args.to_put_options(),
+                SseSettings {
+                    customer_source_key: sse_c_source_key.clone(),
+                    ..sse_args.to_settings()
+                },
+                s3_client,
+            )
+            .await
+        }
+        Commands::Cat {
+            path,
+            version_id,
+            range,
+            head,
+            tail,
+            lines,
+            hex,
+            sse_args,
+            cse_args,
+            decompress,
+        } => {
+            let selection = match (range, head, tail) {
+                (Some(range), _, _) => Selection::Range(*range),
+                (_, Some(count), _) if *lines => Selection::HeadLines(*count as u64),
+                (_, Some(count), _) => Selection::HeadBytes(*count as u64),
+                (_, _, Some(count)) if *lines => Selection::TailLines(*count as u64),
+                (_, _, Some(count)) => Selection::TailBytes(*count as u64),
+                _ => Selection::All,
+            };
+            cmds::cat::execute(
+                path.clone(),
+                version_id.clone(),
+                selection,
+                *hex,
+                sse_args.to_settings(),
+                cse_args.to_settings(),
+                *decompress,
+                s3_client,
+            )
+            .await
+        }
         Commands::Rm { path, recursive } => {
             cmds::rm::execute(path.clone(), *recursive, s3_client).await
         }
@@ -144,12 +813,28 @@ async fn handle_s3_command(command: &Commands, s3_client: &S3Client) -> Result<(
             destination,
             recursive,
             chunk_size,
+            limit_rate,
+            put_args,
+            tags,
+            sse_args,
+            compress,
+            encrypt,
+            cse_args,
         } => {
             cmds::up::execute(
                 path.clone(),
                 destination.clone(),
                 *recursive,
                 *chunk_size,
+                *limit_rate,
+                PutOptions {
+                    tags: tags.iter().cloned().collect(),
+                    ..put_args.to_put_options()
+                },
+                sse_args.to_settings(),
+                *compress,
+                *encrypt,
+                cse_args.to_settings(),
                 s3_client,
             )
             .await
@@ -159,17 +844,140 @@ async fn handle_s3_command(command: &Commands, s3_client: &S3Client) -> Result<(
             destination,
             recursive,
             chunk_size,
+            limit_rate,
+            version_id,
+            sse_args,
+            cse_args,
+            decompress,
         } => {
             cmds::dl::execute(
                 source.clone(),
                 destination.clone(),
                 *recursive,
                 *chunk_size,
+                *limit_rate,
+                version_id.clone(),
+                sse_args.to_settings(),
+                cse_args.to_settings(),
+                *decompress,
                 s3_client,
             )
             .await
         }
-        Commands::Config { .. } => unreachable!(),
+        Commands::Presign {
+            key,
+            expires,
+            method,
+            content_type,
+            disposition,
+            recursive,
+            format,
+        } => {
+            cmds::presign::execute(
+                key.clone(),
+                *expires,
+                *method,
+                content_type.clone(),
+                disposition.clone(),
+                *recursive,
+                *format,
+                s3_client,
+            )
+            .await
+        }
+        Commands::Stat {
+            path,
+            output,
+            version_id,
+            sse_args,
+        } => {
+            cmds::stat::execute(
+                path.clone(),
+                *output,
+                version_id.clone(),
+                sse_args.to_settings(),
+                s3_client,
+            )
+            .await
+        }
+        Commands::Tag { action } => match action {
+            TagAction::Get { path, output } => {
+                cmds::tag::get(path.clone(), *output, s3_client).await
+            }
+            TagAction::Set {
+                path,
+                tags,
+                replace,
+            } => cmds::tag::set(path.clone(), tags.clone(), *replace, s3_client).await,
+            TagAction::Rm { path, keys } => {
+                cmds::tag::rm(path.clone(), keys.clone(), s3_client).await
+            }
+        },
+        Commands::Versioning { action } => match action {
+            VersioningAction::Status { output } => {
+                cmds::versioning::status(*output, s3_client).await
+            }
+            VersioningAction::Enable { mfa_args } => {
+                cmds::versioning::set(true, mfa_args.mfa_delete, mfa_args.mfa.clone(), s3_client)
+                    .await
+            }
+            VersioningAction::Suspend { mfa_args } => {
+                cmds::versioning::set(false, mfa_args.mfa_delete, mfa_args.mfa.clone(), s3_client)
+                    .await
+            }
+        },
+        Commands::Lifecycle { action } => match action {
+            LifecycleAction::Get { format } => cmds::lifecycle::get(*format, s3_client).await,
+            LifecycleAction::Set { file } => cmds::lifecycle::set(file.clone(), s3_client).await,
+            LifecycleAction::AddExpire { prefix, days, id } => {
+                cmds::lifecycle::add_expire(prefix.clone(), *days, id.clone(), s3_client).await
+            }
+            LifecycleAction::AddAbortMultipart { prefix, days, id } => {
+                cmds::lifecycle::add_abort_multipart(prefix.clone(), *days, id.clone(), s3_client)
+                    .await
+            }
+        },
+        Commands::Acl { action } => match action {
+            AclAction::Get { path, output } => {
+                cmds::acl::get(path.clone(), *output, s3_client).await
+            }
+            AclAction::Set {
+                path,
+                acl,
+                grant_read,
+                grant_read_acp,
+                grant_write_acp,
+                grant_full_control,
+            } => {
+                let grants = cmds::acl::Grants {
+                    read: grant_read.clone(),
+                    read_acp: grant_read_acp.clone(),
+                    write_acp: grant_write_acp.clone(),
+                    full_control: grant_full_control.clone(),
+                };
+                cmds::acl::set(path.clone(), *acl, grants, s3_client).await
+            }
+        },
+        Commands::Policy { action } => match action {
+            PolicyAction::Get => cmds::policy::get(s3_client).await,
+            PolicyAction::Set { file } => cmds::policy::set(file.clone(), s3_client).await,
+            PolicyAction::Rm => cmds::policy::rm(s3_client).await,
+            PolicyAction::PublicRead { prefix } => {
+                cmds::policy::public_read(prefix.clone(), s3_client).await
+            }
+        },
+        Commands::Cors { action } => match action {
+            CorsAction::Get { format } => cmds::cors::get(*format, s3_client).await,
+            CorsAction::Set { file } => cmds::cors::set(file.clone(), s3_client).await,
+            CorsAction::Rm => cmds::cors::rm(s3_client).await,
+        },
+        Commands::Restore { path, version_id } => {
+            cmds::restore::execute(path.clone(), version_id.clone(), s3_client).await
+        }
+        Commands::Undelete { path } => cmds::undelete::execute(path.clone(), s3_client).await,
+        Commands::Config { .. } | Commands::Completions { .. } | Commands::Serve { .. } => {
+            unreachable!()
+        }
     }
 }
 
@@ -184,8 +992,15 @@ async fn main() -> Result<()> {
             region,
             bucket,
             endpoint,
+            path_style,
             upload_chunk_size,
             download_chunk_size,
+            limit_rate,
+            content_type_map,
+            sse,
+            sse_kms_key_id,
+            sse_c_key,
+            encryption_key_file,
             view,
             show_all,
             reset,
@@ -196,14 +1011,42 @@ async fn main() -> Result<()> {
                 region.clone(),
                 bucket.clone(),
                 endpoint.clone(),
+                *path_style,
                 *upload_chunk_size,
                 *download_chunk_size,
+                *limit_rate,
+                content_type_map.clone(),
+                sse.clone(),
+                sse_kms_key_id.clone(),
+                sse_c_key.clone(),
+                encryption_key_file.clone(),
                 *view,
                 *show_all,
                 *reset,
             )?;
             return Ok(());
         }
+        Commands::Completions { shell } => {
+            cmds::completions::execute(*shell, &mut Cli::command());
+            return Ok(());
+        }
+        Commands::Serve {
+            root,
+            address,
+            port,
+            access_key,
+            secret_key,
+        } => {
+            cmds::serve::execute(
+                root.clone(),
+                *address,
+                *port,
+                access_key.clone(),
+                secret_key.clone(),
+            )
+            .await?;
+            return Ok(());
+        }
         _ => {
             let config = Config::load()?;
             let s3_client = S3Client::new(&config.s3)?;
@@ -1,16 +1,341 @@
 use crate::config::S3Config;
-use anyhow::Result;
+use crate::sse::{self, Encryption};
+use crate::utils::io::read_full;
+use crate::utils::throttle::{RateLimiter, Throttled};
+use crate::utils::{mime, xml};
+use anyhow::{bail, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use http::header::{AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, HOST};
+use http::{HeaderMap, HeaderName, HeaderValue, Method};
 use s3::creds::Credentials;
+use s3::error::S3Error;
+use s3::serde_types::{BucketLifecycleConfiguration, Object, Part};
+use s3::signing;
 use s3::{Bucket, Region};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
 use std::str::FromStr;
 use std::sync::Arc;
+use time::format_description::FormatItem;
+use time::macros::format_description;
+use time::OffsetDateTime;
 use tokio::fs;
-use tokio::io::AsyncReadExt;
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use url::Url;
 
+/// Smallest part size S3 accepts for every part of a multipart upload but the last
+const MIN_PART_SIZE: usize = 5 * 1024 * 1024;
+
+/// Largest part S3 accepts in a multipart upload
+const MAX_PART_SIZE: usize = 5 * 1024 * 1024 * 1024;
+
+/// Most parts S3 accepts in a multipart upload
+const MAX_PARTS: u32 = 10_000;
+
+/// Parts uploaded before the part size doubles, so streams of unknown size
+/// are not capped at `MAX_PARTS` times the first part size
+const PARTS_PER_SIZE: u32 = 1_000;
+
+/// Prefix of the user metadata s3mgr itself writes and relies on to read
+/// objects back, e.g. the client-side encryption envelope
+const OWN_METADATA_PREFIX: &str = "s3mgr-";
+
+/// Storage class S3 implies when a listing or HEAD response does not name one
+pub const DEFAULT_STORAGE_CLASS: &str = "STANDARD";
+
+const LONG_DATETIME: &[FormatItem<'static>] =
+    format_description!("[year][month][day]T[hour][minute][second]Z");
+
+/// Turns a path into the prefix of its "directory", e.g. `docs` into `docs/`.
+/// An empty path stays empty and means the whole bucket.
+pub fn dir_prefix(path: &str) -> String {
+    if path.is_empty() || path.ends_with('/') {
+        path.to_string()
+    } else {
+        format!("{}/", path)
+    }
+}
+
+/// Whether `err` is the 404 S3 answers with when a bucket has no
+/// configuration of some kind, e.g. `NoSuchBucketPolicy`.
+fn is_missing_configuration(err: &anyhow::Error, code: &str) -> bool {
+    matches!(
+        err.downcast_ref::<S3Error>(),
+        Some(S3Error::HttpFailWithBody(404, body)) if body.contains(code)
+    )
+}
+
+/// Whether `err` is the 404 S3 answers with for a missing object
+fn is_not_found(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<S3Error>(),
+        Some(S3Error::HttpFailWithBody(404, _))
+    )
+}
+
+/// Everything a HEAD request reports about an object
+#[derive(Debug, Serialize)]
+pub struct ObjectMetadata {
+    pub key: String,
+    pub size: u64,
+    pub content_type: Option<String>,
+    pub content_encoding: Option<String>,
+    pub content_disposition: Option<String>,
+    pub content_language: Option<String>,
+    pub cache_control: Option<String>,
+    pub expires: Option<String>,
+    pub e_tag: Option<String>,
+    pub last_modified: Option<String>,
+    pub storage_class: Option<String>,
+    pub server_side_encryption: Option<String>,
+    pub sse_kms_key_id: Option<String>,
+    pub sse_customer_algorithm: Option<String>,
+    pub version_id: Option<String>,
+    pub object_lock_mode: Option<String>,
+    pub object_lock_retain_until_date: Option<String>,
+    pub object_lock_legal_hold_status: Option<String>,
+    pub replication_status: Option<String>,
+    pub restore: Option<String>,
+    pub parts_count: Option<i64>,
+    pub metadata: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct ListVersionsResult {
+    #[serde(rename = "IsTruncated", default)]
+    is_truncated: bool,
+    #[serde(rename = "NextKeyMarker")]
+    next_key_marker: Option<String>,
+    #[serde(rename = "NextVersionIdMarker")]
+    next_version_id_marker: Option<String>,
+    /// Versions and delete markers together, as their order in the listing
+    /// is the only reliable order of the versions of a key
+    #[serde(rename = "$value", default)]
+    entries: Vec<VersionListEntry>,
+}
+
+#[derive(Deserialize)]
+enum VersionListEntry {
+    Version(VersionEntry),
+    DeleteMarker(VersionEntry),
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+struct VersionEntry {
+    #[serde(rename = "Key")]
+    key: String,
+    #[serde(rename = "VersionId")]
+    version_id: String,
+    #[serde(rename = "IsLatest")]
+    is_latest: bool,
+    #[serde(rename = "LastModified")]
+    last_modified: String,
+    #[serde(rename = "Size", default)]
+    size: u64,
+    #[serde(rename = "StorageClass")]
+    storage_class: Option<String>,
+}
+
+/// One version of an object, or a delete marker, from ListObjectVersions
+#[derive(Debug, Clone, Serialize)]
+pub struct ObjectVersion {
+    pub key: String,
+    pub version_id: String,
+    pub is_latest: bool,
+    pub is_delete_marker: bool,
+    pub last_modified: String,
+    pub size: u64,
+    pub storage_class: Option<String>,
+}
+
+impl ObjectVersion {
+    fn from_entry(entry: VersionEntry, is_delete_marker: bool) -> Self {
+        Self {
+            key: entry.key,
+            version_id: entry.version_id,
+            is_latest: entry.is_latest,
+            is_delete_marker,
+            last_modified: entry.last_modified,
+            size: entry.size,
+            storage_class: entry.storage_class,
+        }
+    }
+}
+
+/// Versioning state of a bucket from GetBucketVersioning. Both fields are
+/// absent on a bucket that never had versioning enabled.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct BucketVersioning {
+    #[serde(rename(deserialize = "Status"))]
+    pub status: Option<String>,
+    #[serde(rename(deserialize = "MfaDelete"))]
+    pub mfa_delete: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AccessControlPolicy {
+    #[serde(rename = "Owner")]
+    owner: Option<AclGrantee>,
+    #[serde(rename = "AccessControlList")]
+    access_control_list: AccessControlList,
+}
+
+#[derive(Deserialize)]
+struct AccessControlList {
+    #[serde(rename = "Grant", default)]
+    grants: Vec<AclGrant>,
+}
+
+/// An account, email address or predefined group named in an ACL
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AclGrantee {
+    #[serde(rename(deserialize = "ID"))]
+    pub id: Option<String>,
+    #[serde(rename(deserialize = "DisplayName"))]
+    pub display_name: Option<String>,
+    #[serde(rename(deserialize = "URI"))]
+    pub uri: Option<String>,
+    #[serde(rename(deserialize = "EmailAddress"))]
+    pub email_address: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AclGrant {
+    #[serde(rename(deserialize = "Grantee"))]
+    pub grantee: AclGrantee,
+    #[serde(rename(deserialize = "Permission"))]
+    pub permission: String,
+}
+
+/// Owner and grants of an object from GetObjectAcl
+#[derive(Debug, Serialize)]
+pub struct ObjectAcl {
+    pub owner: Option<AclGrantee>,
+    pub grants: Vec<AclGrant>,
+}
+
+/// CORS rules of a bucket, named after the elements of the S3 XML format
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename = "CORSConfiguration")]
+pub struct CorsConfiguration {
+    #[serde(rename = "CORSRule", default)]
+    pub rules: Vec<CorsRule>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CorsRule {
+    #[serde(rename = "ID", skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(rename = "AllowedOrigin", default)]
+    pub allowed_origins: Vec<String>,
+    #[serde(rename = "AllowedMethod", default)]
+    pub allowed_methods: Vec<String>,
+    #[serde(
+        rename = "AllowedHeader",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub allowed_headers: Vec<String>,
+    #[serde(
+        rename = "ExposeHeader",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub expose_headers: Vec<String>,
+    #[serde(rename = "MaxAgeSeconds", skip_serializing_if = "Option::is_none")]
+    pub max_age_seconds: Option<u32>,
+}
+
+/// Headers and user metadata applied when an object is written
+#[derive(Debug, Clone, Default)]
+pub struct PutOptions {
+    pub content_type: Option<String>,
+    pub cache_control: Option<String>,
+    pub content_disposition: Option<String>,
+    pub content_encoding: Option<String>,
+    pub content_language: Option<String>,
+    pub expires: Option<String>,
+    pub metadata: BTreeMap<String, String>,
+    pub tags: BTreeMap<String, String>,
+    /// Canned ACL sent as x-amz-acl
+    pub acl: Option<String>,
+}
+
+impl PutOptions {
+    /// Headers for the request creating the object, without Content-Type
+    /// which rust-s3 always sets from its own argument.
+    fn headers(&self) -> Result<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        let standard = [
+            ("cache-control", &self.cache_control),
+            ("content-disposition", &self.content_disposition),
+            ("content-encoding", &self.content_encoding),
+            ("content-language", &self.content_language),
+            ("expires", &self.expires),
+            ("x-amz-acl", &self.acl),
+        ];
+        for (name, value) in standard {
+            if let Some(value) = value {
+                headers.insert(HeaderName::from_static(name), HeaderValue::from_str(value)?);
+            }
+        }
+        for (key, value) in &self.metadata {
+            headers.insert(
+                HeaderName::from_str(&format!("x-amz-meta-{}", key.to_lowercase()))?,
+                HeaderValue::from_str(value)?,
+            );
+        }
+        if !self.tags.is_empty() {
+            let tagging = self
+                .tags
+                .iter()
+                .map(|(key, value)| {
+                    format!(
+                        "{}={}",
+                        signing::uri_encode(key, true),
+                        signing::uri_encode(value, true)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("&");
+            headers.insert(
+                HeaderName::from_static("x-amz-tagging"),
+                HeaderValue::from_str(&tagging)?,
+            );
+        }
+        Ok(headers)
+    }
+
+    fn content_type(&self) -> &str {
+        self.content_type
+            .as_deref()
+            .unwrap_or(mime::DEFAULT_CONTENT_TYPE)
+    }
+}
+
+/// Response of a request sent with `S3Client::raw_request`
+pub struct RawResponse {
+    pub body: Vec<u8>,
+}
+
+#[derive(Clone)]
 pub struct S3Client {
     bucket: Arc<Bucket>,
+    /// Bucket used for GET and HEAD requests, which may select an object version
+    read_bucket: Arc<Bucket>,
+    /// Bucket used for multipart parts, which need the SSE-C key too
+    part_bucket: Arc<Bucket>,
+    version_id: Option<String>,
+    encryption: Encryption,
+    /// SSE-C key of the objects copied from, when they are encrypted with one
+    copy_source_key: Option<Vec<u8>>,
+    rate_limiter: Option<Arc<RateLimiter>>,
 }
 
 impl S3Client {
@@ -32,67 +357,671 @@ impl S3Client {
             Region::from_str(&config.region)?
         };
 
-        let bucket = Bucket::new(&config.bucket, region, credentials)?;
+        let mut bucket = Bucket::new(&config.bucket, region, credentials)?;
+        if config.path_style {
+            bucket = bucket.with_path_style();
+        }
+        let bucket = Arc::new(*bucket);
         Ok(Self {
-            bucket: Arc::new(*bucket),
+            read_bucket: Arc::clone(&bucket),
+            part_bucket: Arc::clone(&bucket),
+            bucket,
+            version_id: None,
+            encryption: Encryption::default(),
+            copy_source_key: None,
+            rate_limiter: None,
         })
     }
 
-    pub async fn list(&self, prefix: Option<&str>) -> Result<Vec<(String, u64)>> {
-        let prefix = prefix.unwrap_or("");
-        let prefix = if prefix.is_empty() {
-            "".to_string()
-        } else if prefix.ends_with('/') {
-            prefix.to_string()
-        } else {
-            format!("{}/", prefix)
+    /// Returns a client whose transfers all draw from one token bucket of
+    /// `bytes_per_sec`. A limit of `None` or zero disables throttling.
+    pub fn with_rate_limit(&self, bytes_per_sec: Option<usize>) -> Self {
+        Self {
+            rate_limiter: bytes_per_sec
+                .filter(|&rate| rate > 0)
+                .map(|rate| Arc::new(RateLimiter::new(rate))),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a client whose reads (GET, HEAD and copy sources) target the
+    /// given object version instead of the current one.
+    pub fn with_version_id(&self, version_id: Option<String>) -> Result<Self> {
+        let Some(version_id) = version_id else {
+            return Ok(self.clone());
         };
 
-        let objects = self.bucket.list(prefix, None).await?;
+        let mut query = self.read_bucket.extra_query().clone();
+        query.insert("versionId".to_string(), version_id.clone());
+        Ok(Self {
+            read_bucket: Arc::new(self.read_bucket.with_extra_query(query)?),
+            version_id: Some(version_id),
+            ..self.clone()
+        })
+    }
+
+    /// Returns a client that encrypts the objects it writes and sends the
+    /// SSE-C key, if any, with every request reading or writing object data.
+    pub fn with_encryption(&self, encryption: Encryption) -> Result<Self> {
+        let customer_headers = encryption.customer_headers()?;
+        let mut read_headers = self.read_bucket.extra_headers().clone();
+        read_headers.extend(customer_headers.clone());
+        Ok(Self {
+            read_bucket: Arc::new(self.read_bucket.with_extra_headers(read_headers)?),
+            part_bucket: Arc::new(self.bucket.with_extra_headers(customer_headers)?),
+            encryption,
+            ..self.clone()
+        })
+    }
+
+    /// Returns a client that reads the sources of copies with the SSE-C key
+    /// `key`, or as objects without SSE-C when there is none, while still
+    /// writing with its own encryption. Call it after `with_encryption`.
+    pub fn with_copy_source_key(&self, key: Option<Vec<u8>>) -> Result<Self> {
+        let mut read_headers = self.read_bucket.extra_headers().clone();
+        for name in self.encryption.customer_headers()?.keys() {
+            read_headers.remove(name);
+        }
+        let source = Encryption {
+            customer_key: key.clone(),
+            ..Default::default()
+        };
+        read_headers.extend(source.customer_headers()?);
+        Ok(Self {
+            read_bucket: Arc::new(self.read_bucket.with_extra_headers(read_headers)?),
+            copy_source_key: key,
+            ..self.clone()
+        })
+    }
+
+    pub fn bucket_name(&self) -> String {
+        self.bucket.name()
+    }
+
+    /// Returns the bucket with `headers` and the encryption headers added to
+    /// every request made through it, for requests creating objects.
+    fn bucket_with_headers(&self, mut headers: HeaderMap) -> Result<Bucket> {
+        headers.extend(self.encryption.write_headers()?);
+        Ok(self.bucket.with_extra_headers(headers)?)
+    }
+
+    /// Signs and sends a request for operations rust-s3 has no API for.
+    /// `path` is an object key, or empty for bucket subresources like
+    /// `?versioning`. Non-2xx responses are returned as errors.
+    pub async fn raw_request(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, &str)],
+        mut headers: HeaderMap,
+        body: Vec<u8>,
+    ) -> Result<RawResponse> {
+        let mut url = format!(
+            "{}/{}",
+            self.bucket.url(),
+            signing::uri_encode(path.trim_start_matches('/'), false)
+        );
+        if !query.is_empty() {
+            let query = query
+                .iter()
+                .map(|(key, value)| {
+                    if value.is_empty() {
+                        signing::uri_encode(key, true)
+                    } else {
+                        format!(
+                            "{}={}",
+                            signing::uri_encode(key, true),
+                            signing::uri_encode(value, true)
+                        )
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("&");
+            url.push('?');
+            url.push_str(&query);
+        }
+        let url = Url::parse(&url)?;
+
+        let datetime = OffsetDateTime::now_utc();
+        let payload_sha256 = hex::encode(Sha256::digest(&body));
+        headers.insert(HOST, self.bucket.host().parse()?);
+        headers.insert(
+            HeaderName::from_static("x-amz-date"),
+            datetime.format(LONG_DATETIME)?.parse()?,
+        );
+        headers.insert(
+            HeaderName::from_static("x-amz-content-sha256"),
+            payload_sha256.parse()?,
+        );
+        headers.insert(CONTENT_LENGTH, body.len().into());
+        if !body.is_empty() {
+            let digest = md5::compute(&body);
+            headers.insert(
+                HeaderName::from_static("content-md5"),
+                BASE64.encode(digest.as_ref()).parse()?,
+            );
+        }
+
+        let region = self.bucket.region();
+        let canonical_request =
+            signing::canonical_request(method.as_str(), &url, &headers, &payload_sha256)?;
+        let string_to_sign = signing::string_to_sign(&datetime, &region, &canonical_request)?;
+        let secret_key = self.bucket.secret_key().await?.unwrap_or_default();
+        let access_key = self.bucket.access_key().await?.unwrap_or_default();
+        let signing_key = signing::signing_key(&datetime, &secret_key, &region, "s3")?;
+        let mut hmac =
+            Hmac::<Sha256>::new_from_slice(&signing_key).expect("HMAC accepts keys of any length");
+        hmac.update(string_to_sign.as_bytes());
+        let signature = hex::encode(hmac.finalize().into_bytes());
+        let authorization = signing::authorization_header(
+            &access_key,
+            &datetime,
+            &region,
+            &signing::signed_header_string(&headers),
+            &signature,
+        )?;
+        headers.insert(AUTHORIZATION, authorization.parse()?);
+
+        let mut request = hyper::Request::builder().method(method).uri(url.as_str());
+        if let Some(request_headers) = request.headers_mut() {
+            *request_headers = headers;
+        }
+        let request = request.body(hyper::Body::from(body))?;
+
+        let response = self.bucket.http_client().request(request).await?;
+        let status = response.status().as_u16();
+        let body = hyper::body::to_bytes(response.into_body()).await?.to_vec();
+
+        if !(200..300).contains(&status) {
+            let text = String::from_utf8_lossy(&body).into_owned();
+            return Err(S3Error::HttpFailWithBody(status, text).into());
+        }
+
+        Ok(RawResponse { body })
+    }
+
+    async fn throttle(&self, bytes: usize) {
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire(bytes).await;
+        }
+    }
+
+    pub async fn list(&self, prefix: Option<&str>) -> Result<Vec<(String, u64)>> {
+        let objects = self.list_objects(prefix).await?;
         Ok(objects
             .into_iter()
-            .flat_map(|obj| obj.contents)
-            .map(|content| {
-                let key = content.key;
-                let size = content.size as u64;
-                (key, size)
-            })
+            .map(|object| (object.key, object.size))
             .collect())
     }
 
+    pub async fn list_objects(&self, prefix: Option<&str>) -> Result<Vec<Object>> {
+        let prefix = dir_prefix(prefix.unwrap_or(""));
+        let objects = self.bucket.list(prefix, None).await?;
+        Ok(objects.into_iter().flat_map(|obj| obj.contents).collect())
+    }
+
+    /// Lists the direct children of `prefix` with a delimiter listing: the
+    /// sub-prefixes, each ending with `/`, and the objects. The prefix's own
+    /// directory marker is left out.
+    pub async fn list_dir(&self, prefix: &str) -> Result<(Vec<String>, Vec<Object>)> {
+        let prefix = dir_prefix(prefix);
+        let results = self
+            .bucket
+            .list(prefix.clone(), Some("/".to_string()))
+            .await?;
+
+        let mut prefixes = Vec::new();
+        let mut objects = Vec::new();
+        for result in results {
+            prefixes.extend(
+                result
+                    .common_prefixes
+                    .into_iter()
+                    .flatten()
+                    .map(|common| common.prefix),
+            );
+            objects.extend(
+                result
+                    .contents
+                    .into_iter()
+                    .filter(|object| object.key != prefix),
+            );
+        }
+        Ok((prefixes, objects))
+    }
+
+    /// Lists the first page of the sub-prefixes and keys starting with
+    /// `prefix` up to the next `/`, and whether there are more. Unlike
+    /// `list_dir`, `prefix` can end in the middle of a name.
+    pub async fn list_names(&self, prefix: &str) -> Result<(Vec<String>, bool)> {
+        let (page, _) = self
+            .bucket
+            .list_page(prefix.to_string(), Some("/".to_string()), None, None, None)
+            .await?;
+        let names = page
+            .common_prefixes
+            .into_iter()
+            .flatten()
+            .map(|common| common.prefix)
+            .chain(page.contents.into_iter().map(|object| object.key))
+            .collect();
+        Ok((names, page.is_truncated))
+    }
+
+    /// Lists a single page of the objects whose keys start with `prefix`,
+    /// with the token to pass for the next page, if there is one
+    pub async fn list_page(
+        &self,
+        prefix: &str,
+        continuation_token: Option<String>,
+    ) -> Result<(Vec<Object>, Option<String>)> {
+        let (page, _) = self
+            .bucket
+            .list_page(prefix.to_string(), None, continuation_token, None, None)
+            .await?;
+        let next_token = page.next_continuation_token.filter(|_| page.is_truncated);
+        Ok((page.contents, next_token))
+    }
+
+    /// Lists every version and delete marker of the keys starting with
+    /// `prefix` in the order S3 lists them, by key and newest first for each.
+    pub async fn list_versions(&self, prefix: &str) -> Result<Vec<ObjectVersion>> {
+        let mut versions = Vec::new();
+        let mut key_marker: Option<String> = None;
+        let mut version_id_marker: Option<String> = None;
+
+        loop {
+            let mut query = vec![("versions", ""), ("prefix", prefix)];
+            if let Some(marker) = &key_marker {
+                query.push(("key-marker", marker));
+            }
+            if let Some(marker) = &version_id_marker {
+                query.push(("version-id-marker", marker));
+            }
+
+            let response = self
+                .raw_request(Method::GET, "", &query, HeaderMap::new(), Vec::new())
+                .await?;
+            let page: ListVersionsResult = quick_xml::de::from_reader(response.body.as_slice())?;
+
+            versions.extend(page.entries.into_iter().filter_map(|entry| match entry {
+                VersionListEntry::Version(entry) => Some(ObjectVersion::from_entry(entry, false)),
+                VersionListEntry::DeleteMarker(entry) => {
+                    Some(ObjectVersion::from_entry(entry, true))
+                }
+                VersionListEntry::Other => None,
+            }));
+
+            if !page.is_truncated {
+                break;
+            }
+            key_marker = page.next_key_marker;
+            version_id_marker = page.next_version_id_marker;
+        }
+
+        Ok(versions)
+    }
+
+    /// Permanently deletes one version of an object, or a delete marker.
+    pub async fn delete_version(&self, path: &str, version_id: &str) -> Result<()> {
+        self.raw_request(
+            Method::DELETE,
+            path,
+            &[("versionId", version_id)],
+            HeaderMap::new(),
+            Vec::new(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_versioning(&self) -> Result<BucketVersioning> {
+        let response = self
+            .raw_request(
+                Method::GET,
+                "",
+                &[("versioning", "")],
+                HeaderMap::new(),
+                Vec::new(),
+            )
+            .await?;
+        Ok(quick_xml::de::from_reader(response.body.as_slice())?)
+    }
+
+    /// Enables or suspends versioning. Changing MFA delete requires `mfa`,
+    /// the device serial number and current code separated by a space.
+    pub async fn put_versioning(
+        &self,
+        enabled: bool,
+        mfa_delete: Option<bool>,
+        mfa: Option<&str>,
+    ) -> Result<()> {
+        let status = if enabled { "Enabled" } else { "Suspended" };
+        let mfa_delete = match mfa_delete {
+            Some(true) => "<MfaDelete>Enabled</MfaDelete>",
+            Some(false) => "<MfaDelete>Disabled</MfaDelete>",
+            None => "",
+        };
+        let body = format!(
+            "<VersioningConfiguration xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">\
+             <Status>{}</Status>{}</VersioningConfiguration>",
+            status, mfa_delete
+        );
+
+        let mut headers = HeaderMap::new();
+        if let Some(mfa) = mfa {
+            headers.insert(HeaderName::from_static("x-amz-mfa"), mfa.parse()?);
+        }
+        self.raw_request(
+            Method::PUT,
+            "",
+            &[("versioning", "")],
+            headers,
+            body.into_bytes(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Returns the bucket's lifecycle rules, or `None` if it has none.
+    pub async fn get_lifecycle(&self) -> Result<Option<BucketLifecycleConfiguration>> {
+        match self.bucket.get_bucket_lifecycle().await {
+            Ok(configuration) => Ok(Some(configuration)),
+            Err(err) => {
+                let err = err.into();
+                if is_missing_configuration(&err, "NoSuchLifecycleConfiguration") {
+                    Ok(None)
+                } else {
+                    Err(err)
+                }
+            }
+        }
+    }
+
+    /// Replaces all lifecycle rules of the bucket.
+    pub async fn put_lifecycle(&self, configuration: BucketLifecycleConfiguration) -> Result<()> {
+        self.bucket.put_bucket_lifecycle(configuration).await?;
+        Ok(())
+    }
+
+    pub async fn get_acl(&self, path: &str) -> Result<ObjectAcl> {
+        let response = self
+            .raw_request(
+                Method::GET,
+                path,
+                &[("acl", "")],
+                HeaderMap::new(),
+                Vec::new(),
+            )
+            .await?;
+        let policy: AccessControlPolicy = quick_xml::de::from_reader(response.body.as_slice())?;
+        Ok(ObjectAcl {
+            owner: policy.owner,
+            grants: policy.access_control_list.grants,
+        })
+    }
+
+    /// Replaces the ACL of an object with a canned ACL or with explicit
+    /// grants, given as `(permission, grantees)` like `("read", "uri=\"...\"")`.
+    pub async fn put_acl(
+        &self,
+        path: &str,
+        canned_acl: Option<&str>,
+        grants: &[(&str, String)],
+    ) -> Result<()> {
+        let mut headers = HeaderMap::new();
+        if let Some(acl) = canned_acl {
+            headers.insert(HeaderName::from_static("x-amz-acl"), acl.parse()?);
+        }
+        for (permission, grantees) in grants {
+            headers.insert(
+                HeaderName::from_str(&format!("x-amz-grant-{}", permission))?,
+                grantees.parse()?,
+            );
+        }
+        self.raw_request(Method::PUT, path, &[("acl", "")], headers, Vec::new())
+            .await?;
+        Ok(())
+    }
+
+    /// Returns the bucket policy document, or `None` if the bucket has none.
+    pub async fn get_policy(&self) -> Result<Option<String>> {
+        let response = self
+            .raw_request(
+                Method::GET,
+                "",
+                &[("policy", "")],
+                HeaderMap::new(),
+                Vec::new(),
+            )
+            .await;
+        match response {
+            Ok(response) => Ok(Some(String::from_utf8(response.body)?)),
+            Err(err) if is_missing_configuration(&err, "NoSuchBucketPolicy") => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub async fn put_policy(&self, policy: &str) -> Result<()> {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        self.raw_request(
+            Method::PUT,
+            "",
+            &[("policy", "")],
+            headers,
+            policy.as_bytes().to_vec(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn delete_policy(&self) -> Result<()> {
+        self.raw_request(
+            Method::DELETE,
+            "",
+            &[("policy", "")],
+            HeaderMap::new(),
+            Vec::new(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Returns the bucket's CORS rules, or `None` if it has none.
+    pub async fn get_cors(&self) -> Result<Option<CorsConfiguration>> {
+        let response = self
+            .raw_request(
+                Method::GET,
+                "",
+                &[("cors", "")],
+                HeaderMap::new(),
+                Vec::new(),
+            )
+            .await;
+        match response {
+            Ok(response) => Ok(Some(quick_xml::de::from_reader(response.body.as_slice())?)),
+            Err(err) if is_missing_configuration(&err, "NoSuchCORSConfiguration") => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Replaces all CORS rules of the bucket.
+    pub async fn put_cors(&self, configuration: &CorsConfiguration) -> Result<()> {
+        let body = quick_xml::se::to_string(configuration)?;
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/xml"));
+        self.raw_request(Method::PUT, "", &[("cors", "")], headers, body.into_bytes())
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete_cors(&self) -> Result<()> {
+        self.raw_request(
+            Method::DELETE,
+            "",
+            &[("cors", "")],
+            HeaderMap::new(),
+            Vec::new(),
+        )
+        .await?;
+        Ok(())
+    }
+
     pub async fn upload_with_progress<F>(
         &self,
         local_path: &Path,
         s3_path: &str,
         chunk_size: usize,
+        options: &PutOptions,
         progress_callback: F,
     ) -> Result<()>
     where
         F: Fn(u64) + Send + Sync,
     {
-        let file_size = fs::metadata(local_path).await?.len();
         let mut file = fs::File::open(local_path).await?;
+        self.upload_reader(&mut file, s3_path, chunk_size, options, progress_callback)
+            .await
+    }
+
+    /// Uploads everything read from `reader`, in a single request when it
+    /// fits in `chunk_size` or as a multipart upload otherwise. The size
+    /// does not need to be known up front, so the data can be transformed
+    /// while streaming.
+    pub async fn upload_reader<R, F>(
+        &self,
+        reader: &mut R,
+        s3_path: &str,
+        chunk_size: usize,
+        options: &PutOptions,
+        progress_callback: F,
+    ) -> Result<()>
+    where
+        R: AsyncRead + Unpin,
+        F: Fn(u64) + Send + Sync,
+    {
+        let content_type = options.content_type();
+        // Headers go on the request that creates the object, which for
+        // multipart uploads is the initiation rather than the parts
+        let create_bucket = self.bucket_with_headers(options.headers()?)?;
+        // The rate is paid as the data is read rather than a part at a time
+        let mut reader = Throttled::new(reader, self.rate_limiter.clone());
+
+        // One byte more than a chunk tells whether a single request is enough
+        let mut head = vec![0; chunk_size + 1];
+        let n = read_full(&mut reader, &mut head).await?;
+        head.truncate(n);
+
+        if n <= chunk_size {
+            let data = head;
+            create_bucket
+                .put_object_with_content_type(s3_path, &data, content_type)
+                .await?;
+            progress_callback(data.len() as u64);
+            return Ok(());
+        }
+
+        let upload_id = create_bucket
+            .initiate_multipart_upload(s3_path, content_type)
+            .await?
+            .upload_id;
+
+        let part_size = chunk_size.max(MIN_PART_SIZE);
+        let result = self
+            .upload_parts(
+                head,
+                &mut reader,
+                s3_path,
+                &upload_id,
+                part_size,
+                content_type,
+                &progress_callback,
+            )
+            .await;
+
+        match result {
+            Ok(parts) => {
+                self.bucket
+                    .complete_multipart_upload(s3_path, &upload_id, parts)
+                    .await?;
+                Ok(())
+            }
+            Err(e) => {
+                // Best effort, the original error is more useful than an abort failure
+                let _ = self.bucket.abort_upload(s3_path, &upload_id).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Uploads `head` and then the rest of `reader` as parts. Only one part
+    /// is held in memory at a time, starting with the allocation of `head`.
+    #[allow(clippy::too_many_arguments)]
+    async fn upload_parts<R, F>(
+        &self,
+        head: Vec<u8>,
+        reader: &mut R,
+        s3_path: &str,
+        upload_id: &str,
+        mut part_size: usize,
+        content_type: &str,
+        progress_callback: &F,
+    ) -> Result<Vec<Part>>
+    where
+        R: AsyncRead + Unpin,
+        F: Fn(u64) + Send + Sync,
+    {
+        let mut parts = Vec::new();
         let mut uploaded: u64 = 0;
-        let mut buffer = vec![0; chunk_size];
+        let mut part = head;
 
-        while uploaded < file_size {
-            let n = file.read(&mut buffer).await?;
-            if n == 0 {
+        loop {
+            let part_number = parts.len() as u32 + 1;
+            if part_number > PARTS_PER_SIZE && part_number % PARTS_PER_SIZE == 1 {
+                part_size = (part_size * 2).min(MAX_PART_SIZE);
+            }
+
+            if part.len() < part_size {
+                let filled = part.len();
+                part.resize(part_size, 0);
+                let n = read_full(reader, &mut part[filled..]).await?;
+                part.truncate(filled + n);
+            }
+            if part.is_empty() {
                 break;
             }
+            // Bytes read past this part, like the spare byte of the head
+            let next = if part.len() > part_size {
+                part.split_off(part_size)
+            } else {
+                Vec::new()
+            };
+            let n = part.len();
+
+            if part_number > MAX_PARTS {
+                bail!(
+                    "{} is larger than {} parts, raise --chunk-size",
+                    s3_path,
+                    MAX_PARTS
+                );
+            }
 
-            let chunk = buffer[..n].to_vec();
-            self.bucket.put_object(s3_path, &chunk).await?;
+            let uploaded_part = self
+                .part_bucket
+                .put_multipart_chunk(part, s3_path, part_number, upload_id, content_type)
+                .await?;
+            parts.push(uploaded_part);
 
             uploaded += n as u64;
             progress_callback(uploaded);
 
-            if n < chunk_size {
+            if n < part_size {
                 break;
             }
+            part = next;
         }
 
-        Ok(())
+        Ok(parts)
     }
 
     pub async fn delete(&self, path: &str) -> Result<()> {
@@ -101,33 +1030,173 @@ impl S3Client {
     }
 
     pub async fn move_object(&self, source: &str, destination: &str) -> Result<()> {
-        let content = self.bucket.get_object(source).await?;
+        self.copy_object(source, destination, &PutOptions::default())
+            .await?;
+        self.bucket.delete_object(source).await?;
+
+        Ok(())
+    }
+
+    /// Copies an object by downloading and re-uploading it. The source's
+    /// Content-Type is kept unless `options` overrides it.
+    pub async fn copy_object(
+        &self,
+        source: &str,
+        destination: &str,
+        options: &PutOptions,
+    ) -> Result<()> {
+        let content = self.read_bucket.get_object(source).await?;
+        let source_headers = content.headers();
         let data = content.to_vec();
 
-        self.bucket.put_object(destination, &data).await?;
+        let mut options = options.clone();
+        if options.content_type.is_none() {
+            options.content_type = source_headers.get("content-type").cloned();
+        }
+        // Without its own metadata, e.g. the encryption envelope, the copy
+        // could not be read back
+        for (name, value) in source_headers {
+            if let Some(key) = name.strip_prefix("x-amz-meta-") {
+                if key.starts_with(OWN_METADATA_PREFIX) {
+                    options.metadata.entry(key.to_string()).or_insert(value);
+                }
+            }
+        }
 
-        self.bucket.delete_object(source).await?;
+        self.throttle(data.len()).await;
+        self.bucket_with_headers(options.headers()?)?
+            .put_object_with_content_type(destination, &data, options.content_type())
+            .await?;
 
         Ok(())
     }
 
-    pub async fn copy_object(&self, source: &str, destination: &str) -> Result<()> {
-        let content = self.bucket.get_object(source).await?;
-        let data = content.to_vec();
+    /// Copies an object with a server-side CopyObject request. With
+    /// `replace_metadata` the headers in `options` replace the source's
+    /// metadata, otherwise the source's metadata is kept.
+    pub async fn copy_object_server_side(
+        &self,
+        source: &str,
+        destination: &str,
+        replace_metadata: bool,
+        options: &PutOptions,
+    ) -> Result<()> {
+        let mut copy_source = format!(
+            "/{}/{}",
+            self.bucket.name(),
+            signing::uri_encode(source.trim_start_matches('/'), false)
+        );
+        if let Some(version_id) = &self.version_id {
+            copy_source.push_str(&format!(
+                "?versionId={}",
+                signing::uri_encode(version_id, true)
+            ));
+        }
+
+        let mut headers = self.encryption.write_headers()?;
+        if let Some(key) = &self.copy_source_key {
+            headers.extend(sse::copy_source_headers(key)?);
+        }
+        headers.insert(
+            HeaderName::from_static("x-amz-copy-source"),
+            copy_source.parse()?,
+        );
+
+        if replace_metadata {
+            let source_metadata = self.head_object(source).await?;
+            // The encryption envelope and similar must survive a REPLACE
+            let mut options = options.clone();
+            for (key, value) in source_metadata.metadata {
+                if key.starts_with(OWN_METADATA_PREFIX) {
+                    options.metadata.entry(key).or_insert(value);
+                }
+            }
+            headers.extend(options.headers()?);
+            headers.insert(
+                HeaderName::from_static("x-amz-metadata-directive"),
+                HeaderValue::from_static("REPLACE"),
+            );
+            // REPLACE resets Content-Type too, so keep the source's by default
+            let content_type = match &options.content_type {
+                Some(content_type) => content_type.clone(),
+                None => source_metadata
+                    .content_type
+                    .unwrap_or_else(|| mime::DEFAULT_CONTENT_TYPE.to_string()),
+            };
+            headers.insert(CONTENT_TYPE, HeaderValue::from_str(&content_type)?);
+        } else {
+            headers.insert(
+                HeaderName::from_static("x-amz-metadata-directive"),
+                HeaderValue::from_static("COPY"),
+            );
+            // The ACL is never copied from the source, whatever the directive
+            if let Some(acl) = &options.acl {
+                headers.insert(HeaderName::from_static("x-amz-acl"), acl.parse()?);
+            }
+        }
 
-        self.bucket.put_object(destination, &data).await?;
+        let response = self
+            .raw_request(Method::PUT, destination, &[], headers, Vec::new())
+            .await?;
 
+        // CopyObject can fail after the 200 status line has been sent
+        let body = String::from_utf8_lossy(&response.body);
+        if body.contains("<Error>") {
+            return Err(S3Error::HttpFailWithBody(200, body.into_owned()).into());
+        }
         Ok(())
     }
 
-    pub async fn put_empty_object(&self, key: &str) -> Result<()> {
-        self.bucket.put_object(key, &[]).await?;
+    pub async fn put_empty_object(&self, key: &str, options: &PutOptions) -> Result<()> {
+        self.bucket_with_headers(options.headers()?)?
+            .put_object_with_content_type(key, &[], options.content_type())
+            .await?;
         Ok(())
     }
 
+    pub async fn head_object(&self, s3_path: &str) -> Result<ObjectMetadata> {
+        let (head, _) = self.read_bucket.head_object(s3_path).await?;
+        Ok(ObjectMetadata {
+            key: s3_path.to_string(),
+            size: head.content_length.unwrap_or(0).max(0) as u64,
+            content_type: head.content_type,
+            content_encoding: head.content_encoding,
+            content_disposition: head.content_disposition,
+            content_language: head.content_language,
+            cache_control: head.cache_control,
+            expires: head.expires,
+            e_tag: head.e_tag,
+            last_modified: head.last_modified,
+            // S3 omits the header for the default class
+            storage_class: Some(
+                head.storage_class
+                    .unwrap_or_else(|| DEFAULT_STORAGE_CLASS.to_string()),
+            ),
+            server_side_encryption: head.server_side_encryption,
+            sse_kms_key_id: head.ssekms_key_id,
+            sse_customer_algorithm: head.sse_customer_algorithm,
+            version_id: head.version_id,
+            object_lock_mode: head.object_lock_mode,
+            object_lock_retain_until_date: head.object_lock_retain_until_date,
+            object_lock_legal_hold_status: head.object_lock_legal_hold_status,
+            replication_status: head.replication_status,
+            restore: head.restore,
+            parts_count: head.parts_count,
+            metadata: head.metadata.unwrap_or_default().into_iter().collect(),
+        })
+    }
+
+    /// Like `head_object`, but `None` when the object does not exist
+    pub async fn find_object(&self, s3_path: &str) -> Result<Option<ObjectMetadata>> {
+        match self.head_object(s3_path).await {
+            Ok(metadata) => Ok(Some(metadata)),
+            Err(e) if is_not_found(&e) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
     pub async fn get_object_size(&self, s3_path: &str) -> Result<u64> {
-        let (head, _) = self.bucket.head_object(s3_path).await?;
-        Ok(head.content_length.unwrap_or(0).max(0) as u64)
+        Ok(self.head_object(s3_path).await?.size)
     }
 
     pub async fn download_with_progress<F>(
@@ -140,28 +1209,82 @@ impl S3Client {
     where
         F: Fn(u64) + Send + Sync,
     {
-        let content = self.bucket.get_object(s3_path).await?;
-        let data = content.to_vec();
-
         let mut file = fs::File::create(local_path).await?;
-        let mut downloaded: u64 = 0;
-
-        let mut offset = 0;
-        while offset < data.len() {
-            let end = std::cmp::min(offset + chunk_size, data.len());
-            let chunk = &data[offset..end];
+        self.download_to_writer(s3_path, &mut file, chunk_size, progress_callback)
+            .await?;
+        file.flush().await?;
+        Ok(())
+    }
 
-            file.write_all(chunk).await?;
+    /// Downloads an object into `writer` with ranged GETs of `chunk_size`
+    /// bytes. The writer is not shut down.
+    pub async fn download_to_writer<W, F>(
+        &self,
+        s3_path: &str,
+        writer: &mut W,
+        chunk_size: usize,
+        progress_callback: F,
+    ) -> Result<()>
+    where
+        W: AsyncWrite + Unpin,
+        F: Fn(u64) + Send + Sync,
+    {
+        let file_size = self.get_object_size(s3_path).await?;
+        self.download_range_to_writer(s3_path, writer, 0, file_size, chunk_size, progress_callback)
+            .await
+    }
 
-            downloaded += (end - offset) as u64;
-            progress_callback(downloaded);
+    /// Downloads bytes `start..end` of an object into `writer` with ranged
+    /// GETs of `chunk_size` bytes, reporting the bytes written so far.
+    pub async fn download_range_to_writer<W, F>(
+        &self,
+        s3_path: &str,
+        writer: &mut W,
+        start: u64,
+        end: u64,
+        chunk_size: usize,
+        progress_callback: F,
+    ) -> Result<()>
+    where
+        W: AsyncWrite + Unpin,
+        F: Fn(u64) + Send + Sync,
+    {
+        let mut position = start;
+        while position < end {
+            let last = std::cmp::min(position + chunk_size as u64, end) - 1;
+            let chunk = self.get_range(s3_path, position, last).await?;
+            if chunk.is_empty() {
+                break;
+            }
+            writer.write_all(&chunk).await?;
 
-            offset = end;
+            position += chunk.len() as u64;
+            progress_callback(position - start);
         }
 
         Ok(())
     }
 
+    /// Reads bytes `start..=last` of an object, at the configured rate
+    pub async fn get_range(&self, s3_path: &str, start: u64, last: u64) -> Result<Vec<u8>> {
+        if start < last {
+            let mut data = Throttled::new(Vec::new(), self.rate_limiter.clone());
+            self.read_bucket
+                .get_object_range_to_writer(s3_path, start, Some(last), &mut data)
+                .await?;
+            return Ok(data.into_inner());
+        }
+
+        // rust-s3 asserts `start < end`, so a single byte is read along
+        // with its neighbour
+        let (range_start, skip) = if start > 0 { (start - 1, 1) } else { (0, 0) };
+        let data = self
+            .read_bucket
+            .get_object_range(s3_path, range_start, Some(range_start + 1))
+            .await?;
+        Ok(data.as_slice().iter().skip(skip).take(1).copied().collect())
+    }
+
     pub async fn is_directory(&self, path: &str) -> Result<bool> {
         let path = if path.ends_with('/') {
             path.to_string()
@@ -184,23 +1307,103 @@ impl S3Client {
         Ok(objects.into_iter().map(|(key, _)| key).collect())
     }
 
-    pub async fn cat(&self, path: &str) -> Result<String> {
-        let content = self.bucket.get_object(path).await?;
-        let data = content.to_vec();
-        Ok(String::from_utf8(data)?)
+    pub async fn get_tags(&self, path: &str) -> Result<Vec<(String, String)>> {
+        let (tags, _) = self.bucket.get_object_tagging(path).await?;
+        Ok(tags
+            .into_iter()
+            .map(|tag| (tag.key(), tag.value()))
+            .collect())
     }
 
-    pub async fn is_exists(&self, path: &str) -> Result<bool> {
-        match self.bucket.get_object(path).await {
-            Ok(_) => Ok(true),
-            Err(e) => {
-                // if 404 in error, return false
-                if e.to_string().contains("404") {
-                    Ok(false)
-                } else {
-                    Err(e.into())
-                }
-            }
-        }
+    /// Replaces the whole tag set of an object.
+    pub async fn put_tags(&self, path: &str, tags: &[(String, String)]) -> Result<()> {
+        // rust-s3 pastes keys and values into the XML body as they are
+        let escaped: Vec<(String, String)> = tags
+            .iter()
+            .map(|(key, value)| (xml::escape(key), xml::escape(value)))
+            .collect();
+        self.bucket.put_object_tagging(path, &escaped).await?;
+        Ok(())
+    }
+
+    pub async fn delete_tags(&self, path: &str) -> Result<()> {
+        self.bucket.delete_object_tagging(path).await?;
+        Ok(())
+    }
+
+    pub async fn presign_get(
+        &self,
+        path: &str,
+        expiry_secs: u32,
+        response_overrides: HashMap<String, String>,
+    ) -> Result<String> {
+        let custom_queries = (!response_overrides.is_empty()).then_some(response_overrides);
+        Ok(self
+            .bucket
+            .presign_get(path, expiry_secs, custom_queries)
+            .await?)
+    }
+
+    pub async fn presign_put(&self, path: &str, expiry_secs: u32) -> Result<String> {
+        Ok(self
+            .bucket
+            .presign_put(path, expiry_secs, None, None)
+            .await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_keep_the_listing_order() {
+        let body = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListVersionsResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+  <Name>bucket</Name>
+  <Prefix>a</Prefix>
+  <KeyMarker></KeyMarker>
+  <VersionIdMarker></VersionIdMarker>
+  <MaxKeys>1000</MaxKeys>
+  <IsTruncated>true</IsTruncated>
+  <NextKeyMarker>b</NextKeyMarker>
+  <NextVersionIdMarker>v5</NextVersionIdMarker>
+  <DeleteMarker>
+    <Key>a</Key><VersionId>v3</VersionId><IsLatest>true</IsLatest>
+    <LastModified>2024-01-01T00:00:02.000Z</LastModified>
+  </DeleteMarker>
+  <Version>
+    <Key>a</Key><VersionId>v2</VersionId><IsLatest>false</IsLatest>
+    <LastModified>2024-01-01T00:00:02.000Z</LastModified>
+    <ETag>"x"</ETag><Size>2</Size><StorageClass>STANDARD</StorageClass>
+  </Version>
+  <Version>
+    <Key>a</Key><VersionId>v1</VersionId><IsLatest>false</IsLatest>
+    <LastModified>2024-01-01T00:00:02.000Z</LastModified>
+    <ETag>"y"</ETag><Size>1</Size><StorageClass>STANDARD</StorageClass>
+  </Version>
+  <DeleteMarker>
+    <Key>b</Key><VersionId>v5</VersionId><IsLatest>true</IsLatest>
+    <LastModified>2024-01-01T00:00:01.000Z</LastModified>
+  </DeleteMarker>
+</ListVersionsResult>"#;
+        let page: ListVersionsResult = quick_xml::de::from_str(body).unwrap();
+        assert!(page.is_truncated);
+        assert_eq!(page.next_key_marker.as_deref(), Some("b"));
+        assert_eq!(page.next_version_id_marker.as_deref(), Some("v5"));
+
+        let entries: Vec<(&str, bool)> = page
+            .entries
+            .iter()
+            .filter_map(|entry| match entry {
+                VersionListEntry::Version(entry) => Some((entry.version_id.as_str(), false)),
+                VersionListEntry::DeleteMarker(entry) => Some((entry.version_id.as_str(), true)),
+                VersionListEntry::Other => None,
+            })
+            .collect();
+        assert_eq!(
+            entries,
+            [("v3", true), ("v2", false), ("v1", false), ("v5", true)]
+        );
     }
 }
@@ -0,0 +1,1049 @@
+//! A small S3-compatible server backed by a local directory, for apps and
+//! tests that need an S3 endpoint without a real one.
+
+mod auth;
+mod store;
+
+use crate::utils::server::{self, header, Range};
+use crate::utils::{mime, xml};
+pub use auth::Credentials;
+use auth::Payload;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use http::header::{
+    ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, HOST, LAST_MODIFIED,
+};
+use http::request::Parts;
+use http::{HeaderMap, HeaderName, HeaderValue, Method, Request, Response, StatusCode};
+use httpdate::HttpDate;
+use hyper::body::HttpBody;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Server};
+use percent_encoding::percent_decode_str;
+use s3::signing;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::io::SeekFrom;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use store::{Metadata, ObjectInfo, Store, Upload};
+use time::format_description::FormatItem;
+use time::macros::format_description;
+use time::OffsetDateTime;
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+
+const XML_DECLARATION: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n";
+
+const XMLNS: &str = "http://s3.amazonaws.com/doc/2006-03-01/";
+
+const ISO_DATETIME: &[FormatItem<'static>] =
+    format_description!("[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond digits:3]Z");
+
+/// Most keys a listing or a DeleteObjects request may hold
+const MAX_KEYS: usize = 1000;
+
+/// Bytes read from a file at a time when sending an object
+const STREAM_BUFFER: usize = 64 * 1024;
+
+/// Largest chunk of an aws-chunked body, far above what clients send
+const MAX_CHUNK: usize = 16 * 1024 * 1024;
+
+/// Headers stored with an object and sent back when it is read
+const STORED_HEADERS: [&str; 5] = [
+    "cache-control",
+    "content-disposition",
+    "content-encoding",
+    "content-language",
+    "expires",
+];
+
+/// Subresources the server does not implement, refused rather than
+/// mistaken for plain object or bucket requests
+const UNSUPPORTED: [&str; 27] = [
+    "accelerate",
+    "acl",
+    "analytics",
+    "attributes",
+    "cors",
+    "encryption",
+    "intelligent-tiering",
+    "inventory",
+    "legal-hold",
+    "lifecycle",
+    "logging",
+    "metrics",
+    "notification",
+    "object-lock",
+    "ownershipControls",
+    "policy",
+    "policyStatus",
+    "publicAccessBlock",
+    "replication",
+    "requestPayment",
+    "restore",
+    "retention",
+    "select",
+    "tagging",
+    "torrent",
+    "versioning",
+    "versions",
+];
+
+/// An S3 error, sent as the usual `<Error>` document
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, code: &'static str, message: &str) -> Self {
+        Self {
+            status,
+            code,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn bad_request(code: &'static str, message: &str) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, code, message)
+    }
+
+    pub fn forbidden(code: &'static str, message: &str) -> Self {
+        Self::new(StatusCode::FORBIDDEN, code, message)
+    }
+
+    pub fn access_denied(message: &str) -> Self {
+        Self::forbidden("AccessDenied", message)
+    }
+
+    pub fn not_found(code: &'static str, message: &str) -> Self {
+        Self::new(StatusCode::NOT_FOUND, code, message)
+    }
+
+    pub fn conflict(code: &'static str, message: &str) -> Self {
+        Self::new(StatusCode::CONFLICT, code, message)
+    }
+
+    pub fn internal(message: &str) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, "InternalError", message)
+    }
+
+    fn not_implemented(message: &str) -> Self {
+        Self::new(StatusCode::NOT_IMPLEMENTED, "NotImplemented", message)
+    }
+
+    fn method_not_allowed() -> Self {
+        Self::new(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "MethodNotAllowed",
+            "The specified method is not allowed against this resource.",
+        )
+    }
+
+    fn malformed_xml() -> Self {
+        Self::bad_request(
+            "MalformedXML",
+            "The XML you provided was not well-formed or did not validate against our published schema.",
+        )
+    }
+
+    fn response(&self, resource: &str, head: bool) -> Response<Body> {
+        // A HEAD response has no body to carry the details
+        let body = if head {
+            Body::empty()
+        } else {
+            Body::from(format!(
+                "{}<Error><Code>{}</Code><Message>{}</Message><Resource>{}</Resource></Error>",
+                XML_DECLARATION,
+                self.code,
+                xml::escape(&self.message),
+                xml::escape(resource)
+            ))
+        };
+        let mut response = Response::new(body);
+        *response.status_mut() = self.status;
+        response
+            .headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/xml"));
+        response
+    }
+}
+
+impl From<std::io::Error> for ApiError {
+    fn from(err: std::io::Error) -> Self {
+        Self::internal(&err.to_string())
+    }
+}
+
+#[derive(Deserialize)]
+struct CompleteMultipartUpload {
+    #[serde(rename = "Part", default)]
+    parts: Vec<CompletedPart>,
+}
+
+#[derive(Deserialize)]
+struct CompletedPart {
+    #[serde(rename = "PartNumber")]
+    number: u32,
+    #[serde(rename = "ETag")]
+    etag: String,
+}
+
+#[derive(Deserialize)]
+struct Delete {
+    #[serde(rename = "Quiet", default)]
+    quiet: bool,
+    #[serde(rename = "Object", default)]
+    objects: Vec<ObjectIdentifier>,
+}
+
+#[derive(Deserialize)]
+struct ObjectIdentifier {
+    #[serde(rename = "Key")]
+    key: String,
+}
+
+struct State {
+    store: Store,
+    credentials: Credentials,
+}
+
+/// The digest of what was written of a request body
+struct Received {
+    md5: md5::Digest,
+}
+
+fn iso_date(time: SystemTime) -> String {
+    OffsetDateTime::from(time)
+        .format(ISO_DATETIME)
+        .unwrap_or_default()
+}
+
+fn quoted_etag(info: &ObjectInfo) -> String {
+    format!("\"{}\"", info.metadata.etag)
+}
+
+fn empty(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+fn xml_response(body: String) -> Response<Body> {
+    let mut response = Response::new(Body::from(format!("{}{}", XML_DECLARATION, body)));
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/xml"));
+    response
+}
+
+fn etag_response(info: &ObjectInfo) -> Response<Body> {
+    let mut response = empty(StatusCode::OK);
+    if let Ok(etag) = HeaderValue::from_str(&quoted_etag(info)) {
+        response.headers_mut().insert(ETAG, etag);
+    }
+    response
+}
+
+/// Splits a request into bucket and key, taking the bucket from the host
+/// for virtual-hosted requests like `bucket.localhost:9000/key` and from
+/// the path otherwise
+fn address(parts: &Parts, store: &Store) -> Result<(Option<String>, String), ApiError> {
+    let path = percent_decode_str(parts.uri.path())
+        .decode_utf8()
+        .map_err(|_| ApiError::bad_request("InvalidURI", "Couldn't parse the specified URI."))?;
+    let path = path.strip_prefix('/').unwrap_or(&path);
+
+    let host = header(&parts.headers, HOST).unwrap_or("");
+    let hostname = match host.rsplit_once(':') {
+        Some((hostname, port)) if port.bytes().all(|b| b.is_ascii_digit()) => hostname,
+        _ => host,
+    };
+    if hostname.parse::<IpAddr>().is_err() {
+        if let Some((bucket, domain)) = hostname.split_once('.') {
+            if domain == "localhost" || store.has_bucket(bucket) {
+                return Ok((Some(bucket.to_string()), path.to_string()));
+            }
+        }
+    }
+
+    Ok(match path.split_once('/') {
+        Some((bucket, key)) => (Some(bucket.to_string()), key.to_string()),
+        None if path.is_empty() => (None, String::new()),
+        None => (Some(path.to_string()), String::new()),
+    })
+}
+
+/// What to keep of an upload's headers
+fn metadata_from(headers: &HeaderMap, key: &str) -> Metadata {
+    let content_type = header(headers, CONTENT_TYPE)
+        .map(str::to_string)
+        .or_else(|| mime::content_type_from_name(Path::new(key), &BTreeMap::new()))
+        .unwrap_or_else(|| mime::DEFAULT_CONTENT_TYPE.to_string());
+    let mut metadata = Metadata {
+        content_type: Some(content_type),
+        ..Default::default()
+    };
+
+    for name in STORED_HEADERS {
+        let Some(value) = header(headers, name) else {
+            continue;
+        };
+        // aws-chunked is how the body was sent, not how the object is encoded
+        let value = if name == "content-encoding" {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|encoding| *encoding != "aws-chunked")
+                .collect::<Vec<_>>()
+                .join(",")
+        } else {
+            value.to_string()
+        };
+        if !value.is_empty() {
+            metadata.headers.insert(name.to_string(), value);
+        }
+    }
+
+    for (name, value) in headers {
+        if let (Some(user), Ok(value)) = (name.as_str().strip_prefix("x-amz-meta-"), value.to_str())
+        {
+            metadata.user.insert(user.to_string(), value.to_string());
+        }
+    }
+    metadata
+}
+
+/// The headers describing an object on GET and HEAD, with the overrides a
+/// presigned URL may ask for through `response-*` parameters
+fn object_headers(info: &ObjectInfo, query: &HashMap<String, String>) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    let mut insert = |name: &str, value: &str| {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            headers.insert(name, value);
+        }
+    };
+
+    insert(ETAG.as_str(), &quoted_etag(info));
+    insert(
+        LAST_MODIFIED.as_str(),
+        &httpdate::fmt_http_date(info.modified),
+    );
+    insert(ACCEPT_RANGES.as_str(), "bytes");
+    if let Some(content_type) = &info.metadata.content_type {
+        insert(CONTENT_TYPE.as_str(), content_type);
+    }
+    for (name, value) in &info.metadata.headers {
+        insert(name, value);
+    }
+    for (name, value) in &info.metadata.user {
+        insert(&format!("x-amz-meta-{}", name), value);
+    }
+    for name in ["content-type"].iter().chain(&STORED_HEADERS) {
+        if let Some(value) = query.get(&format!("response-{}", name)) {
+            insert(name, value);
+        }
+    }
+    headers
+}
+
+fn check_content_md5(headers: &HeaderMap, md5: &md5::Digest) -> Result<(), ApiError> {
+    match header(headers, "content-md5") {
+        Some(expected) if expected != BASE64.encode(md5.as_ref()) => Err(ApiError::bad_request(
+            "BadDigest",
+            "The Content-MD5 you specified did not match what we received.",
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Writes a request body to `writer`, decoding aws-chunked bodies and
+/// checking every signature and hash the client sent along
+async fn receive<W: AsyncWrite + Unpin>(
+    mut body: Body,
+    payload: Payload,
+    writer: &mut W,
+) -> Result<Received, ApiError> {
+    let (chunked, mut signer, expected) = match payload {
+        Payload::Sha256(hash) => (false, None, Some(hash)),
+        Payload::Unsigned => (false, None, None),
+        Payload::SignedChunks(signer) => (true, Some(signer), None),
+        Payload::UnsignedChunks => (true, None, None),
+    };
+    let incomplete = || {
+        ApiError::bad_request(
+            "IncompleteBody",
+            "You did not provide the number of bytes specified by the Content-Length HTTP header",
+        )
+    };
+    let malformed = || ApiError::bad_request("InvalidRequest", "The aws-chunked body is malformed");
+
+    let mut md5 = md5::Context::new();
+    let mut sha256 = Sha256::new();
+    let mut pending = Vec::new();
+    let mut finished = false;
+
+    while let Some(data) = body.data().await {
+        let data = data.map_err(|_| incomplete())?;
+        if !chunked {
+            writer.write_all(&data).await?;
+            md5.consume(&data);
+            sha256.update(&data);
+            continue;
+        }
+
+        // Each chunk is `<hex size>[;chunk-signature=<signature>]\r\n<data>\r\n`,
+        // the last one empty and followed by optional trailers
+        pending.extend_from_slice(&data);
+        while !finished {
+            let Some(end) = pending.windows(2).position(|pair| pair == b"\r\n") else {
+                break;
+            };
+            let line = std::str::from_utf8(&pending[..end]).map_err(|_| malformed())?;
+            let (length, signature) = match line.split_once(';') {
+                Some((length, extension)) => (length, extension.strip_prefix("chunk-signature=")),
+                None => (line, None),
+            };
+            let length = usize::from_str_radix(length.trim(), 16).map_err(|_| malformed())?;
+            if length > MAX_CHUNK {
+                return Err(malformed());
+            }
+            let start = end + 2;
+            if length > 0 && pending.len() < start + length + 2 {
+                break;
+            }
+
+            let chunk = &pending[start..start + length];
+            if let Some(signer) = &mut signer {
+                signer.verify(signature.ok_or_else(malformed)?, chunk)?;
+            }
+            writer.write_all(chunk).await?;
+            md5.consume(chunk);
+
+            if length == 0 {
+                finished = true;
+            } else {
+                pending.drain(..start + length + 2);
+            }
+        }
+    }
+
+    if chunked && !finished {
+        return Err(incomplete());
+    }
+    if let Some(expected) = expected {
+        if hex::encode(sha256.finalize()) != expected {
+            return Err(ApiError::bad_request(
+                "XAmzContentSHA256Mismatch",
+                "The provided 'x-amz-content-sha256' header does not match what was computed.",
+            ));
+        }
+    }
+    writer.flush().await?;
+    Ok(Received { md5: md5.compute() })
+}
+
+/// Receives an upload into a temporary file, removed again if anything
+/// about the body is wrong
+async fn receive_to_temp(
+    store: &Store,
+    headers: &HeaderMap,
+    body: Body,
+    payload: Payload,
+) -> Result<(PathBuf, Received), ApiError> {
+    let (temp, mut file) = store.temp_file().await?;
+    let received = receive(body, payload, &mut file).await;
+    drop(file);
+    match received.and_then(|received| {
+        check_content_md5(headers, &received.md5)?;
+        Ok(received)
+    }) {
+        Ok(received) => Ok((temp, received)),
+        Err(e) => {
+            let _ = fs::remove_file(&temp).await;
+            Err(e)
+        }
+    }
+}
+
+/// Reads a small XML request body, like the part list of a multipart upload
+async fn receive_xml<T: for<'de> Deserialize<'de>>(
+    body: Body,
+    payload: Payload,
+) -> Result<T, ApiError> {
+    let mut data = Vec::new();
+    receive(body, payload, &mut data).await?;
+    quick_xml::de::from_reader(data.as_slice()).map_err(|_| ApiError::malformed_xml())
+}
+
+async fn stream_file(path: &Path, start: u64, length: u64) -> Result<Body, ApiError> {
+    let mut file = fs::File::open(path).await?;
+    file.seek(SeekFrom::Start(start)).await?;
+    let stream = futures::stream::try_unfold(file.take(length), |mut reader| async move {
+        let mut buffer = vec![0; STREAM_BUFFER];
+        let read = reader.read(&mut buffer).await?;
+        buffer.truncate(read);
+        Ok::<_, std::io::Error>((read > 0).then_some((buffer, reader)))
+    });
+    Ok(Body::wrap_stream(stream))
+}
+
+async fn list_buckets(store: &Store) -> Result<Response<Body>, ApiError> {
+    let mut body = format!(
+        "<ListAllMyBucketsResult xmlns=\"{}\"><Owner><ID>s3mgr</ID><DisplayName>s3mgr</DisplayName></Owner><Buckets>",
+        XMLNS
+    );
+    for (name, created) in store.list_buckets().await? {
+        body.push_str(&format!(
+            "<Bucket><Name>{}</Name><CreationDate>{}</CreationDate></Bucket>",
+            xml::escape(&name),
+            iso_date(created)
+        ));
+    }
+    body.push_str("</Buckets></ListAllMyBucketsResult>");
+    Ok(xml_response(body))
+}
+
+/// ListObjectsV2, or the original ListObjects without `list-type=2`
+async fn list_objects(
+    store: &Store,
+    bucket: &str,
+    query: &HashMap<String, String>,
+) -> Result<Response<Body>, ApiError> {
+    let param = |name: &str| query.get(name).map(String::as_str);
+    let v2 = param("list-type") == Some("2");
+    let prefix = param("prefix").unwrap_or("");
+    let delimiter = param("delimiter").filter(|delimiter| !delimiter.is_empty());
+    let max_keys = match param("max-keys") {
+        Some(max_keys) => max_keys
+            .parse::<usize>()
+            .map_err(|_| ApiError::bad_request("InvalidArgument", "max-keys must be a number"))?
+            .min(MAX_KEYS),
+        None => MAX_KEYS,
+    };
+    // Keys are URL-encoded on request, e.g. by the AWS CLI, so any
+    // character survives the XML
+    let url_encoded = param("encoding-type") == Some("url");
+    let encode = |text: &str| {
+        if url_encoded {
+            xml::escape(&signing::uri_encode(text, false))
+        } else {
+            xml::escape(text)
+        }
+    };
+
+    let token = param("continuation-token");
+    let after = match (v2, token) {
+        (true, Some(token)) => BASE64
+            .decode(token)
+            .ok()
+            .and_then(|after| String::from_utf8(after).ok())
+            .ok_or_else(|| {
+                ApiError::bad_request(
+                    "InvalidArgument",
+                    "The continuation token provided is incorrect",
+                )
+            })?,
+        (true, None) => param("start-after").unwrap_or("").to_string(),
+        (false, _) => param("marker").unwrap_or("").to_string(),
+    };
+
+    let listing = store
+        .list(bucket, prefix, delimiter, &after, max_keys)
+        .await?;
+
+    let mut body = format!(
+        "<ListBucketResult xmlns=\"{}\"><Name>{}</Name><Prefix>{}</Prefix><MaxKeys>{}</MaxKeys>",
+        XMLNS,
+        xml::escape(bucket),
+        encode(prefix),
+        max_keys
+    );
+    if let Some(delimiter) = delimiter {
+        body.push_str(&format!("<Delimiter>{}</Delimiter>", encode(delimiter)));
+    }
+    if url_encoded {
+        body.push_str("<EncodingType>url</EncodingType>");
+    }
+    body.push_str(&format!("<IsTruncated>{}</IsTruncated>", listing.truncated));
+    let next = listing.last.as_deref().filter(|_| listing.truncated);
+    if v2 {
+        body.push_str(&format!(
+            "<KeyCount>{}</KeyCount>",
+            listing.objects.len() + listing.prefixes.len()
+        ));
+        if let Some(token) = token {
+            body.push_str(&format!(
+                "<ContinuationToken>{}</ContinuationToken>",
+                xml::escape(token)
+            ));
+        }
+        if let Some(start_after) = param("start-after") {
+            body.push_str(&format!("<StartAfter>{}</StartAfter>", encode(start_after)));
+        }
+        if let Some(next) = next {
+            body.push_str(&format!(
+                "<NextContinuationToken>{}</NextContinuationToken>",
+                BASE64.encode(next)
+            ));
+        }
+    } else {
+        body.push_str(&format!("<Marker>{}</Marker>", encode(&after)));
+        if let Some(next) = next {
+            body.push_str(&format!("<NextMarker>{}</NextMarker>", encode(next)));
+        }
+    }
+
+    for object in &listing.objects {
+        body.push_str(&format!(
+            "<Contents><Key>{}</Key><LastModified>{}</LastModified><ETag>{}</ETag><Size>{}</Size><StorageClass>STANDARD</StorageClass></Contents>",
+            encode(&object.key),
+            iso_date(object.modified),
+            xml::escape(&quoted_etag(object)),
+            object.size
+        ));
+    }
+    for prefix in &listing.prefixes {
+        body.push_str(&format!(
+            "<CommonPrefixes><Prefix>{}</Prefix></CommonPrefixes>",
+            encode(prefix)
+        ));
+    }
+    body.push_str("</ListBucketResult>");
+    Ok(xml_response(body))
+}
+
+async fn delete_objects(
+    store: &Store,
+    bucket: &str,
+    body: Body,
+    payload: Payload,
+) -> Result<Response<Body>, ApiError> {
+    let request: Delete = receive_xml(body, payload).await?;
+    if request.objects.len() > MAX_KEYS {
+        return Err(ApiError::malformed_xml());
+    }
+
+    let mut body = format!("<DeleteResult xmlns=\"{}\">", XMLNS);
+    for object in &request.objects {
+        let key = xml::escape(&object.key);
+        match store.delete(bucket, &object.key).await {
+            Ok(()) if request.quiet => {}
+            Ok(()) => body.push_str(&format!("<Deleted><Key>{}</Key></Deleted>", key)),
+            Err(e) => body.push_str(&format!(
+                "<Error><Key>{}</Key><Code>{}</Code><Message>{}</Message></Error>",
+                key,
+                e.code,
+                xml::escape(&e.message)
+            )),
+        }
+    }
+    body.push_str("</DeleteResult>");
+    Ok(xml_response(body))
+}
+
+async fn put_object(
+    store: &Store,
+    headers: &HeaderMap,
+    bucket: &str,
+    key: &str,
+    body: Body,
+    payload: Payload,
+) -> Result<Response<Body>, ApiError> {
+    let metadata = metadata_from(headers, key);
+    let (temp, received) = receive_to_temp(store, headers, body, payload).await?;
+    let metadata = Metadata {
+        etag: format!("{:x}", received.md5),
+        ..metadata
+    };
+    match store.commit(bucket, key, &temp, metadata).await {
+        Ok(info) => Ok(etag_response(&info)),
+        Err(e) => {
+            let _ = fs::remove_file(&temp).await;
+            Err(e)
+        }
+    }
+}
+
+/// The bucket and key of `x-amz-copy-source`, `/bucket/key` or `bucket/key`
+fn copy_source(headers: &HeaderMap) -> Result<(String, String), ApiError> {
+    let invalid = || {
+        ApiError::bad_request(
+            "InvalidArgument",
+            "Copy Source must mention the source bucket and key: sourcebucket/sourcekey",
+        )
+    };
+    let source = header(headers, "x-amz-copy-source").ok_or_else(invalid)?;
+    let (path, query) = source.split_once('?').unwrap_or((source, ""));
+    if query
+        .strip_prefix("versionId=")
+        .is_some_and(|version_id| version_id != "null")
+    {
+        return Err(ApiError::not_found(
+            "NoSuchVersion",
+            "The specified version does not exist.",
+        ));
+    }
+    let path = percent_decode_str(path)
+        .decode_utf8()
+        .map_err(|_| invalid())?;
+    match path.trim_start_matches('/').split_once('/') {
+        Some((bucket, key)) if !key.is_empty() => Ok((bucket.to_string(), key.to_string())),
+        _ => Err(invalid()),
+    }
+}
+
+async fn copy_object(
+    store: &Store,
+    headers: &HeaderMap,
+    bucket: &str,
+    key: &str,
+) -> Result<Response<Body>, ApiError> {
+    let (source_bucket, source_key) = copy_source(headers)?;
+    store.check_bucket(&source_bucket)?;
+    let source = store.head(&source_bucket, &source_key).await?;
+
+    let replace = header(headers, "x-amz-metadata-directive") == Some("REPLACE");
+    if !replace && source_bucket == bucket && source_key == key {
+        return Err(ApiError::bad_request(
+            "InvalidRequest",
+            "This copy request is illegal because it is trying to copy an object to itself without changing the object's metadata.",
+        ));
+    }
+    let metadata = if replace {
+        Metadata {
+            etag: source.metadata.etag,
+            ..metadata_from(headers, key)
+        }
+    } else {
+        source.metadata
+    };
+
+    let temp = store.copy_to_temp(&source_bucket, &source_key).await?;
+    let info = match store.commit(bucket, key, &temp, metadata).await {
+        Ok(info) => info,
+        Err(e) => {
+            let _ = fs::remove_file(&temp).await;
+            return Err(e);
+        }
+    };
+    Ok(xml_response(format!(
+        "<CopyObjectResult xmlns=\"{}\"><LastModified>{}</LastModified><ETag>{}</ETag></CopyObjectResult>",
+        XMLNS,
+        iso_date(info.modified),
+        xml::escape(&quoted_etag(&info))
+    )))
+}
+
+async fn get_object(
+    store: &Store,
+    parts: &Parts,
+    bucket: &str,
+    key: &str,
+    query: &HashMap<String, String>,
+) -> Result<Response<Body>, ApiError> {
+    if query
+        .get("versionId")
+        .is_some_and(|version_id| version_id != "null")
+    {
+        return Err(ApiError::not_found(
+            "NoSuchVersion",
+            "The specified version does not exist.",
+        ));
+    }
+    let info = store.head(bucket, key).await?;
+    let mut headers = object_headers(&info, query);
+
+    let matches = |list: &str| server::etag_matches(list, &info.metadata.etag);
+    let date =
+        |name: &str| header(&parts.headers, name).and_then(|date| date.parse::<HttpDate>().ok());
+    let modified = HttpDate::from(info.modified);
+
+    let precondition_failed = match header(&parts.headers, "if-match") {
+        Some(list) => !matches(list),
+        None => date("if-unmodified-since").is_some_and(|since| modified > since),
+    };
+    if precondition_failed {
+        return Err(ApiError::new(
+            StatusCode::PRECONDITION_FAILED,
+            "PreconditionFailed",
+            "At least one of the pre-conditions you specified did not hold",
+        ));
+    }
+    let not_modified = match header(&parts.headers, "if-none-match") {
+        Some(list) => matches(list),
+        None => date("if-modified-since").is_some_and(|since| modified <= since),
+    };
+    if not_modified {
+        let mut response = empty(StatusCode::NOT_MODIFIED);
+        for name in [ETAG, LAST_MODIFIED] {
+            if let Some(value) = headers.remove(&name) {
+                response.headers_mut().insert(name, value);
+            }
+        }
+        return Ok(response);
+    }
+
+    let range =
+        header(&parts.headers, "range").and_then(|range| server::parse_range(range, info.size));
+    let (status, start, length) = match range {
+        Some(Range::Unsatisfiable) => {
+            return Err(ApiError::new(
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                "InvalidRange",
+                "The requested range is not satisfiable",
+            ))
+        }
+        Some(Range::Bytes(first, last)) => {
+            let content_range = format!("bytes {}-{}/{}", first, last, info.size);
+            if let Ok(value) = HeaderValue::from_str(&content_range) {
+                headers.insert(CONTENT_RANGE, value);
+            }
+            (StatusCode::PARTIAL_CONTENT, first, last - first + 1)
+        }
+        None => (StatusCode::OK, 0, info.size),
+    };
+    headers.insert(CONTENT_LENGTH, length.into());
+
+    let body = if parts.method == Method::HEAD || length == 0 {
+        Body::empty()
+    } else {
+        stream_file(&store.object_path(bucket, key), start, length).await?
+    };
+    let mut response = Response::new(body);
+    *response.status_mut() = status;
+    *response.headers_mut() = headers;
+    Ok(response)
+}
+
+async fn create_upload(
+    store: &Store,
+    headers: &HeaderMap,
+    bucket: &str,
+    key: &str,
+) -> Result<Response<Body>, ApiError> {
+    let id = store
+        .create_upload(&Upload {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            metadata: metadata_from(headers, key),
+        })
+        .await?;
+    Ok(xml_response(format!(
+        "<InitiateMultipartUploadResult xmlns=\"{}\"><Bucket>{}</Bucket><Key>{}</Key><UploadId>{}</UploadId></InitiateMultipartUploadResult>",
+        XMLNS,
+        xml::escape(bucket),
+        xml::escape(key),
+        id
+    )))
+}
+
+/// The upload `uploadId` names, which must be for this bucket and key
+async fn find_upload(
+    store: &Store,
+    query: &HashMap<String, String>,
+    bucket: &str,
+    key: &str,
+) -> Result<String, ApiError> {
+    let id = query.get("uploadId").map_or("", String::as_str);
+    let upload = store.upload(id).await?;
+    if upload.bucket != bucket || upload.key != key {
+        return Err(ApiError::not_found(
+            "NoSuchUpload",
+            "The specified multipart upload does not exist.",
+        ));
+    }
+    Ok(id.to_string())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn upload_part(
+    store: &Store,
+    headers: &HeaderMap,
+    query: &HashMap<String, String>,
+    bucket: &str,
+    key: &str,
+    body: Body,
+    payload: Payload,
+) -> Result<Response<Body>, ApiError> {
+    let id = find_upload(store, query, bucket, key).await?;
+    let number = query
+        .get("partNumber")
+        .and_then(|number| number.parse::<u32>().ok())
+        .filter(|number| (1..=10_000).contains(number))
+        .ok_or_else(|| {
+            ApiError::bad_request(
+                "InvalidArgument",
+                "Part number must be an integer between 1 and 10000, inclusive",
+            )
+        })?;
+    if headers.contains_key("x-amz-copy-source") {
+        return Err(ApiError::not_implemented("UploadPartCopy is not supported"));
+    }
+
+    let (temp, received) = receive_to_temp(store, headers, body, payload).await?;
+    let etag = format!("{:x}", received.md5);
+    if let Err(e) = store.commit_part(&id, number, &temp, &etag).await {
+        let _ = fs::remove_file(&temp).await;
+        return Err(e);
+    }
+
+    let mut response = empty(StatusCode::OK);
+    if let Ok(etag) = HeaderValue::from_str(&format!("\"{}\"", etag)) {
+        response.headers_mut().insert(ETAG, etag);
+    }
+    Ok(response)
+}
+
+async fn complete_upload(
+    store: &Store,
+    query: &HashMap<String, String>,
+    bucket: &str,
+    key: &str,
+    body: Body,
+    payload: Payload,
+) -> Result<Response<Body>, ApiError> {
+    let id = find_upload(store, query, bucket, key).await?;
+    let request: CompleteMultipartUpload = receive_xml(body, payload).await?;
+    let parts: Vec<(u32, String)> = request
+        .parts
+        .into_iter()
+        .map(|part| (part.number, part.etag))
+        .collect();
+    let info = store.complete_upload(&id, &parts).await?;
+    Ok(xml_response(format!(
+        "<CompleteMultipartUploadResult xmlns=\"{}\"><Location>/{}/{}</Location><Bucket>{}</Bucket><Key>{}</Key><ETag>{}</ETag></CompleteMultipartUploadResult>",
+        XMLNS,
+        xml::escape(bucket),
+        xml::escape(key),
+        xml::escape(bucket),
+        xml::escape(key),
+        xml::escape(&quoted_etag(&info))
+    )))
+}
+
+async fn route(state: &State, request: Request<Body>) -> Result<Response<Body>, ApiError> {
+    let (parts, body) = request.into_parts();
+    let payload = auth::verify(&parts, &state.credentials)?;
+    let query: HashMap<String, String> = parts
+        .uri
+        .query()
+        .map(|query| {
+            url::form_urlencoded::parse(query.as_bytes())
+                .into_owned()
+                .collect()
+        })
+        .unwrap_or_default();
+    if let Some(name) = UNSUPPORTED.iter().find(|name| query.contains_key(**name)) {
+        return Err(ApiError::not_implemented(&format!(
+            "The {} subresource is not supported",
+            name
+        )));
+    }
+
+    let store = &state.store;
+    let has = |name: &str| query.contains_key(name);
+    let (bucket, key) = address(&parts, store)?;
+    let Some(bucket) = bucket else {
+        return match parts.method {
+            Method::GET => list_buckets(store).await,
+            _ => Err(ApiError::method_not_allowed()),
+        };
+    };
+
+    if key.is_empty() {
+        if parts.method == Method::PUT {
+            store.create_bucket(&bucket).await?;
+            return Ok(empty(StatusCode::OK));
+        }
+        store.check_bucket(&bucket)?;
+        return match parts.method {
+            Method::HEAD => Ok(empty(StatusCode::OK)),
+            Method::DELETE => {
+                store.delete_bucket(&bucket).await?;
+                Ok(empty(StatusCode::NO_CONTENT))
+            }
+            Method::GET if has("location") => Ok(xml_response(format!(
+                "<LocationConstraint xmlns=\"{}\"/>",
+                XMLNS
+            ))),
+            Method::GET if has("uploads") => Err(ApiError::not_implemented(
+                "ListMultipartUploads is not supported",
+            )),
+            Method::GET => list_objects(store, &bucket, &query).await,
+            Method::POST if has("delete") => delete_objects(store, &bucket, body, payload).await,
+            _ => Err(ApiError::method_not_allowed()),
+        };
+    }
+
+    store.check_bucket(&bucket)?;
+    let headers = &parts.headers;
+    match parts.method {
+        Method::PUT if has("uploadId") => {
+            upload_part(store, headers, &query, &bucket, &key, body, payload).await
+        }
+        Method::PUT if headers.contains_key("x-amz-copy-source") => {
+            copy_object(store, headers, &bucket, &key).await
+        }
+        Method::PUT => put_object(store, headers, &bucket, &key, body, payload).await,
+        Method::GET if has("uploadId") => {
+            Err(ApiError::not_implemented("ListParts is not supported"))
+        }
+        Method::GET | Method::HEAD => get_object(store, &parts, &bucket, &key, &query).await,
+        Method::DELETE if has("uploadId") => {
+            let id = find_upload(store, &query, &bucket, &key).await?;
+            store.abort_upload(&id).await?;
+            Ok(empty(StatusCode::NO_CONTENT))
+        }
+        Method::DELETE => {
+            store.delete(&bucket, &key).await?;
+            Ok(empty(StatusCode::NO_CONTENT))
+        }
+        Method::POST if has("uploads") => create_upload(store, headers, &bucket, &key).await,
+        Method::POST if has("uploadId") => {
+            complete_upload(store, &query, &bucket, &key, body, payload).await
+        }
+        _ => Err(ApiError::method_not_allowed()),
+    }
+}
+
+async fn handle(state: Arc<State>, request: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let response = match route(&state, request).await {
+        Ok(response) => response,
+        Err(e) => e.response(&path, method == Method::HEAD),
+    };
+
+    server::log_request(&method, &path, response.status());
+    Ok(response)
+}
+
+/// Serves the directories of `root` as buckets at `address` until the
+/// process is stopped
+pub async fn run(root: &Path, address: SocketAddr, credentials: Credentials) -> anyhow::Result<()> {
+    let state = Arc::new(State {
+        store: Store::open(root).await?,
+        credentials,
+    });
+    let make_service = make_service_fn(move |_| {
+        let state = Arc::clone(&state);
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                handle(Arc::clone(&state), request)
+            }))
+        }
+    });
+    Server::try_bind(&address)?.serve(make_service).await?;
+    Ok(())
+}
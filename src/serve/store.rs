@@ -0,0 +1,699 @@
+//! Buckets and objects kept as plain directories and files under the served
+//! root, with what S3 knows about each object in a hidden tree beside them.
+//!
+//! An object `photos/cat.jpg` of bucket `pets` is the file
+//! `<root>/pets/photos/cat.jpg`, a directory marker `photos/` is the
+//! directory itself. Metadata, uploads in progress and half-written files
+//! live in `<root>/.s3mgr`, which no bucket name can clash with.
+
+use super::ApiError;
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::OsRng;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+const SYSTEM_DIR: &str = ".s3mgr";
+
+const METADATA_SUFFIX: &str = ".meta";
+
+/// Smallest size S3 accepts for every part of a multipart upload but the last
+const MIN_PART_SIZE: u64 = 5 * 1024 * 1024;
+
+/// What S3 keeps about an object besides its content
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Metadata {
+    /// ETag without the quotes
+    pub etag: String,
+    pub content_type: Option<String>,
+    /// Headers returned with the object, e.g. Cache-Control
+    pub headers: BTreeMap<String, String>,
+    /// User metadata, without the `x-amz-meta-` prefix
+    pub user: BTreeMap<String, String>,
+}
+
+/// Metadata as stored, with the file's size and modification time when it
+/// was written so changes made to the file outside the server are noticed
+#[derive(Serialize, Deserialize)]
+struct StoredMetadata {
+    size: u64,
+    modified: u64,
+    #[serde(flatten)]
+    metadata: Metadata,
+}
+
+pub struct ObjectInfo {
+    pub key: String,
+    pub size: u64,
+    pub modified: SystemTime,
+    pub metadata: Metadata,
+}
+
+#[derive(Default)]
+pub struct Listing {
+    pub objects: Vec<ObjectInfo>,
+    pub prefixes: Vec<String>,
+    pub truncated: bool,
+    /// The last key or common prefix returned, where the next page starts
+    pub last: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Upload {
+    pub bucket: String,
+    pub key: String,
+    pub metadata: Metadata,
+}
+
+pub struct Store {
+    root: PathBuf,
+}
+
+fn nanos(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_nanos() as u64)
+}
+
+fn random_id() -> String {
+    let mut id = [0; 16];
+    OsRng.fill_bytes(&mut id);
+    hex::encode(id)
+}
+
+/// The "directory" a key is in, e.g. `a/b` for `a/b/c` and `a/b/`
+fn parent(key: &str) -> &str {
+    let key = key.strip_suffix('/').unwrap_or(key);
+    &key[..key.rfind('/').unwrap_or(0)]
+}
+
+fn no_such_key() -> ApiError {
+    ApiError::not_found("NoSuchKey", "The specified key does not exist.")
+}
+
+fn no_such_upload() -> ApiError {
+    ApiError::not_found(
+        "NoSuchUpload",
+        "The specified multipart upload does not exist.",
+    )
+}
+
+fn conflict() -> ApiError {
+    ApiError::conflict(
+        "InvalidArgument",
+        "The key conflicts with an existing object or prefix on the filesystem",
+    )
+}
+
+/// Checks that `key` maps onto a path below its bucket. S3 allows empty,
+/// `.` and `..` segments, the filesystem cannot hold them.
+fn check_key(key: &str) -> Result<(), ApiError> {
+    if key.len() > 1024 {
+        return Err(ApiError::bad_request(
+            "KeyTooLongError",
+            "Your key is too long",
+        ));
+    }
+    let name = key.strip_suffix('/').unwrap_or(key);
+    if name
+        .split('/')
+        .any(|segment| matches!(segment, "" | "." | ".."))
+        || key.contains('\0')
+    {
+        return Err(ApiError::bad_request(
+            "InvalidArgument",
+            "The key cannot be stored on the filesystem",
+        ));
+    }
+    Ok(())
+}
+
+/// Checks that the directory part of a listing prefix stays below its
+/// bucket, for the same reasons as `check_key`
+fn check_prefix(prefix: &str) -> Result<(), ApiError> {
+    let dir = &prefix[..prefix.rfind('/').unwrap_or(0)];
+    if (!dir.is_empty()
+        && dir
+            .split('/')
+            .any(|segment| matches!(segment, "" | "." | "..")))
+        || prefix.starts_with('/')
+        || prefix.contains('\0')
+    {
+        return Err(ApiError::bad_request(
+            "InvalidArgument",
+            "The prefix cannot be stored on the filesystem",
+        ));
+    }
+    Ok(())
+}
+
+/// Checks a bucket name the way S3 does, which also keeps it from
+/// escaping the root or naming the hidden system directory
+pub fn check_bucket_name(name: &str) -> Result<(), ApiError> {
+    let valid = (3..=63).contains(&name.len())
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'.' || b == b'-')
+        && name.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && name.ends_with(|c: char| c.is_ascii_alphanumeric())
+        && !name.contains("..");
+    if valid {
+        Ok(())
+    } else {
+        Err(ApiError::bad_request(
+            "InvalidBucketName",
+            "The specified bucket is not valid.",
+        ))
+    }
+}
+
+impl Store {
+    pub async fn open(root: &Path) -> std::io::Result<Self> {
+        let store = Self {
+            root: root.to_path_buf(),
+        };
+        for dir in ["tmp", "meta", "uploads"] {
+            fs::create_dir_all(store.system_dir().join(dir)).await?;
+        }
+        Ok(store)
+    }
+
+    fn system_dir(&self) -> PathBuf {
+        self.root.join(SYSTEM_DIR)
+    }
+
+    fn bucket_dir(&self, bucket: &str) -> PathBuf {
+        self.root.join(bucket)
+    }
+
+    pub fn object_path(&self, bucket: &str, key: &str) -> PathBuf {
+        self.bucket_dir(bucket).join(key.trim_end_matches('/'))
+    }
+
+    /// The metadata of `a/b` is `a/b.meta` and that of the marker `a/` is
+    /// `a/.meta`, so no two keys share a metadata file
+    fn metadata_path(&self, bucket: &str, key: &str) -> PathBuf {
+        self.system_dir()
+            .join("meta")
+            .join(bucket)
+            .join(format!("{}{}", key, METADATA_SUFFIX))
+    }
+
+    fn upload_dir(&self, id: &str) -> PathBuf {
+        self.system_dir().join("uploads").join(id)
+    }
+
+    pub fn has_bucket(&self, bucket: &str) -> bool {
+        check_bucket_name(bucket).is_ok() && self.bucket_dir(bucket).is_dir()
+    }
+
+    pub fn check_bucket(&self, bucket: &str) -> Result<(), ApiError> {
+        if self.has_bucket(bucket) {
+            Ok(())
+        } else {
+            Err(ApiError::not_found(
+                "NoSuchBucket",
+                "The specified bucket does not exist",
+            ))
+        }
+    }
+
+    /// Every directory of the root is a bucket, with its creation time
+    pub async fn list_buckets(&self) -> Result<Vec<(String, SystemTime)>, ApiError> {
+        let mut buckets = Vec::new();
+        let mut entries = fs::read_dir(&self.root).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            let metadata = entry.metadata().await?;
+            if metadata.is_dir() && check_bucket_name(&name).is_ok() {
+                let created = metadata.created().or_else(|_| metadata.modified())?;
+                buckets.push((name, created));
+            }
+        }
+        buckets.sort();
+        Ok(buckets)
+    }
+
+    pub async fn create_bucket(&self, bucket: &str) -> Result<(), ApiError> {
+        check_bucket_name(bucket)?;
+        match fs::create_dir(self.bucket_dir(bucket)).await {
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => Err(ApiError::conflict(
+                "BucketAlreadyOwnedByYou",
+                "Your previous request to create the named bucket succeeded and you already own it.",
+            )),
+            result => Ok(result?),
+        }
+    }
+
+    pub async fn delete_bucket(&self, bucket: &str) -> Result<(), ApiError> {
+        self.check_bucket(bucket)?;
+        if fs::remove_dir(self.bucket_dir(bucket)).await.is_err() {
+            return Err(ApiError::conflict(
+                "BucketNotEmpty",
+                "The bucket you tried to delete is not empty",
+            ));
+        }
+        let metadata_dir = self.system_dir().join("meta").join(bucket);
+        match fs::remove_dir_all(metadata_dir).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Creates an empty file to receive an upload, moved into place once
+    /// complete so readers never see half an object
+    pub async fn temp_file(&self) -> Result<(PathBuf, fs::File), ApiError> {
+        let path = self.system_dir().join("tmp").join(random_id());
+        let file = fs::File::create(&path).await?;
+        Ok((path, file))
+    }
+
+    async fn read_metadata(&self, bucket: &str, key: &str) -> Option<StoredMetadata> {
+        let data = fs::read(self.metadata_path(bucket, key)).await.ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    async fn write_metadata(
+        &self,
+        bucket: &str,
+        key: &str,
+        stored: &StoredMetadata,
+    ) -> Result<(), ApiError> {
+        let path = self.metadata_path(bucket, key);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).await.map_err(|_| conflict())?;
+        }
+        let data = serde_json::to_vec(stored).map_err(|e| ApiError::internal(&e.to_string()))?;
+        fs::write(path, data).await?;
+        Ok(())
+    }
+
+    pub async fn head(&self, bucket: &str, key: &str) -> Result<ObjectInfo, ApiError> {
+        check_key(key)?;
+        let stat = match fs::metadata(self.object_path(bucket, key)).await {
+            Ok(stat) => stat,
+            Err(_) => return Err(no_such_key()),
+        };
+        let stored = self.read_metadata(bucket, key).await;
+
+        if key.ends_with('/') {
+            // Only directories created as markers are objects
+            return match stored {
+                Some(stored) if stat.is_dir() => Ok(ObjectInfo {
+                    key: key.to_string(),
+                    size: 0,
+                    modified: UNIX_EPOCH + std::time::Duration::from_nanos(stored.modified),
+                    metadata: stored.metadata,
+                }),
+                _ => Err(no_such_key()),
+            };
+        }
+        if !stat.is_file() {
+            return Err(no_such_key());
+        }
+
+        let modified = stat.modified()?;
+        let size = stat.len();
+        let metadata = match stored {
+            Some(stored) if stored.size == size && stored.modified == nanos(modified) => {
+                stored.metadata
+            }
+            // A file the server did not write, or that changed since, gets
+            // an ETag from its size and time, which the dash keeps from
+            // passing for an MD5
+            _ => Metadata {
+                etag: format!("{:x}-{:x}", nanos(modified), size),
+                content_type: crate::utils::mime::content_type_from_name(
+                    Path::new(key),
+                    &BTreeMap::new(),
+                ),
+                ..Default::default()
+            },
+        };
+        Ok(ObjectInfo {
+            key: key.to_string(),
+            size,
+            modified,
+            metadata,
+        })
+    }
+
+    /// Moves the finished upload at `temp` into place as `key`
+    pub async fn commit(
+        &self,
+        bucket: &str,
+        key: &str,
+        temp: &Path,
+        metadata: Metadata,
+    ) -> Result<ObjectInfo, ApiError> {
+        check_key(key)?;
+        let path = self.object_path(bucket, key);
+
+        if key.ends_with('/') {
+            if fs::metadata(temp).await?.len() > 0 {
+                return Err(ApiError::bad_request(
+                    "InvalidArgument",
+                    "A key ending with / is a directory and cannot have content",
+                ));
+            }
+            fs::remove_file(temp).await?;
+            fs::create_dir_all(&path).await.map_err(|_| conflict())?;
+            let modified = SystemTime::now();
+            self.write_metadata(
+                bucket,
+                key,
+                &StoredMetadata {
+                    size: 0,
+                    modified: nanos(modified),
+                    metadata: metadata.clone(),
+                },
+            )
+            .await?;
+            return Ok(ObjectInfo {
+                key: key.to_string(),
+                size: 0,
+                modified,
+                metadata,
+            });
+        }
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).await.map_err(|_| conflict())?;
+        }
+        if fs::metadata(&path).await.is_ok_and(|stat| stat.is_dir()) {
+            return Err(conflict());
+        }
+        fs::rename(temp, &path).await?;
+
+        let stat = fs::metadata(&path).await?;
+        let modified = stat.modified()?;
+        self.write_metadata(
+            bucket,
+            key,
+            &StoredMetadata {
+                size: stat.len(),
+                modified: nanos(modified),
+                metadata: metadata.clone(),
+            },
+        )
+        .await?;
+        Ok(ObjectInfo {
+            key: key.to_string(),
+            size: stat.len(),
+            modified,
+            metadata,
+        })
+    }
+
+    /// Copies the content of an object to a new temporary file
+    pub async fn copy_to_temp(&self, bucket: &str, key: &str) -> Result<PathBuf, ApiError> {
+        let (temp, file) = self.temp_file().await?;
+        drop(file);
+        if !key.ends_with('/') {
+            if let Err(e) = fs::copy(self.object_path(bucket, key), &temp).await {
+                let _ = fs::remove_file(&temp).await;
+                return Err(e.into());
+            }
+        }
+        Ok(temp)
+    }
+
+    /// Deletes an object, succeeding when there is none like S3 does
+    pub async fn delete(&self, bucket: &str, key: &str) -> Result<(), ApiError> {
+        check_key(key)?;
+        let path = self.object_path(bucket, key);
+        let is_object = match fs::metadata(&path).await {
+            Ok(stat) => stat.is_dir() == key.ends_with('/'),
+            Err(_) => false,
+        };
+        if !is_object {
+            return Ok(());
+        }
+
+        if !key.ends_with('/') {
+            fs::remove_file(&path).await?;
+        }
+        match fs::remove_file(self.metadata_path(bucket, key)).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+
+        // Directories left empty go too, so the prefix disappears like on
+        // S3, unless they are markers in their own right
+        let mut dir = if key.ends_with('/') {
+            key.trim_end_matches('/')
+        } else {
+            parent(key)
+        };
+        while !dir.is_empty() {
+            let marker = format!("{}/", dir);
+            if fs::try_exists(self.metadata_path(bucket, &marker))
+                .await
+                .unwrap_or(true)
+            {
+                break;
+            }
+            if fs::remove_dir(self.bucket_dir(bucket).join(dir))
+                .await
+                .is_err()
+            {
+                break;
+            }
+            let _ = fs::remove_dir(self.system_dir().join("meta").join(bucket).join(dir)).await;
+            dir = parent(dir);
+        }
+        Ok(())
+    }
+
+    /// The files and directories in the directory `dir` of a bucket as
+    /// keys, directories ending with `/`, in reverse S3 order. Symlinks
+    /// are skipped so a walk never leaves the bucket.
+    async fn dir_keys(&self, bucket: &str, dir: &str) -> Result<Vec<String>, ApiError> {
+        let mut entries = match fs::read_dir(self.bucket_dir(bucket).join(dir)).await {
+            Ok(entries) => entries,
+            Err(e) if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::NotADirectory) => {
+                return Ok(Vec::new())
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let mut keys = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            let Ok(file_type) = entry.file_type().await else {
+                continue;
+            };
+            if file_type.is_dir() {
+                keys.push(format!("{}{}/", dir, name));
+            } else if file_type.is_file() {
+                keys.push(format!("{}{}", dir, name));
+            }
+        }
+        // A directory sorts by its name and `/`, so its whole subtree falls
+        // between the right siblings
+        keys.sort_by(|a, b| b.cmp(a));
+        Ok(keys)
+    }
+
+    /// Lists keys like ListObjects: those after `after`, with the ones
+    /// sharing a prefix up to `delimiter` rolled up into common prefixes.
+    /// The bucket is walked in S3's order from `after` and only until the
+    /// page is full, without entering directories rolled up as a whole.
+    pub async fn list(
+        &self,
+        bucket: &str,
+        prefix: &str,
+        delimiter: Option<&str>,
+        after: &str,
+        max_keys: usize,
+    ) -> Result<Listing, ApiError> {
+        check_prefix(prefix)?;
+        let delimiter = delimiter.filter(|d| !d.is_empty());
+        let common_prefix = |key: &str| {
+            delimiter.and_then(|delimiter| {
+                key[prefix.len()..]
+                    .find(delimiter)
+                    .map(|i| key[..prefix.len() + i + delimiter.len()].to_string())
+            })
+        };
+        let mut listing = Listing::default();
+        let mut count = 0;
+
+        // Keys still to visit, the next one last
+        let mut pending = vec![prefix[..prefix.rfind('/').map_or(0, |i| i + 1)].to_string()];
+        while let Some(key) = pending.pop() {
+            let is_dir = key.is_empty() || key.ends_with('/');
+            // Everything in a directory sorts after it and before `after`
+            // unless `after` is inside it
+            let skipped = key.as_str() <= after && !(is_dir && after.starts_with(&key));
+            if skipped || !(key.starts_with(prefix) || prefix.starts_with(&key)) {
+                continue;
+            }
+
+            let common = key
+                .starts_with(prefix)
+                .then(|| common_prefix(&key))
+                .flatten();
+            if is_dir && common.is_none() {
+                pending.extend(self.dir_keys(bucket, &key).await?);
+                // Only directories created as markers are objects
+                let is_marker = !key.is_empty()
+                    && key.starts_with(prefix)
+                    && key.as_str() > after
+                    && fs::try_exists(self.metadata_path(bucket, &key))
+                        .await
+                        .unwrap_or(false);
+                if !is_marker {
+                    continue;
+                }
+            }
+
+            if let Some(common) = &common {
+                if listing.prefixes.last() == Some(common) || common.as_str() <= after {
+                    continue;
+                }
+            } else if !key.starts_with(prefix) {
+                continue;
+            }
+            if count == max_keys {
+                listing.truncated = true;
+                break;
+            }
+
+            match common {
+                Some(common) => {
+                    listing.last = Some(common.clone());
+                    listing.prefixes.push(common);
+                }
+                None => {
+                    // Gone since the directory was read
+                    let Ok(object) = self.head(bucket, &key).await else {
+                        continue;
+                    };
+                    listing.last = Some(key);
+                    listing.objects.push(object);
+                }
+            }
+            count += 1;
+        }
+        Ok(listing)
+    }
+
+    pub async fn create_upload(&self, upload: &Upload) -> Result<String, ApiError> {
+        check_key(&upload.key)?;
+        let id = random_id();
+        let dir = self.upload_dir(&id);
+        fs::create_dir(&dir).await?;
+        let data = serde_json::to_vec(upload).map_err(|e| ApiError::internal(&e.to_string()))?;
+        fs::write(dir.join("upload.json"), data).await?;
+        Ok(id)
+    }
+
+    pub async fn upload(&self, id: &str) -> Result<Upload, ApiError> {
+        if id.is_empty() || !id.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(no_such_upload());
+        }
+        let data = fs::read(self.upload_dir(id).join("upload.json"))
+            .await
+            .map_err(|_| no_such_upload())?;
+        serde_json::from_slice(&data).map_err(|e| ApiError::internal(&e.to_string()))
+    }
+
+    /// Keeps the part at `temp` as part `number` of the upload, replacing
+    /// an earlier upload of the same part
+    pub async fn commit_part(
+        &self,
+        id: &str,
+        number: u32,
+        temp: &Path,
+        etag: &str,
+    ) -> Result<(), ApiError> {
+        let dir = self.upload_dir(id);
+        fs::rename(temp, dir.join(number.to_string())).await?;
+        fs::write(dir.join(format!("{}.etag", number)), etag).await?;
+        Ok(())
+    }
+
+    /// Joins the parts, given as numbers and ETags, into the object
+    pub async fn complete_upload(
+        &self,
+        id: &str,
+        parts: &[(u32, String)],
+    ) -> Result<ObjectInfo, ApiError> {
+        let upload = self.upload(id).await?;
+        let dir = self.upload_dir(id);
+        if parts.is_empty() {
+            return Err(ApiError::bad_request(
+                "MalformedXML",
+                "The XML you provided lists no parts",
+            ));
+        }
+        if parts.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+            return Err(ApiError::bad_request(
+                "InvalidPartOrder",
+                "The list of parts was not in ascending order.",
+            ));
+        }
+
+        let invalid_part = || {
+            ApiError::bad_request(
+                "InvalidPart",
+                "One or more of the specified parts could not be found.",
+            )
+        };
+        let mut digests = Vec::new();
+        for (i, (number, etag)) in parts.iter().enumerate() {
+            let stored = fs::read_to_string(dir.join(format!("{}.etag", number)))
+                .await
+                .map_err(|_| invalid_part())?;
+            if stored != etag.trim_matches('"') {
+                return Err(invalid_part());
+            }
+            let size = fs::metadata(dir.join(number.to_string())).await?.len();
+            if size < MIN_PART_SIZE && i + 1 < parts.len() {
+                return Err(ApiError::bad_request(
+                    "EntityTooSmall",
+                    "Your proposed upload is smaller than the minimum allowed object size.",
+                ));
+            }
+            digests.extend(hex::decode(&stored).map_err(|_| invalid_part())?);
+        }
+
+        let (temp, mut file) = self.temp_file().await?;
+        for (number, _) in parts {
+            let mut part = fs::File::open(dir.join(number.to_string())).await?;
+            if let Err(e) = tokio::io::copy(&mut part, &mut file).await {
+                let _ = fs::remove_file(&temp).await;
+                return Err(e.into());
+            }
+        }
+        file.flush().await?;
+        drop(file);
+
+        let metadata = Metadata {
+            etag: format!("{:x}-{}", md5::compute(&digests), parts.len()),
+            ..upload.metadata
+        };
+        let result = self
+            .commit(&upload.bucket, &upload.key, &temp, metadata)
+            .await;
+        if result.is_err() {
+            let _ = fs::remove_file(&temp).await;
+        } else {
+            fs::remove_dir_all(dir).await?;
+        }
+        result
+    }
+
+    pub async fn abort_upload(&self, id: &str) -> Result<(), ApiError> {
+        self.upload(id).await?;
+        fs::remove_dir_all(self.upload_dir(id)).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+use crate::config::Config;
+use crate::utils::key;
+use anyhow::{bail, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use clap::ValueEnum;
+use http::{HeaderMap, HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Server-side encryption managed by the provider
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+pub enum SseAlgorithm {
+    /// SSE-S3, keys managed by the provider
+    #[value(name = "AES256")]
+    #[serde(rename = "AES256")]
+    Aes256,
+    /// SSE-KMS, keys managed by a key management service
+    #[value(name = "aws:kms")]
+    #[serde(rename = "aws:kms")]
+    AwsKms,
+}
+
+impl SseAlgorithm {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Aes256 => "AES256",
+            Self::AwsKms => "aws:kms",
+        }
+    }
+}
+
+/// Encryption options as given on the command line, before the defaults
+/// from the config are applied.
+#[derive(Debug, Clone, Default)]
+pub struct SseSettings {
+    pub algorithm: Option<SseAlgorithm>,
+    pub kms_key_id: Option<String>,
+    pub customer_key: Option<PathBuf>,
+    /// SSE-C key file of the object a copy reads, kept apart from the key
+    /// the copy is written with
+    pub customer_source_key: Option<PathBuf>,
+}
+
+impl SseSettings {
+    /// Resolves the settings into the encryption to use. Options given on
+    /// the command line replace the config defaults as a whole, so a
+    /// `--sse-c-key` is not combined with a default `sse = "AES256"`.
+    pub async fn resolve(self, config: &Config) -> Result<Encryption> {
+        let from_command_line =
+            self.algorithm.is_some() || self.kms_key_id.is_some() || self.customer_key.is_some();
+        let (algorithm, kms_key_id, customer_key) = if from_command_line {
+            (self.algorithm, self.kms_key_id, self.customer_key)
+        } else {
+            (
+                config.sse,
+                config.sse_kms_key_id.clone(),
+                config.sse_c_key.clone(),
+            )
+        };
+
+        // A KMS key only makes sense with SSE-KMS
+        let algorithm = match (algorithm, &kms_key_id) {
+            (None, Some(_)) => Some(SseAlgorithm::AwsKms),
+            (Some(SseAlgorithm::Aes256), Some(_)) => {
+                bail!("--sse-kms-key-id requires --sse aws:kms")
+            }
+            _ => algorithm,
+        };
+        if algorithm.is_some() && customer_key.is_some() {
+            bail!("SSE-C cannot be combined with --sse, choose one of them");
+        }
+
+        let customer_key = match customer_key {
+            Some(path) => Some(key::read_file(&path, "SSE-C key").await?),
+            None => None,
+        };
+
+        Ok(Encryption {
+            algorithm,
+            kms_key_id,
+            customer_key,
+        })
+    }
+}
+
+/// Server-side encryption applied to the requests of a client
+#[derive(Debug, Clone, Default)]
+pub struct Encryption {
+    pub algorithm: Option<SseAlgorithm>,
+    pub kms_key_id: Option<String>,
+    pub customer_key: Option<Vec<u8>>,
+}
+
+impl Encryption {
+    /// Headers for requests creating an object: PutObject, CreateMultipartUpload
+    /// and CopyObject.
+    pub fn write_headers(&self) -> Result<HeaderMap> {
+        let mut headers = self.customer_headers()?;
+        if let Some(algorithm) = self.algorithm {
+            headers.insert(
+                HeaderName::from_static("x-amz-server-side-encryption"),
+                HeaderValue::from_static(algorithm.as_str()),
+            );
+        }
+        if let Some(key_id) = &self.kms_key_id {
+            headers.insert(
+                HeaderName::from_static("x-amz-server-side-encryption-aws-kms-key-id"),
+                HeaderValue::from_str(key_id)?,
+            );
+        }
+        Ok(headers)
+    }
+
+    /// SSE-C headers, which S3 needs on every request touching the object's
+    /// data: GET, HEAD, PutObject and each UploadPart.
+    pub fn customer_headers(&self) -> Result<HeaderMap> {
+        match &self.customer_key {
+            Some(key) => customer_key_headers(key, "x-amz-server-side-encryption-customer-"),
+            None => Ok(HeaderMap::new()),
+        }
+    }
+}
+
+/// SSE-C headers for the source of a CopyObject, encrypted with `key`. The
+/// destination's key is sent separately with `Encryption::write_headers`.
+pub fn copy_source_headers(key: &[u8]) -> Result<HeaderMap> {
+    customer_key_headers(key, "x-amz-copy-source-server-side-encryption-customer-")
+}
+
+fn customer_key_headers(key: &[u8], prefix: &str) -> Result<HeaderMap> {
+    let key_md5 = md5::compute(key);
+    let values = [
+        ("algorithm", "AES256".to_string()),
+        ("key", BASE64.encode(key)),
+        ("key-md5", BASE64.encode(key_md5.as_ref())),
+    ];
+    let mut headers = HeaderMap::new();
+    for (name, value) in values {
+        headers.insert(
+            HeaderName::from_bytes(format!("{}{}", prefix, name).as_bytes())?,
+            HeaderValue::from_str(&value)?,
+        );
+    }
+    Ok(headers)
+}
@@ -0,0 +1,42 @@
+use anyhow::{anyhow, Result};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+/// Parses a human readable duration (e.g., 90, 30s, 15m, 1h, 7d, 2w) into seconds.
+pub fn parse_duration(s: &str) -> Result<u64> {
+    let s = s.trim().to_lowercase();
+
+    if s.is_empty() {
+        return Err(anyhow!("Duration cannot be empty"));
+    }
+
+    let (num_part, multiplier) = match s.chars().last().unwrap() {
+        's' => (&s[..s.len() - 1], 1),
+        'm' => (&s[..s.len() - 1], 60),
+        'h' => (&s[..s.len() - 1], 60 * 60),
+        'd' => (&s[..s.len() - 1], 24 * 60 * 60),
+        'w' => (&s[..s.len() - 1], 7 * 24 * 60 * 60),
+        _ => (&s[..], 1),
+    };
+
+    let num = match u64::from_str(num_part) {
+        Ok(n) => n,
+        Err(_) => return Err(anyhow!("Invalid duration: {}", s)),
+    };
+
+    num.checked_mul(multiplier)
+        .ok_or_else(|| anyhow!("Duration out of range: {}", s))
+}
+
+/// Parses an `Expires` header value, given either as an HTTP date or as a
+/// duration from now (e.g., 7d), into an HTTP date.
+pub fn parse_expires(s: &str) -> Result<String> {
+    if let Ok(secs) = parse_duration(s) {
+        let expires = SystemTime::now() + Duration::from_secs(secs);
+        return Ok(httpdate::fmt_http_date(expires));
+    }
+
+    httpdate::parse_http_date(s.trim())
+        .map(httpdate::fmt_http_date)
+        .map_err(|_| anyhow!("Invalid expiration date or duration: {}", s))
+}
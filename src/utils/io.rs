@@ -0,0 +1,18 @@
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Reads until `buffer` is full or the reader is exhausted, returning the
+/// number of bytes read. A single `read` may return less than requested.
+pub async fn read_full<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    buffer: &mut [u8],
+) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]).await? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
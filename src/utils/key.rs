@@ -0,0 +1,34 @@
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use std::path::Path;
+
+/// Length of the AES-256 keys read from key files
+pub const KEY_LENGTH: usize = 32;
+
+/// Reads a key file holding 32 raw bytes, or the key encoded as base64 or
+/// hex. `label` names the key in error messages, e.g. `SSE-C key`.
+pub async fn read_file(path: &Path, label: &str) -> Result<Vec<u8>> {
+    let content = tokio::fs::read(path)
+        .await
+        .with_context(|| format!("Failed to read {} {}", label, path.display()))?;
+    if content.len() == KEY_LENGTH {
+        return Ok(content);
+    }
+
+    let text = String::from_utf8_lossy(&content);
+    let text = text.trim();
+    let key = hex::decode(text)
+        .ok()
+        .or_else(|| BASE64.decode(text).ok())
+        .filter(|key| key.len() == KEY_LENGTH);
+    match key {
+        Some(key) => Ok(key),
+        None => bail!(
+            "{} {} must hold {} bytes, raw or as base64 or hex",
+            label,
+            path.display(),
+            KEY_LENGTH
+        ),
+    }
+}
@@ -0,0 +1,15 @@
+use anyhow::{anyhow, Result};
+
+/// Parses a `key=value` pair, as used by repeatable options like `--meta`.
+pub fn parse_key_value(s: &str) -> Result<(String, String)> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Expected KEY=VALUE, got `{}`", s))?;
+    let key = key.trim();
+
+    if key.is_empty() {
+        return Err(anyhow!("Key cannot be empty in `{}`", s));
+    }
+
+    Ok((key.to_string(), value.to_string()))
+}
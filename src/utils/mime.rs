@@ -0,0 +1,69 @@
+use crate::utils::io::read_full;
+use std::collections::BTreeMap;
+use std::path::Path;
+use tokio::fs;
+
+pub const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// Number of leading bytes inspected when sniffing file contents
+const SNIFF_LEN: usize = 8192;
+
+/// Guesses the MIME type of a file, first from custom extension mappings,
+/// then from the extension itself and finally from its magic bytes.
+pub async fn detect_content_type(path: &Path, custom_types: &BTreeMap<String, String>) -> String {
+    if let Some(content_type) = content_type_from_name(path, custom_types) {
+        return content_type;
+    }
+
+    match read_head(path).await {
+        Some(head) => sniff_content_type(&head).to_string(),
+        None => DEFAULT_CONTENT_TYPE.to_string(),
+    }
+}
+
+/// Guesses the MIME type from the extension alone, for content that
+/// cannot be read twice like stdin.
+pub fn content_type_from_name(
+    path: &Path,
+    custom_types: &BTreeMap<String, String>,
+) -> Option<String> {
+    if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
+        if let Some(content_type) = custom_types.get(&ext.to_lowercase()) {
+            return Some(content_type.clone());
+        }
+    }
+
+    mime_guess::from_path(path)
+        .first()
+        .map(|mime| mime.essence_str().to_string())
+}
+
+/// Detects a MIME type from the leading bytes of some content.
+pub fn sniff_content_type(head: &[u8]) -> &'static str {
+    if let Some(kind) = infer::get(head) {
+        return kind.mime_type();
+    }
+
+    if !head.is_empty() && !head.contains(&0) && is_utf8_prefix(head) {
+        "text/plain"
+    } else {
+        DEFAULT_CONTENT_TYPE
+    }
+}
+
+/// Like `str::from_utf8`, but tolerates a multi-byte character cut off at
+/// the end of the sniffed window.
+fn is_utf8_prefix(bytes: &[u8]) -> bool {
+    match std::str::from_utf8(bytes) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    }
+}
+
+async fn read_head(path: &Path) -> Option<Vec<u8>> {
+    let mut file = fs::File::open(path).await.ok()?;
+    let mut head = vec![0; SNIFF_LEN];
+    let filled = read_full(&mut file, &mut head).await.ok()?;
+    head.truncate(filled);
+    Some(head)
+}
@@ -1,3 +1,12 @@
 pub mod colors;
+pub mod duration;
+pub mod io;
+pub mod key;
+pub mod kv;
+pub mod mime;
+pub mod output;
 pub mod progress;
+pub mod server;
 pub mod size;
+pub mod throttle;
+pub mod xml;
@@ -0,0 +1,59 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io;
+use std::path::Path;
+
+/// Output format for commands that can print machine-readable results
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+/// Format of bucket configuration files such as lifecycle or CORS rules
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RulesFormat {
+    Toml,
+    Json,
+}
+
+impl RulesFormat {
+    /// Files ending in `.json` are JSON, anything else is TOML.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("json") => Self::Json,
+            _ => Self::Toml,
+        }
+    }
+
+    /// Renders `value` with a trailing newline.
+    pub fn render<T: Serialize>(self, value: &T) -> Result<String> {
+        Ok(match self {
+            Self::Toml => toml::to_string_pretty(value)?,
+            Self::Json => format!("{}\n", serde_json::to_string_pretty(value)?),
+        })
+    }
+
+    pub async fn read_file<T: DeserializeOwned>(path: &Path) -> Result<T> {
+        let content = tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let value = match Self::from_path(path) {
+            Self::Toml => toml::from_str(&content).map_err(anyhow::Error::from),
+            Self::Json => serde_json::from_str(&content).map_err(anyhow::Error::from),
+        };
+        value.with_context(|| format!("Failed to parse {}", path.display()))
+    }
+}
+
+/// Whether `err` comes from writing to a pipe whose reader went away, e.g.
+/// `| head`, which means the output is no longer wanted rather than failed
+pub fn is_broken_pipe(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause
+            .downcast_ref::<io::Error>()
+            .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe)
+    })
+}
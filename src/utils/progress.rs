@@ -1,4 +1,9 @@
 use indicatif::{ProgressBar, ProgressStyle};
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, ReadBuf};
 
 pub enum ProgressType {
     Download,
@@ -53,6 +58,46 @@ pub fn create_upload_progress_bar(size: u64, prefix: &str) -> ProgressBar {
     create_progress_bar(size, prefix, ProgressType::Upload)
 }
 
+/// Spinner counting the bytes of a transfer whose total size is unknown
+pub fn create_stream_progress_bar(prefix: &str) -> ProgressBar {
+    let pb = ProgressBar::new_spinner();
+    pb.set_style(
+        ProgressStyle::default_spinner()
+            .template("{spinner:.red} {bytes} [{bytes_per_sec}] {prefix}")
+            .unwrap(),
+    );
+    pb.set_prefix(prefix.to_string());
+    pb.enable_steady_tick(Duration::from_millis(100));
+    pb
+}
+
 pub fn create_list_progress_bar(total: u64) -> ProgressBar {
     create_progress_bar(total, "", ProgressType::List)
 }
+
+/// Moves a progress bar forward as bytes are read from `inner`, for
+/// uploads where the bytes sent differ from the bytes of the file.
+pub struct ProgressReader<R> {
+    inner: R,
+    pb: ProgressBar,
+}
+
+impl<R> ProgressReader<R> {
+    pub fn new(inner: R, pb: ProgressBar) -> Self {
+        Self { inner, pb }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for ProgressReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let before = buf.filled().len();
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        this.pb.inc((buf.filled().len() - before) as u64);
+        Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,156 @@
+//! Pieces shared by the HTTP servers s3mgr can run
+
+use crate::utils::colors;
+use http::header::{AsHeaderName, CONTENT_TYPE};
+use http::{HeaderMap, HeaderValue, Method, Response, StatusCode, Uri};
+use hyper::Body;
+use percent_encoding::{percent_decode_str, AsciiSet, NON_ALPHANUMERIC};
+
+/// Characters left as they are when a key goes into a URL path, like the
+/// links of a listing
+pub const LINK: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~')
+    .remove(b'/');
+
+/// A `Range` header resolved against the size of an object
+pub enum Range {
+    /// The first and last byte to send
+    Bytes(u64, u64),
+    /// The range starts past the end of the object
+    Unsatisfiable,
+}
+
+/// Parses a single `bytes=` range. Ranges it does not understand are
+/// ignored and the whole object is sent.
+pub fn parse_range(value: &str, size: u64) -> Option<Range> {
+    let (first, last) = value.strip_prefix("bytes=")?.split_once('-')?;
+    let (first, last) = match (first.trim(), last.trim()) {
+        ("", suffix) => {
+            let suffix: u64 = suffix.parse().ok()?;
+            (size.saturating_sub(suffix), size.checked_sub(1)?)
+        }
+        (first, "") => (first.parse().ok()?, size.saturating_sub(1)),
+        (first, last) => {
+            let first: u64 = first.parse().ok()?;
+            let last: u64 = last.parse().ok()?;
+            if last < first {
+                return None;
+            }
+            (first, last.min(size.saturating_sub(1)))
+        }
+    };
+    if first >= size {
+        return Some(Range::Unsatisfiable);
+    }
+    Some(Range::Bytes(first, last))
+}
+
+/// Whether an `If-Match` or `If-None-Match` list names `etag`, with or
+/// without quotes
+pub fn etag_matches(list: &str, etag: &str) -> bool {
+    let etag = etag.trim_matches('"');
+    list.split(',')
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == "*" || tag.trim_matches('"') == etag)
+}
+
+/// Whether a relative path, or key, has no empty, `.` or `..` segment
+/// apart from a trailing `/`, so joining it to a directory stays inside.
+pub fn is_plain_path(path: &str) -> bool {
+    let name = path.strip_suffix('/').unwrap_or(path);
+    name.is_empty()
+        || !name
+            .split('/')
+            .any(|segment| matches!(segment, "" | "." | ".."))
+}
+
+/// The decoded path of a request without its leading `/`, or `None` when
+/// it is not valid UTF-8 or not a plain path. S3 URLs are resolved like
+/// any other, so such a path could reach keys outside the prefix a server
+/// exposes.
+pub fn request_path(uri: &Uri) -> Option<String> {
+    let path = percent_decode_str(uri.path()).decode_utf8().ok()?;
+    let path = path.strip_prefix('/').unwrap_or(&path);
+    is_plain_path(path).then(|| path.to_string())
+}
+
+/// A header of a request, when it is there and readable as text
+pub fn header<K: AsHeaderName>(headers: &HeaderMap, name: K) -> Option<&str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+/// A plain text response, for errors of servers that do not speak XML
+pub fn text(status: StatusCode, message: &str) -> Response<Body> {
+    let mut response = Response::new(Body::from(format!("{}\n", message)));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; charset=utf-8"),
+    );
+    response
+}
+
+/// Prints the access log line of a request, colored by its status
+pub fn log_request(method: &Method, path: &str, status: StatusCode) {
+    let line = format!("{} {} {}", status.as_u16(), method, path);
+    if status.is_server_error() {
+        println!("{}", colors::fmt_error(&line));
+    } else if status.is_client_error() {
+        println!("{}", colors::fmt_warn(&line));
+    } else {
+        println!("{}", line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(uri: &str) -> Option<String> {
+        request_path(&uri.parse().unwrap())
+    }
+
+    #[test]
+    fn decodes_request_paths() {
+        assert_eq!(path("/").as_deref(), Some(""));
+        assert_eq!(path("/docs/").as_deref(), Some("docs/"));
+        assert_eq!(path("/docs/a%20b.txt").as_deref(), Some("docs/a b.txt"));
+        assert_eq!(path("/..a/b..").as_deref(), Some("..a/b.."));
+    }
+
+    #[test]
+    fn plain_paths_stay_inside_their_directory() {
+        for path in ["", "a.txt", "docs/", "docs/a.txt", "..a/b.."] {
+            assert!(is_plain_path(path), "{}", path);
+        }
+        for path in [
+            "/etc/passwd",
+            "../a.txt",
+            "docs/../../a.txt",
+            "docs//a.txt",
+            "./",
+        ] {
+            assert!(!is_plain_path(path), "{}", path);
+        }
+    }
+
+    #[test]
+    fn rejects_paths_leaving_their_prefix() {
+        for uri in [
+            "/../secret/s.txt",
+            "/%2e%2e/secret/s.txt",
+            "/docs/%2E%2E/%2e%2e/secret/",
+            "/docs%2F..%2Fsecret",
+            "/docs/./a.txt",
+            "/docs//a.txt",
+            "//secret",
+            "/docs/..",
+            "/%ff",
+        ] {
+            assert_eq!(path(uri), None, "{}", uri);
+        }
+    }
+}
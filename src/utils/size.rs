@@ -37,7 +37,7 @@ pub fn parse_human_size(s: &str) -> Result<usize> {
         'K' => num * 1024.0,
         'M' => num * 1024.0 * 1024.0,
         'G' => num * 1024.0 * 1024.0 * 1024.0,
-        'B' | _ => num,
+        _ => num,
     };
 
     if bytes < 0.0 || bytes > (usize::MAX as f64) {
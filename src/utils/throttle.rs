@@ -0,0 +1,205 @@
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{Instant, Sleep};
+
+/// Most bytes a `Throttled` read or write passes on at once, so a transfer
+/// never runs far ahead of what it has paid for
+const SLICE: usize = 16 * 1024;
+
+struct BucketState {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Token bucket shared by every transfer of a command, so the configured
+/// rate is a limit on the total throughput rather than per file.
+pub struct RateLimiter {
+    bytes_per_sec: f64,
+    state: Mutex<BucketState>,
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_sec: usize) -> Self {
+        let bytes_per_sec = bytes_per_sec as f64;
+        Self {
+            bytes_per_sec,
+            state: Mutex::new(BucketState {
+                tokens: bytes_per_sec,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes `bytes` tokens from the bucket, sleeping until the debt is paid
+    /// off when the bucket runs dry.
+    pub async fn acquire(&self, bytes: usize) {
+        let wait = self.take(bytes);
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Takes `bytes` tokens from the bucket and returns how long the caller
+    /// has to wait before the debt is paid off
+    fn take(&self, bytes: usize) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * self.bytes_per_sec).min(self.bytes_per_sec);
+        state.last_refill = now;
+        state.tokens -= bytes as f64;
+
+        if state.tokens < 0.0 {
+            Duration::from_secs_f64(-state.tokens / self.bytes_per_sec)
+        } else {
+            Duration::ZERO
+        }
+    }
+}
+
+/// Reader or writer that pays the limiter for the bytes passing through it
+/// as they pass, in slices of at most `SLICE` bytes. Without a limiter it
+/// is a plain pass-through.
+pub struct Throttled<T> {
+    inner: T,
+    limiter: Option<Arc<RateLimiter>>,
+    delay: Option<Pin<Box<Sleep>>>,
+}
+
+impl<T> Throttled<T> {
+    pub fn new(inner: T, limiter: Option<Arc<RateLimiter>>) -> Self {
+        Self {
+            inner,
+            limiter,
+            delay: None,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Waits out the debt of the previous slice
+    fn poll_delay(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if let Some(delay) = &mut self.delay {
+            ready!(delay.as_mut().poll(cx));
+            self.delay = None;
+        }
+        Poll::Ready(())
+    }
+
+    fn pay(&mut self, bytes: usize) {
+        if let Some(limiter) = &self.limiter {
+            let wait = limiter.take(bytes);
+            if !wait.is_zero() {
+                self.delay = Some(Box::pin(tokio::time::sleep(wait)));
+            }
+        }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Throttled<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_delay(cx));
+
+        let mut slice = [0; SLICE];
+        let len = buf.remaining().min(SLICE);
+        let mut slice = ReadBuf::new(&mut slice[..len]);
+        ready!(Pin::new(&mut this.inner).poll_read(cx, &mut slice))?;
+
+        buf.put_slice(slice.filled());
+        this.pay(slice.filled().len());
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Throttled<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_delay(cx));
+
+        let len = buf.len().min(SLICE);
+        let n = ready!(Pin::new(&mut this.inner).poll_write(cx, &buf[..len]))?;
+        this.pay(n);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test(start_paused = true)]
+    async fn acquire_waits_for_the_debt() {
+        let limiter = RateLimiter::new(1000);
+
+        // The bucket starts full, so the first second's worth is free
+        let start = Instant::now();
+        limiter.acquire(1000).await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        limiter.acquire(500).await;
+        assert_eq!(start.elapsed(), Duration::from_millis(500));
+
+        limiter.acquire(2000).await;
+        assert_eq!(start.elapsed(), Duration::from_millis(2500));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn acquire_refills_while_idle() {
+        let limiter = RateLimiter::new(1000);
+        limiter.acquire(1000).await;
+
+        // Idle time refills the bucket, but never beyond one second's worth
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        let start = Instant::now();
+        limiter.acquire(1000).await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        limiter.acquire(250).await;
+        assert_eq!(start.elapsed(), Duration::from_millis(250));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn throttled_io_pays_as_bytes_pass() {
+        let limiter = Arc::new(RateLimiter::new(SLICE));
+        limiter.acquire(SLICE).await;
+
+        let start = Instant::now();
+        let data = vec![7; 4 * SLICE];
+        let mut reader = Throttled::new(&data[..], Some(limiter.clone()));
+        let mut read = Vec::new();
+        reader.read_to_end(&mut read).await.unwrap();
+        assert_eq!(read, data);
+        // Reading on to the end waits out the debt of the last slice too
+        assert_eq!(start.elapsed(), Duration::from_secs(4));
+
+        let mut writer = Throttled::new(Vec::new(), Some(limiter));
+        writer.write_all(&data).await.unwrap();
+        assert_eq!(writer.into_inner(), data);
+        // The debt of the last slice is left to the next transfer
+        assert_eq!(start.elapsed(), Duration::from_secs(7));
+    }
+}
@@ -0,0 +1,555 @@
+//! A WebDAV server fronting the bucket, so file managers can mount it.
+//! Collections are prefixes, and MKCOL creates the same directory markers
+//! as `md`.
+//!
+//! There is no authentication at all: every client that reaches the
+//! address can read, write and delete the keys under the prefix with the
+//! credentials of the configuration.
+
+use crate::gateway::Gateway;
+use crate::s3::{dir_prefix, ObjectMetadata, PutOptions, S3Client};
+use crate::utils::server::{self, header, text, LINK};
+use crate::utils::{colors, mime, xml};
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::OsRng;
+use http::header::{ALLOW, CONTENT_LENGTH, CONTENT_TYPE};
+use http::{HeaderMap, HeaderValue, Request, Response, StatusCode, Uri};
+use httpdate::HttpDate;
+use hyper::body::{Bytes, HttpBody};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Server};
+use percent_encoding::utf8_percent_encode;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::io;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use std::time::SystemTime;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use tokio::io::{AsyncRead, ReadBuf};
+
+const METHODS: &str = "OPTIONS, GET, HEAD, PUT, DELETE, PROPFIND, MKCOL, COPY, MOVE, LOCK, UNLOCK";
+
+struct State {
+    gateway: Gateway,
+    /// Bytes sent with each part of an upload
+    chunk_size: usize,
+    /// Extensions mapped to Content-Types, from the configuration
+    content_types: BTreeMap<String, String>,
+}
+
+/// What a path of the server stands for
+enum Resource {
+    /// A prefix, with or without a directory marker
+    Collection,
+    File(Box<ObjectMetadata>),
+}
+
+/// A resource as listed in a PROPFIND answer
+struct Entry {
+    href: String,
+    collection: bool,
+    size: u64,
+    modified: Option<String>,
+    etag: Option<String>,
+    content_type: Option<String>,
+}
+
+/// Reads a request body as it arrives, for `S3Client::upload_reader`
+struct BodyReader {
+    body: Body,
+    chunk: Bytes,
+}
+
+impl AsyncRead for BodyReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        while self.chunk.is_empty() {
+            match ready!(Pin::new(&mut self.body).poll_data(cx)) {
+                Some(Ok(chunk)) => self.chunk = chunk,
+                Some(Err(e)) => return Poll::Ready(Err(io::Error::other(e))),
+                None => return Poll::Ready(Ok(())),
+            }
+        }
+        let length = self.chunk.len().min(buf.remaining());
+        let data = self.chunk.split_to(length);
+        buf.put_slice(&data);
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn status(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+fn xml_response(status: StatusCode, body: String) -> Response<Body> {
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("application/xml; charset=utf-8"),
+    );
+    response
+}
+
+fn href(path: &str) -> String {
+    format!("/{}", utf8_percent_encode(path, LINK))
+}
+
+/// Turns the ISO 8601 dates of a listing into the HTTP dates WebDAV uses
+fn http_date(iso: &str) -> Option<String> {
+    let time = OffsetDateTime::parse(iso, &Rfc3339).ok()?;
+    Some(HttpDate::from(SystemTime::from(time)).to_string())
+}
+
+/// Whether any key starts with `dir`, its directory marker included
+async fn has_keys(s3_client: &S3Client, dir: &str) -> anyhow::Result<bool> {
+    let (objects, _) = s3_client.list_page(dir, None).await?;
+    Ok(!objects.is_empty())
+}
+
+/// Looks up the object or prefix at `key`. The root is always there, even
+/// when nothing is under it yet.
+async fn resource(s3_client: &S3Client, key: &str, root: bool) -> anyhow::Result<Option<Resource>> {
+    if root {
+        return Ok(Some(Resource::Collection));
+    }
+    if !key.ends_with('/') {
+        if let Some(metadata) = s3_client.find_object(key).await? {
+            return Ok(Some(Resource::File(Box::new(metadata))));
+        }
+    }
+    Ok(has_keys(s3_client, &dir_prefix(key))
+        .await?
+        .then_some(Resource::Collection))
+}
+
+/// Deletes an object, or every key under a collection
+async fn delete(s3_client: &S3Client, key: &str, resource: &Resource) -> anyhow::Result<()> {
+    match resource {
+        Resource::File(_) => s3_client.delete(key).await,
+        Resource::Collection => {
+            for key in s3_client.list_objects_recursive(key).await? {
+                s3_client.delete(&key).await?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn render(entries: &[Entry]) -> String {
+    let mut body = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n",
+    );
+    for entry in entries {
+        body.push_str(&format!(
+            "<D:response><D:href>{}</D:href><D:propstat><D:prop>",
+            xml::escape(&entry.href)
+        ));
+        if entry.collection {
+            body.push_str("<D:resourcetype><D:collection/></D:resourcetype>");
+        } else {
+            body.push_str(&format!(
+                "<D:resourcetype/><D:getcontentlength>{}</D:getcontentlength>",
+                entry.size
+            ));
+        }
+        let properties = [
+            ("getlastmodified", &entry.modified),
+            ("getetag", &entry.etag),
+            ("getcontenttype", &entry.content_type),
+        ];
+        for (name, value) in properties {
+            if let Some(value) = value {
+                body.push_str(&format!("<D:{name}>{}</D:{name}>", xml::escape(value)));
+            }
+        }
+        body.push_str("</D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>\n");
+    }
+    body.push_str("</D:multistatus>\n");
+    body
+}
+
+async fn propfind(
+    state: &State,
+    headers: &HeaderMap,
+    path: &str,
+) -> anyhow::Result<Response<Body>> {
+    let s3_client = state.gateway.s3_client();
+    let key = state.gateway.key(path);
+    let Some(resource) = resource(s3_client, &key, path.is_empty()).await? else {
+        return Ok(status(StatusCode::NOT_FOUND));
+    };
+
+    let dir = match resource {
+        Resource::File(metadata) => {
+            let entry = Entry {
+                href: href(path),
+                collection: false,
+                size: metadata.size,
+                modified: metadata.last_modified,
+                etag: metadata.e_tag,
+                content_type: metadata.content_type,
+            };
+            return Ok(xml_response(StatusCode::MULTI_STATUS, render(&[entry])));
+        }
+        Resource::Collection => dir_prefix(&key),
+    };
+    let collection_href = href(&dir_prefix(path));
+    let mut entries = vec![Entry {
+        href: collection_href.clone(),
+        collection: true,
+        size: 0,
+        modified: None,
+        etag: None,
+        content_type: None,
+    }];
+
+    // Depth infinity is answered like depth 1, listing whole trees would
+    // be far too slow on large buckets
+    if header(headers, "depth") != Some("0") {
+        let (prefixes, objects) = s3_client.list_dir(&dir).await?;
+        for prefix in prefixes {
+            entries.push(Entry {
+                href: format!(
+                    "{}{}",
+                    collection_href,
+                    utf8_percent_encode(&prefix[dir.len()..], LINK)
+                ),
+                collection: true,
+                size: 0,
+                modified: None,
+                etag: None,
+                content_type: None,
+            });
+        }
+        for object in objects {
+            let name = &object.key[dir.len()..];
+            entries.push(Entry {
+                href: format!("{}{}", collection_href, utf8_percent_encode(name, LINK)),
+                collection: false,
+                size: object.size,
+                modified: http_date(&object.last_modified),
+                etag: object.e_tag.clone(),
+                content_type: mime::content_type_from_name(Path::new(name), &state.content_types),
+            });
+        }
+    }
+    Ok(xml_response(StatusCode::MULTI_STATUS, render(&entries)))
+}
+
+async fn put(state: &State, request: Request<Body>, path: &str) -> anyhow::Result<Response<Body>> {
+    if path.is_empty() || path.ends_with('/') {
+        return Ok(status(StatusCode::METHOD_NOT_ALLOWED));
+    }
+    let s3_client = state.gateway.s3_client();
+    let key = state.gateway.key(path);
+    let existed = s3_client.find_object(&key).await?.is_some();
+
+    // File managers rarely send a useful Content-Type, so the name decides
+    // like it does for `up`
+    let content_type = mime::content_type_from_name(Path::new(&key), &state.content_types)
+        .or_else(|| header(request.headers(), "content-type").map(str::to_string));
+    let options = PutOptions {
+        content_type,
+        ..Default::default()
+    };
+    let mut reader = BodyReader {
+        body: request.into_body(),
+        chunk: Bytes::new(),
+    };
+    s3_client
+        .upload_reader(&mut reader, &key, state.chunk_size, &options, |_| {})
+        .await?;
+
+    Ok(status(if existed {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::CREATED
+    }))
+}
+
+async fn mkcol(state: &State, headers: &HeaderMap, path: &str) -> anyhow::Result<Response<Body>> {
+    if header(headers, "content-length").is_some_and(|length| length != "0") {
+        return Ok(status(StatusCode::UNSUPPORTED_MEDIA_TYPE));
+    }
+    let s3_client = state.gateway.s3_client();
+    let key = state.gateway.key(path);
+    if path.is_empty() || resource(s3_client, &key, false).await?.is_some() {
+        return Ok(status(StatusCode::METHOD_NOT_ALLOWED));
+    }
+    s3_client
+        .put_empty_object(&dir_prefix(&key), &PutOptions::default())
+        .await?;
+    Ok(status(StatusCode::CREATED))
+}
+
+async fn delete_resource(state: &State, path: &str) -> anyhow::Result<Response<Body>> {
+    // Deleting the root would empty the whole bucket or prefix
+    if path.is_empty() {
+        return Ok(status(StatusCode::FORBIDDEN));
+    }
+    let s3_client = state.gateway.s3_client();
+    let key = state.gateway.key(path);
+    match resource(s3_client, &key, false).await? {
+        Some(resource) => {
+            delete(s3_client, &key, &resource).await?;
+            Ok(status(StatusCode::NO_CONTENT))
+        }
+        None => Ok(status(StatusCode::NOT_FOUND)),
+    }
+}
+
+/// COPY, and MOVE as a copy followed by deleting the source
+async fn copy(
+    state: &State,
+    headers: &HeaderMap,
+    path: &str,
+    remove_source: bool,
+) -> anyhow::Result<Response<Body>> {
+    // An absolute URL or path, checked like the request path so a copy
+    // cannot leave the prefix. Parsed as a URI rather than a URL, which
+    // would resolve `..` before the check sees it.
+    let destination = header(headers, "destination")
+        .and_then(|destination| destination.parse::<Uri>().ok())
+        .and_then(|uri| server::request_path(&uri));
+    let Some(destination) = destination else {
+        return Ok(text(
+            StatusCode::BAD_REQUEST,
+            "Missing or invalid Destination",
+        ));
+    };
+    let overwrite = header(headers, "overwrite") != Some("F");
+
+    let s3_client = state.gateway.s3_client();
+    let source_key = state.gateway.key(path);
+    let Some(source) = resource(s3_client, &source_key, path.is_empty()).await? else {
+        return Ok(status(StatusCode::NOT_FOUND));
+    };
+    let destination_key = state.gateway.key(&destination);
+    // Copying into itself would never end, and overwriting a collection
+    // holding the source would delete the source first
+    let source_dir = dir_prefix(&source_key);
+    let destination_dir = dir_prefix(&destination_key);
+    if destination.is_empty()
+        || source_dir.starts_with(&destination_dir)
+        || destination_dir.starts_with(&source_dir)
+    {
+        return Ok(status(StatusCode::FORBIDDEN));
+    }
+
+    let existing = resource(s3_client, &destination_key, false).await?;
+    if let Some(existing) = &existing {
+        if !overwrite {
+            return Ok(status(StatusCode::PRECONDITION_FAILED));
+        }
+        delete(s3_client, &destination_key, existing).await?;
+    }
+
+    match source {
+        Resource::File(_) => {
+            let destination_key = destination_key.trim_end_matches('/');
+            s3_client
+                .copy_object_server_side(
+                    &source_key,
+                    destination_key,
+                    false,
+                    &PutOptions::default(),
+                )
+                .await?;
+            if remove_source {
+                s3_client.delete(&source_key).await?;
+            }
+        }
+        Resource::Collection if !remove_source && header(headers, "depth") == Some("0") => {
+            s3_client
+                .put_empty_object(&dir_prefix(&destination_key), &PutOptions::default())
+                .await?;
+        }
+        Resource::Collection => {
+            let keys = s3_client.list_objects_recursive(&source_dir).await?;
+            for key in &keys {
+                let destination = format!("{}{}", destination_dir, &key[source_dir.len()..]);
+                s3_client
+                    .copy_object_server_side(key, &destination, false, &PutOptions::default())
+                    .await?;
+            }
+            if remove_source {
+                for key in &keys {
+                    s3_client.delete(key).await?;
+                }
+            }
+        }
+    }
+
+    Ok(status(if existing.is_some() {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::CREATED
+    }))
+}
+
+/// Grants every lock without enforcing it. File managers mount a server
+/// read-only unless it supports locking, but nothing else would honour
+/// the locks on the bucket anyway.
+fn lock(headers: &HeaderMap, path: &str) -> Response<Body> {
+    // A refresh names the lock in the If header, e.g. `(<opaquelocktoken:…>)`
+    let token = header(headers, "if")
+        .and_then(|condition| {
+            let start = condition.find("<opaquelocktoken:")? + 1;
+            let end = start + condition[start..].find('>')?;
+            Some(condition[start..end].to_string())
+        })
+        .unwrap_or_else(|| {
+            let mut id = [0; 16];
+            OsRng.fill_bytes(&mut id);
+            let id = hex::encode(id);
+            format!(
+                "opaquelocktoken:{}-{}-{}-{}-{}",
+                &id[..8],
+                &id[8..12],
+                &id[12..16],
+                &id[16..20],
+                &id[20..]
+            )
+        });
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:prop xmlns:D=\"DAV:\"><D:lockdiscovery><D:activelock><D:locktype><D:write/></D:locktype><D:lockscope><D:exclusive/></D:lockscope><D:depth>infinity</D:depth><D:timeout>Second-3600</D:timeout><D:locktoken><D:href>{}</D:href></D:locktoken><D:lockroot><D:href>{}</D:href></D:lockroot></D:activelock></D:lockdiscovery></D:prop>\n",
+        xml::escape(&token),
+        xml::escape(&href(path))
+    );
+    let mut response = xml_response(StatusCode::OK, body);
+    if let Ok(value) = HeaderValue::from_str(&format!("<{}>", token)) {
+        response.headers_mut().insert("lock-token", value);
+    }
+    response
+}
+
+async fn route(state: &State, request: Request<Body>) -> anyhow::Result<Response<Body>> {
+    let Some(path) = server::request_path(request.uri()) else {
+        return Ok(text(StatusCode::BAD_REQUEST, "Invalid path"));
+    };
+    let headers = request.headers().clone();
+
+    match request.method().as_str() {
+        "OPTIONS" => {
+            let mut response = status(StatusCode::OK);
+            let headers = response.headers_mut();
+            headers.insert("dav", HeaderValue::from_static("1, 2"));
+            headers.insert("ms-author-via", HeaderValue::from_static("DAV"));
+            headers.insert(ALLOW, HeaderValue::from_static(METHODS));
+            headers.insert(CONTENT_LENGTH, 0.into());
+            Ok(response)
+        }
+        "GET" | "HEAD" => state.gateway.get(&request).await,
+        "PROPFIND" => propfind(state, &headers, &path).await,
+        "PUT" => put(state, request, &path).await,
+        "MKCOL" => mkcol(state, &headers, &path).await,
+        "DELETE" => delete_resource(state, &path).await,
+        "COPY" => copy(state, &headers, &path, false).await,
+        "MOVE" => copy(state, &headers, &path, true).await,
+        "LOCK" => Ok(lock(&headers, &path)),
+        "UNLOCK" => Ok(status(StatusCode::NO_CONTENT)),
+        _ => {
+            let mut response = status(StatusCode::METHOD_NOT_ALLOWED);
+            response
+                .headers_mut()
+                .insert(ALLOW, HeaderValue::from_static(METHODS));
+            Ok(response)
+        }
+    }
+}
+
+async fn handle(state: Arc<State>, request: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let response = match route(&state, request).await {
+        Ok(response) => response,
+        Err(e) => {
+            println!("{}", colors::fmt_error(&format!("{}: {}", path, e)));
+            text(StatusCode::BAD_GATEWAY, "The request to S3 failed")
+        }
+    };
+    server::log_request(&method, &path, response.status());
+    Ok(response)
+}
+
+/// Serves the keys under `prefix` over WebDAV at `address` until the
+/// process is stopped
+pub async fn run(
+    s3_client: S3Client,
+    prefix: &str,
+    upload_chunk_size: usize,
+    download_chunk_size: usize,
+    content_types: BTreeMap<String, String>,
+    address: SocketAddr,
+) -> anyhow::Result<()> {
+    let state = Arc::new(State {
+        gateway: Gateway::new(s3_client, prefix, download_chunk_size),
+        chunk_size: upload_chunk_size,
+        content_types,
+    });
+    let make_service = make_service_fn(move |_| {
+        let state = Arc::clone(&state);
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                handle(Arc::clone(&state), request)
+            }))
+        }
+    });
+    Server::try_bind(&address)?.serve(make_service).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gateway::unreachable_gateway;
+
+    #[tokio::test]
+    async fn refuses_paths_outside_the_prefix() {
+        let state = State {
+            gateway: unreachable_gateway(),
+            chunk_size: 1024,
+            content_types: BTreeMap::new(),
+        };
+
+        let requests = [
+            ("PUT", "/../secret/s.txt", None),
+            ("MKCOL", "/%2e%2e/secret/", None),
+            ("DELETE", "/a/../../secret/s.txt", None),
+            ("PROPFIND", "/a//b", None),
+            ("MOVE", "/a.txt", Some("/../secret/a.txt")),
+            (
+                "COPY",
+                "/a.txt",
+                Some("http://localhost/%2e%2e/secret/a.txt"),
+            ),
+        ];
+        for (method, uri, destination) in requests {
+            let mut request = Request::builder().method(method).uri(uri);
+            if let Some(destination) = destination {
+                request = request.header("destination", destination);
+            }
+            let response = route(&state, request.body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(
+                response.status(),
+                StatusCode::BAD_REQUEST,
+                "{} {}",
+                method,
+                uri
+            );
+        }
+    }
+}
@@ -0,0 +1,196 @@
+//! Runs s3mgr commands against `s3mgr serve` over a temporary directory,
+//! checking what ends up on the filesystem
+
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use std::{fs, thread};
+
+const BUCKET: &str = "test-bucket";
+
+static NEXT_DIR: AtomicUsize = AtomicUsize::new(0);
+
+/// A server of its own for every test, with the bucket under `root` and
+/// the s3mgr config under `home`
+struct Server {
+    child: Child,
+    dir: PathBuf,
+}
+
+impl Server {
+    fn start() -> Self {
+        let dir = std::env::temp_dir().join(format!(
+            "s3mgr-test-{}-{}",
+            std::process::id(),
+            NEXT_DIR.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("root").join(BUCKET)).unwrap();
+        fs::create_dir_all(dir.join("home")).unwrap();
+        fs::create_dir_all(dir.join("local")).unwrap();
+
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let child = Command::new(env!("CARGO_BIN_EXE_s3mgr"))
+            .args(["serve", "--port", &port.to_string()])
+            .args(["--access-key", "test", "--secret-key", "test-secret"])
+            .arg("--root")
+            .arg(dir.join("root"))
+            .env("HOME", dir.join("home"))
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let server = Self { child, dir };
+
+        let started = Instant::now();
+        while TcpStream::connect(("127.0.0.1", port)).is_err() {
+            assert!(
+                started.elapsed() < Duration::from_secs(10),
+                "the server did not start"
+            );
+            thread::sleep(Duration::from_millis(50));
+        }
+
+        server.s3mgr(&["config", "-a", "test", "-s", "test-secret", "-b", BUCKET]);
+        server.s3mgr(&[
+            "config",
+            "-e",
+            &format!("http://127.0.0.1:{}", port),
+            "--path-style",
+            "true",
+        ]);
+        server
+    }
+
+    /// Runs s3mgr in the local directory and returns what it printed,
+    /// without colors
+    fn s3mgr(&self, args: &[&str]) -> String {
+        let output = Command::new(env!("CARGO_BIN_EXE_s3mgr"))
+            .args(args)
+            .current_dir(self.local(""))
+            .env("HOME", self.dir.join("home"))
+            .env("NO_COLOR", "1")
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        assert!(
+            output.status.success(),
+            "s3mgr {} failed:\n{}{}",
+            args.join(" "),
+            stdout,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        without_escapes(&stdout)
+    }
+
+    /// A path in the directory s3mgr runs in
+    fn local(&self, path: &str) -> PathBuf {
+        self.dir.join("local").join(path)
+    }
+
+    /// The file an object of the bucket is stored as
+    fn stored(&self, key: &str) -> PathBuf {
+        self.dir.join("root").join(BUCKET).join(key)
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Drops the terminal escape sequences, which NO_COLOR leaves in part
+fn without_escapes(text: &str) -> String {
+    let mut plain = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
+
+/// Bytes that do not repeat within a part, so misplaced ranges show
+fn data(size: usize) -> Vec<u8> {
+    (0..size).map(|i| (i % 251) as u8).collect()
+}
+
+fn assert_same_file(a: &Path, b: &Path) {
+    let (a_data, b_data) = (fs::read(a).unwrap(), fs::read(b).unwrap());
+    assert!(
+        a_data == b_data,
+        "{} and {} differ",
+        a.display(),
+        b.display()
+    );
+}
+
+#[test]
+fn uploads_lists_and_downloads() {
+    let server = Server::start();
+    fs::write(server.local("notes.txt"), "hello from s3mgr\n").unwrap();
+
+    server.s3mgr(&["up", "notes.txt", "-d", "docs/"]);
+    assert_same_file(&server.local("notes.txt"), &server.stored("docs/notes.txt"));
+
+    assert!(server.s3mgr(&["ls", "docs/"]).contains("docs/notes.txt"));
+    assert_eq!(
+        server.s3mgr(&["cat", "docs/notes.txt"]),
+        "hello from s3mgr\n"
+    );
+    assert!(server.s3mgr(&["stat", "docs/notes.txt"]).contains("17"));
+
+    fs::create_dir(server.local("out")).unwrap();
+    server.s3mgr(&["dl", "docs/notes.txt", "out"]);
+    assert_same_file(&server.local("notes.txt"), &server.local("out/notes.txt"));
+}
+
+#[test]
+fn uploads_in_parts() {
+    let server = Server::start();
+    fs::write(server.local("big.bin"), data(12 * 1024 * 1024 + 3)).unwrap();
+
+    server.s3mgr(&["up", "big.bin", "-d", "big.bin", "-c", "5M"]);
+    assert_same_file(&server.local("big.bin"), &server.stored("big.bin"));
+
+    fs::create_dir(server.local("out")).unwrap();
+    server.s3mgr(&["dl", "big.bin", "out", "-c", "1M"]);
+    assert_same_file(&server.local("big.bin"), &server.local("out/big.bin"));
+}
+
+#[test]
+fn copies_moves_and_removes() {
+    let server = Server::start();
+    fs::write(server.local("a.txt"), "a").unwrap();
+    server.s3mgr(&["up", "a.txt", "-d", "dir/a.txt"]);
+
+    server.s3mgr(&[
+        "cp",
+        "dir/a.txt",
+        "dir/b.txt",
+        "--metadata-directive",
+        "COPY",
+    ]);
+    server.s3mgr(&["cp", "dir/a.txt", "dir/sub/c.txt"]);
+    server.s3mgr(&["mv", "dir/b.txt", "moved.txt"]);
+    assert_same_file(&server.local("a.txt"), &server.stored("dir/sub/c.txt"));
+    assert_same_file(&server.local("a.txt"), &server.stored("moved.txt"));
+    assert!(!server.stored("dir/b.txt").exists());
+
+    server.s3mgr(&["rm", "moved.txt"]);
+    assert!(!server.stored("moved.txt").exists());
+    server.s3mgr(&["rm", "-r", "dir/"]);
+    assert!(!server.stored("dir").exists());
+}